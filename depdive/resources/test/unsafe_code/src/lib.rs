@@ -0,0 +1,13 @@
+mod safe;
+
+pub unsafe fn read_raw(ptr: *const u8) -> u8 {
+    *ptr
+}
+
+pub fn read_first(bytes: &[u8]) -> u8 {
+    unsafe { read_raw(bytes.as_ptr()) }
+}
+
+pub struct Wrapper(*mut u8);
+
+unsafe impl Send for Wrapper {}
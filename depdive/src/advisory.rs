@@ -1,6 +1,6 @@
 //! This module abstracts interaction with rustsec advisory
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use git2::Repository;
 use rustsec::repository::git::DEFAULT_URL;
 use rustsec::{
@@ -8,9 +8,11 @@ use rustsec::{
     database::{Database, Query},
     package::Name,
 };
-use std::str::FromStr;
+use std::{env, path::Path, str::FromStr};
 use tempfile::tempdir;
 
+pub const ADVISORY_DB_ENV: &str = "DEPDIVE_ADVISORY_DB";
+
 pub struct AdvisoryLookup {
     db: Database,
 }
@@ -18,7 +20,31 @@ pub struct AdvisoryLookup {
 /// Constructs a local copy of rustsec advisory
 /// and facilitates querying for advisries in a specific version of a crate
 impl AdvisoryLookup {
+    /// Uses the local database in DEPDIVE_ADVISORY_DB if set,
+    /// otherwise clones the rustsec advisory repo
     pub fn new() -> Result<Self> {
+        match env::var_os(ADVISORY_DB_ENV) {
+            Some(path) if !path.is_empty() => Self::from_path(Path::new(&path)),
+            _ => Self::fetch(),
+        }
+    }
+
+    /// Loads the database from a local path,
+    /// e.g., a pre-fetched checkout of the rustsec advisory repo
+    pub fn from_path(path: &Path) -> Result<Self> {
+        // Database::open returns an empty database for an invalid path
+        if !path.join("crates").is_dir() {
+            return Err(anyhow!(
+                "{} is not a rustsec advisory database",
+                path.display()
+            ));
+        }
+        let db = Database::open(path)?;
+
+        Ok(Self { db })
+    }
+
+    fn fetch() -> Result<Self> {
         let dir = tempdir()?;
         let dest_path = dir.path().join("rustsec");
         Repository::clone(DEFAULT_URL, &dest_path)?;
@@ -55,6 +81,23 @@ mod test {
         assert!(advisory.is_some());
     }
 
+    #[test]
+    fn test_advisory_lookup_from_path() {
+        let advisory_lookup =
+            AdvisoryLookup::from_path(Path::new("resources/test/advisory_db")).unwrap();
+        let advisories = advisory_lookup
+            .get_crate_version_advisories("tokio", "1.7.1")
+            .unwrap();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].id().as_str(), "RUSTSEC-2021-0072");
+        let advisories = advisory_lookup
+            .get_crate_version_advisories("tokio", "1.7.2")
+            .unwrap();
+        assert!(advisories.is_empty());
+
+        assert!(AdvisoryLookup::from_path(Path::new("resources/test/unsafe_code")).is_err());
+    }
+
     #[test]
     fn test_advisory_crate_version_lookup() {
         let advisories = ADVISORY_LOOKUP
@@ -0,0 +1,451 @@
+//! This module statically scans the code of a crate that runs at build time,
+//! i.e., its build script, proc-macro, or library when it is a build dependency,
+//! for behaviour that needs a closer review:
+//! running processes, network access, file writes outside OUT_DIR,
+//! env var reads, and binaries embedded with include_bytes!
+
+use anyhow::Result;
+use proc_macro2::Span;
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+use crate::crate_layout::{
+    get_module_dir, get_module_file, get_path_attr, normalize_path, ManifestTargets,
+};
+
+const NETWORK_TYPES: [&str; 4] = ["TcpStream", "TcpListener", "UdpSocket", "ToSocketAddrs"];
+const NETWORK_CRATES: [&str; 9] = [
+    "reqwest",
+    "ureq",
+    "curl",
+    "hyper",
+    "attohttpc",
+    "minreq",
+    "isahc",
+    "surf",
+    "http_req",
+];
+const FS_WRITE_CALLS: [&str; 10] = [
+    "fs::write",
+    "fs::create_dir",
+    "fs::create_dir_all",
+    "fs::copy",
+    "fs::rename",
+    "fs::remove_file",
+    "fs::remove_dir",
+    "fs::remove_dir_all",
+    "fs::hard_link",
+    "File::create",
+];
+const ENV_READ_CALLS: [&str; 4] = ["env::var", "env::var_os", "env::vars", "env::vars_os"];
+// Env vars set by Cargo for build scripts and crates
+const CARGO_ENV_PREFIXES: [&str; 3] = ["CARGO", "DEP_", "RUSTC"];
+const CARGO_ENV_VARS: [&str; 8] = [
+    "OUT_DIR",
+    "TARGET",
+    "HOST",
+    "NUM_JOBS",
+    "OPT_LEVEL",
+    "DEBUG",
+    "PROFILE",
+    "RUSTDOC",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BuildTimeTargetKind {
+    BuildScript,
+    ProcMacro,
+    BuildDependency, // library of a crate used only at build time
+}
+
+/// A target of a crate whose code runs at build time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BuildTimeTarget {
+    pub kind: BuildTimeTargetKind,
+    pub path: String, // root source file relative to the crate directory
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BuildBehaviour {
+    ProcessCommand,
+    Network,
+    FsWriteOutsideOutDir,
+    EnvRead,
+    IncludeBytes,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BuildScanFinding {
+    pub target: BuildTimeTargetKind,
+    pub file: String,
+    pub line: usize,
+    pub behaviour: BuildBehaviour,
+    pub detail: String, // the offending code, e.g., `Command::new`
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BuildScanReport {
+    pub scanned_files: Vec<String>,
+    pub findings: Vec<BuildScanFinding>,
+}
+
+/// Returns the build script and proc-macro targets from the Cargo.toml of a crate
+/// not present in a package graph, file_exists checks a path relative to the crate directory.
+/// A library used as a build dependency cannot be told from the manifest alone
+pub fn get_build_time_targets_from_manifest(
+    manifest: &str,
+    file_exists: impl Fn(&str) -> bool,
+) -> Result<BTreeSet<BuildTimeTarget>> {
+    let manifest_targets = ManifestTargets::from_manifest(manifest, file_exists)?;
+    let mut targets: BTreeSet<BuildTimeTarget> = BTreeSet::new();
+
+    if let Some(path) = manifest_targets.build_script {
+        targets.insert(BuildTimeTarget {
+            kind: BuildTimeTargetKind::BuildScript,
+            path,
+        });
+    }
+    if manifest_targets.is_proc_macro {
+        targets.insert(BuildTimeTarget {
+            kind: BuildTimeTargetKind::ProcMacro,
+            path: manifest_targets.lib,
+        });
+    }
+
+    Ok(targets)
+}
+
+/// Scans the targets and the modules they declare,
+/// read_file returns the content of a file relative to the crate directory
+pub fn scan_build_time_targets(
+    targets: &BTreeSet<BuildTimeTarget>,
+    read_file: impl Fn(&str) -> Option<String>,
+) -> BuildScanReport {
+    let mut report = BuildScanReport::default();
+    let mut visited: HashSet<String> = HashSet::new();
+
+    for target in targets {
+        // (file, whether the file is a crate root)
+        let mut files: Vec<(String, bool)> = vec![(normalize_path(&target.path), true)];
+        while let Some((file, is_root)) = files.pop() {
+            if !visited.insert(file.clone()) {
+                continue;
+            }
+            let source = match read_file(&file) {
+                Some(source) => source,
+                None => continue,
+            };
+            let syntax = match syn::parse_file(&source) {
+                Ok(syntax) => syntax,
+                Err(_) => continue,
+            };
+
+            let mut visitor = BuildScanVisitor {
+                target: target.kind,
+                file: file.clone(),
+                findings: Vec::new(),
+                modules: Vec::new(),
+            };
+            visitor.visit_file(&syntax);
+            report.scanned_files.push(file.clone());
+            report.findings.append(&mut visitor.findings);
+
+            let module_dir = get_module_dir(&file, is_root);
+            for (module, path_attr) in visitor.modules {
+                files.push((
+                    get_module_file(
+                        (&file, &module_dir),
+                        &module,
+                        path_attr.as_deref(),
+                        &read_file,
+                    ),
+                    false,
+                ));
+            }
+        }
+    }
+
+    report
+        .findings
+        .sort_by(|a, b| (&a.file, a.line, a.behaviour).cmp(&(&b.file, b.line, b.behaviour)));
+    report.findings.dedup();
+    report
+}
+
+struct BuildScanVisitor {
+    target: BuildTimeTargetKind,
+    file: String,
+    findings: Vec<BuildScanFinding>,
+    modules: Vec<(String, Option<String>)>, // out of line modules and their #[path], if any
+}
+
+impl BuildScanVisitor {
+    fn add_finding(&mut self, span: Span, behaviour: BuildBehaviour, detail: String) {
+        self.findings.push(BuildScanFinding {
+            target: self.target,
+            file: self.file.clone(),
+            line: span.start().line,
+            behaviour,
+            detail,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for BuildScanVisitor {
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        if i.content.is_none() {
+            self.modules
+                .push((i.ident.to_string(), get_path_attr(&i.attrs)));
+        }
+        visit::visit_item_mod(self, i);
+    }
+
+    fn visit_path(&mut self, i: &'ast syn::Path) {
+        let segments: Vec<String> = i.segments.iter().map(|s| s.ident.to_string()).collect();
+        let path = segments.join("::");
+        if path.ends_with("Command::new") {
+            self.add_finding(i.span(), BuildBehaviour::ProcessCommand, path);
+        } else if segments
+            .iter()
+            .any(|segment| NETWORK_TYPES.contains(&segment.as_str()))
+            || segments
+                .first()
+                .is_some_and(|segment| NETWORK_CRATES.contains(&segment.as_str()))
+        {
+            self.add_finding(i.span(), BuildBehaviour::Network, path);
+        }
+        visit::visit_path(self, i);
+    }
+
+    fn visit_expr_call(&mut self, i: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(func) = &*i.func {
+            let path: Vec<String> = func
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            let path = path.join("::");
+            let args: Vec<String> = i
+                .args
+                .iter()
+                .map(|arg| arg.to_token_stream().to_string())
+                .collect();
+
+            if ENV_READ_CALLS.iter().any(|call| path.ends_with(call)) {
+                match get_str_literal(i.args.first()) {
+                    Some(var) if is_cargo_env_var(&var) => (),
+                    Some(var) => self.add_finding(
+                        i.span(),
+                        BuildBehaviour::EnvRead,
+                        format!("{}(\"{}\")", path, var),
+                    ),
+                    None => self.add_finding(
+                        i.span(),
+                        BuildBehaviour::EnvRead,
+                        format!("{}({})", path, args.join(", ")),
+                    ),
+                }
+            }
+
+            // A heuristic: writes are in OUT_DIR if their paths refer to it
+            if FS_WRITE_CALLS.iter().any(|call| path.ends_with(call))
+                && !args
+                    .iter()
+                    .any(|arg| arg.to_lowercase().replace(' ', "").contains("out_dir"))
+            {
+                self.add_finding(
+                    i.span(),
+                    BuildBehaviour::FsWriteOutsideOutDir,
+                    format!("{}({})", path, args.join(", ")),
+                );
+            }
+        }
+        visit::visit_expr_call(self, i);
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        let name = i
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default();
+        match name.as_str() {
+            "include_bytes" => self.add_finding(
+                i.span(),
+                BuildBehaviour::IncludeBytes,
+                format!("include_bytes!({})", i.tokens),
+            ),
+            "env" | "option_env" => {
+                let var = syn::parse2::<syn::LitStr>(i.tokens.clone())
+                    .map(|lit| lit.value())
+                    .ok();
+                if !var.as_deref().is_some_and(is_cargo_env_var) {
+                    self.add_finding(
+                        i.span(),
+                        BuildBehaviour::EnvRead,
+                        format!("{}!({})", name, i.tokens),
+                    );
+                }
+            }
+            _ => (),
+        }
+        visit::visit_macro(self, i);
+    }
+}
+
+fn get_str_literal(expr: Option<&syn::Expr>) -> Option<String> {
+    match expr {
+        Some(syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        })) => Some(lit.value()),
+        _ => None,
+    }
+}
+
+fn is_cargo_env_var(var: &str) -> bool {
+    CARGO_ENV_VARS.contains(&var)
+        || CARGO_ENV_PREFIXES
+            .iter()
+            .any(|prefix| var.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_build_scan_targets_from_manifest() {
+        let get_targets = |manifest: &str, files: &[&str]| -> Vec<(BuildTimeTargetKind, String)> {
+            get_build_time_targets_from_manifest(manifest, |path| files.contains(&path))
+                .unwrap()
+                .into_iter()
+                .map(|target| (target.kind, target.path))
+                .collect()
+        };
+
+        let manifest = "[package]\nname = \"a\"\nversion = \"0.1.0\"\n";
+        assert!(get_targets(manifest, &[]).is_empty());
+        assert_eq!(
+            get_targets(manifest, &["build.rs"]),
+            vec![(BuildTimeTargetKind::BuildScript, "build.rs".to_string())]
+        );
+        assert!(get_targets(&format!("{}build = false\n", manifest), &["build.rs"]).is_empty());
+        assert_eq!(
+            get_targets(
+                &format!(
+                    "{}build = \"./build/main.rs\"\n[lib]\nproc-macro = true\npath = \"src/macros.rs\"\n",
+                    manifest
+                ),
+                &[]
+            ),
+            vec![
+                (BuildTimeTargetKind::BuildScript, "build/main.rs".to_string()),
+                (BuildTimeTargetKind::ProcMacro, "src/macros.rs".to_string()),
+            ]
+        );
+        assert!(get_build_time_targets_from_manifest("[package", |_| true).is_err());
+    }
+
+    #[test]
+    fn test_build_scan_build_script() {
+        let files: HashMap<&str, &str> = vec![
+            (
+                "build.rs",
+                r#"
+                mod util;
+                use std::process::Command;
+
+                fn main() {
+                    let out_dir = std::env::var("OUT_DIR").unwrap();
+                    std::fs::write(format!("{}/gen.rs", out_dir), "").unwrap();
+                    let home = std::env::var("HOME").unwrap();
+                    std::fs::write("/tmp/payload", home).unwrap();
+                    Command::new("curl").status().unwrap();
+                    util::fetch();
+                }
+                "#,
+            ),
+            (
+                "util.rs",
+                r#"
+                pub fn fetch() {
+                    let _ = std::net::TcpStream::connect("example.com:80");
+                    let _ = include_bytes!("blob.bin");
+                }
+                "#,
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let targets: BTreeSet<BuildTimeTarget> = vec![BuildTimeTarget {
+            kind: BuildTimeTargetKind::BuildScript,
+            path: "build.rs".to_string(),
+        }]
+        .into_iter()
+        .collect();
+
+        let report =
+            scan_build_time_targets(&targets, |path| files.get(path).map(|s| s.to_string()));
+        assert_eq!(report.scanned_files, vec!["build.rs", "util.rs"]);
+
+        let findings: Vec<(&str, usize, BuildBehaviour)> = report
+            .findings
+            .iter()
+            .map(|finding| (finding.file.as_str(), finding.line, finding.behaviour))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                ("build.rs", 8, BuildBehaviour::EnvRead),
+                ("build.rs", 9, BuildBehaviour::FsWriteOutsideOutDir),
+                ("build.rs", 10, BuildBehaviour::ProcessCommand),
+                ("util.rs", 3, BuildBehaviour::Network),
+                ("util.rs", 4, BuildBehaviour::IncludeBytes),
+            ]
+        );
+        assert_eq!(report.findings[0].detail, "std::env::var(\"HOME\")");
+    }
+
+    #[test]
+    fn test_build_scan_proc_macro_modules() {
+        let files: HashMap<&str, &str> = vec![
+            ("src/lib.rs", "mod expand; #[path = \"gen/x.rs\"] mod x;"),
+            ("src/expand/mod.rs", "mod inner;"),
+            ("src/expand/inner.rs", "const V: &str = env!(\"SECRET\");"),
+            ("src/gen/x.rs", "const P: &str = env!(\"CARGO_PKG_NAME\");"),
+        ]
+        .into_iter()
+        .collect();
+        let targets: BTreeSet<BuildTimeTarget> = vec![BuildTimeTarget {
+            kind: BuildTimeTargetKind::ProcMacro,
+            path: "./src/lib.rs".to_string(),
+        }]
+        .into_iter()
+        .collect();
+
+        let report =
+            scan_build_time_targets(&targets, |path| files.get(path).map(|s| s.to_string()));
+        let mut scanned_files = report.scanned_files.clone();
+        scanned_files.sort();
+        assert_eq!(
+            scanned_files,
+            vec![
+                "src/expand/inner.rs",
+                "src/expand/mod.rs",
+                "src/gen/x.rs",
+                "src/lib.rs"
+            ]
+        );
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].file, "src/expand/inner.rs");
+        assert_eq!(report.findings[0].behaviour, BuildBehaviour::EnvRead);
+        assert_eq!(report.findings[0].target, BuildTimeTargetKind::ProcMacro);
+    }
+}
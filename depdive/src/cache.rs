@@ -0,0 +1,345 @@
+//! This module abstracts a persistent on-disk cache
+//! for crates.io tarballs and cloned git repositories
+//! so that the same code is not downloaded repeatedly
+//! within a run and across runs.
+//!
+//! The cache is enabled by setting `DEPDIVE_CACHE_DIR`
+//! (or `--cache-dir` in the CLI).
+//! Entries are addressed by a hash of the crate name and version,
+//! or of the repository url, e.g.,
+//! `<cache-dir>/crates/libc-8f2a0c3d1e5b7a9c.crate`
+//! and `<cache-dir>/git/diem-1b3d5f7a9c0e2d4f`.
+
+use anyhow::{anyhow, Result};
+use filetime::FileTime;
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{create_dir_all, remove_dir_all, remove_file},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use twox_hash::XxHash64;
+
+use crate::registry::CRATES_IO_INDEX;
+use walkdir::WalkDir;
+
+pub const CACHE_DIR_ENV: &str = "DEPDIVE_CACHE_DIR";
+pub const CACHE_MAX_AGE_DAYS_ENV: &str = "DEPDIVE_CACHE_MAX_AGE_DAYS";
+pub const CACHE_MAX_SIZE_MB_ENV: &str = "DEPDIVE_CACHE_MAX_SIZE_MB";
+
+const CRATES_DIR: &str = "crates";
+const GIT_DIR: &str = "git";
+
+// Serializes updates to a cached git repo across threads
+static GIT_REPO_LOCKS: Mutex<BTreeMap<PathBuf, Arc<Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+/// Entries not used for max_age are evicted first,
+/// then least recently used entries until the cache fits max_size
+#[derive(Debug, Clone)]
+pub struct EvictionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_size: Option<u64>, // in bytes
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            max_size: Some(10 * 1024 * 1024 * 1024),
+        }
+    }
+}
+
+impl EvictionPolicy {
+    /// Default policy overridden by
+    /// DEPDIVE_CACHE_MAX_AGE_DAYS and DEPDIVE_CACHE_MAX_SIZE_MB
+    pub fn from_env() -> Result<Self> {
+        let mut policy = Self::default();
+        if let Ok(days) = env::var(CACHE_MAX_AGE_DAYS_ENV) {
+            let days: u64 = days
+                .parse()
+                .map_err(|_| anyhow!("invalid {}: {}", CACHE_MAX_AGE_DAYS_ENV, days))?;
+            policy.max_age = Some(Duration::from_secs(days * 24 * 60 * 60));
+        }
+        if let Ok(mb) = env::var(CACHE_MAX_SIZE_MB_ENV) {
+            let mb: u64 = mb
+                .parse()
+                .map_err(|_| anyhow!("invalid {}: {}", CACHE_MAX_SIZE_MB_ENV, mb))?;
+            policy.max_size = Some(mb * 1024 * 1024);
+        }
+        Ok(policy)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DepdiveCache {
+    root: PathBuf,
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    last_used: SystemTime,
+    size: u64,
+}
+
+impl DepdiveCache {
+    pub fn new(root: &Path) -> Result<Self> {
+        create_dir_all(root.join(CRATES_DIR))?;
+        create_dir_all(root.join(GIT_DIR))?;
+        Ok(Self {
+            root: root.to_path_buf(),
+        })
+    }
+
+    /// Returns None if DEPDIVE_CACHE_DIR is not set
+    pub fn from_env() -> Result<Option<Self>> {
+        match env::var_os(CACHE_DIR_ENV) {
+            Some(root) if !root.is_empty() => Ok(Some(Self::new(Path::new(&root))?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub(crate) fn get_crate_tarball_path(&self, index: &str, name: &str, version: &str) -> PathBuf {
+        let mut hasher = XxHash64::default();
+        name.hash(&mut hasher);
+        version.hash(&mut hasher);
+        // crates with the same name and version
+        // can be different across registries
+        if index != CRATES_IO_INDEX {
+            index.hash(&mut hasher);
+        }
+        let hash = hasher.finish();
+        self.root
+            .join(CRATES_DIR)
+            .join(format!("{}-{:x}.crate", name, hash))
+    }
+
+    pub(crate) fn get_git_repo_path(&self, name: &str, url: &str) -> PathBuf {
+        // the same repository may be referred to with or without .git suffix
+        let url = url.trim_end_matches('/').trim_end_matches(".git");
+        let mut hasher = XxHash64::default();
+        url.hash(&mut hasher);
+        let hash = hasher.finish();
+        self.root.join(GIT_DIR).join(format!("{}-{:x}", name, hash))
+    }
+
+    pub(crate) fn get_git_repo_lock(&self, path: &Path) -> Arc<Mutex<()>> {
+        GIT_REPO_LOCKS
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone()
+    }
+
+    /// Drops the lock of a cached git repo when no other thread holds it,
+    /// so that locks do not pile up over a run
+    pub(crate) fn release_git_repo_lock(&self, path: &Path, lock: Arc<Mutex<()>>) {
+        let mut locks = GIT_REPO_LOCKS.lock().unwrap();
+        // one reference in the map and one of the caller
+        if Arc::strong_count(&lock) <= 2 {
+            locks.remove(path);
+        }
+    }
+
+    /// Mark an entry as recently used for eviction
+    pub(crate) fn touch(&self, path: &Path) -> Result<()> {
+        filetime::set_file_mtime(path, FileTime::now())?;
+        Ok(())
+    }
+
+    /// Evict entries per the policy,
+    /// returns the evicted paths
+    pub fn evict(&self, policy: &EvictionPolicy) -> Result<Vec<PathBuf>> {
+        let mut entries = self.get_entries()?;
+        // least recently used first
+        entries.sort_by_key(|entry| entry.last_used);
+
+        let now = SystemTime::now();
+        let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut evicted: Vec<PathBuf> = Vec::new();
+        for entry in entries {
+            let expired = match policy.max_age {
+                Some(max_age) => now
+                    .duration_since(entry.last_used)
+                    .is_ok_and(|age| age > max_age),
+                None => false,
+            };
+            let oversized = match policy.max_size {
+                Some(max_size) => total_size > max_size,
+                None => false,
+            };
+            if !expired && !oversized {
+                continue;
+            }
+
+            Self::remove_entry(&entry.path)?;
+            total_size -= entry.size;
+            evicted.push(entry.path);
+        }
+        Ok(evicted)
+    }
+
+    /// Remove all entries from the cache
+    pub fn clean(&self) -> Result<Vec<PathBuf>> {
+        let mut evicted: Vec<PathBuf> = Vec::new();
+        for entry in self.get_entries()? {
+            Self::remove_entry(&entry.path)?;
+            evicted.push(entry.path);
+        }
+        Ok(evicted)
+    }
+
+    fn get_entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries: Vec<CacheEntry> = Vec::new();
+        for dir in &[CRATES_DIR, GIT_DIR] {
+            for entry in self.root.join(dir).read_dir()? {
+                let path = entry?.path();
+                let last_used = path.metadata()?.modified()?;
+                let size = Self::get_size(&path)?;
+                entries.push(CacheEntry {
+                    path,
+                    last_used,
+                    size,
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    fn get_size(path: &Path) -> Result<u64> {
+        let mut size: u64 = 0;
+        for entry in WalkDir::new(path) {
+            let metadata = entry?.metadata()?;
+            if metadata.is_file() {
+                size += metadata.len();
+            }
+        }
+        Ok(size)
+    }
+
+    fn remove_entry(path: &Path) -> Result<()> {
+        if path.is_dir() {
+            remove_dir_all(path)?;
+        } else {
+            remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    fn set_last_used(path: &Path, days_ago: u64) {
+        let time = SystemTime::now() - Duration::from_secs(days_ago * 24 * 60 * 60);
+        filetime::set_file_mtime(path, FileTime::from_system_time(time)).unwrap();
+    }
+
+    #[test]
+    fn test_cache_entry_paths() {
+        let dir = tempdir().unwrap();
+        let cache = DepdiveCache::new(dir.path()).unwrap();
+
+        let path = cache.get_crate_tarball_path(CRATES_IO_INDEX, "libc", "0.2.93");
+        assert!(path.starts_with(dir.path().join("crates")));
+        assert_eq!(
+            path,
+            cache.get_crate_tarball_path(CRATES_IO_INDEX, "libc", "0.2.93")
+        );
+        assert_ne!(
+            path,
+            cache.get_crate_tarball_path(CRATES_IO_INDEX, "libc", "0.2.92")
+        );
+        assert_ne!(
+            path,
+            cache.get_crate_tarball_path("https://git.example.com/index", "libc", "0.2.93")
+        );
+
+        let path = cache.get_git_repo_path("diem", "https://github.com/diem/diem");
+        assert!(path.starts_with(dir.path().join("git")));
+        assert_eq!(
+            path,
+            cache.get_git_repo_path("diem", "https://github.com/diem/diem.git")
+        );
+        assert_ne!(
+            path,
+            cache.get_git_repo_path("diem", "https://github.com/diem/diem-devtools")
+        );
+    }
+
+    #[test]
+    fn test_cache_git_repo_lock() {
+        let dir = tempdir().unwrap();
+        let cache = DepdiveCache::new(dir.path()).unwrap();
+        let path = cache.get_git_repo_path("diem", "https://github.com/diem/diem");
+
+        let lock = cache.get_git_repo_lock(&path);
+        let other_lock = cache.get_git_repo_lock(&path);
+        assert!(Arc::ptr_eq(&lock, &other_lock));
+
+        // The lock is kept while another thread holds it
+        cache.release_git_repo_lock(&path, lock);
+        assert!(GIT_REPO_LOCKS.lock().unwrap().contains_key(&path));
+        cache.release_git_repo_lock(&path, other_lock);
+        assert!(!GIT_REPO_LOCKS.lock().unwrap().contains_key(&path));
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let dir = tempdir().unwrap();
+        let cache = DepdiveCache::new(dir.path()).unwrap();
+
+        let old_tarball = cache.get_crate_tarball_path(CRATES_IO_INDEX, "libc", "0.2.92");
+        write(&old_tarball, vec![0u8; 100]).unwrap();
+        set_last_used(&old_tarball, 60);
+        let tarball = cache.get_crate_tarball_path(CRATES_IO_INDEX, "libc", "0.2.93");
+        write(&tarball, vec![0u8; 100]).unwrap();
+        set_last_used(&tarball, 2);
+        let repo = cache.get_git_repo_path("diem", "https://github.com/diem/diem");
+        create_dir_all(&repo).unwrap();
+        write(repo.join("lib.rs"), vec![0u8; 100]).unwrap();
+        set_last_used(&repo, 1);
+
+        // Only the entry not used in last 30 days is evicted
+        let evicted = cache.evict(&EvictionPolicy::default()).unwrap();
+        assert_eq!(evicted, vec![old_tarball.clone()]);
+        assert!(!old_tarball.exists());
+
+        // Least recently used entries are evicted to fit the size
+        let policy = EvictionPolicy {
+            max_age: None,
+            max_size: Some(150),
+        };
+        let evicted = cache.evict(&policy).unwrap();
+        assert_eq!(evicted, vec![tarball.clone()]);
+        assert!(repo.exists());
+
+        // Using an entry keeps it from eviction
+        write(&tarball, vec![0u8; 100]).unwrap();
+        set_last_used(&tarball, 2);
+        cache.touch(&tarball).unwrap();
+        let evicted = cache.evict(&policy).unwrap();
+        assert_eq!(evicted, vec![repo.clone()]);
+
+        let evicted = cache.clean().unwrap();
+        assert_eq!(evicted, vec![tarball]);
+        assert!(dir
+            .path()
+            .join("crates")
+            .read_dir()
+            .unwrap()
+            .next()
+            .is_none());
+    }
+}
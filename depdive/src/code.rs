@@ -3,7 +3,7 @@
 use anyhow::{anyhow, Result};
 use camino::Utf8Path;
 use guppy::graph::{PackageGraph, PackageMetadata};
-use semver::Version;
+use guppy::PackageId;
 use serde::{Deserialize, Serialize};
 use std::{
     cell::RefCell, collections::HashMap, collections::HashSet, fs, ops, path::Path,
@@ -12,8 +12,8 @@ use std::{
 use tokei::{Config, LanguageType, Languages};
 
 use crate::guppy_wrapper::{
-    filter_exclusive_deps, get_all_dependencies, get_dep_kind_map, get_direct_dependencies,
-    get_package_dependencies, DependencyKind,
+    filter_exclusive_deps, get_all_dependencies, get_dependency_paths, get_direct_dependencies,
+    get_package_dep_kind_map, get_package_dependencies, DependencyKind, DependencyPaths,
 };
 use crate::super_toml::{CargoTomlParser, CargoTomlType, SuperPackageGenerator};
 
@@ -21,6 +21,7 @@ use crate::super_toml::{CargoTomlParser, CargoTomlType, SuperPackageGenerator};
 pub struct CodeReport {
     pub name: String,
     pub version: String,
+    pub source: String,
     pub is_direct: bool,
     pub kind: DependencyKind,
     pub has_build_script: bool,
@@ -28,6 +29,7 @@ pub struct CodeReport {
     pub unsafe_report: Option<UnsafeReport>,
     pub dep_report: Option<DepReport>,
     pub exclusive_dep_report: Option<DepReport>,
+    pub dependency_paths: Option<DependencyPaths>, // how the workspace members pull it in
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -156,19 +158,19 @@ impl CodeAnalyzer {
 
         // Get direct dependencies of the whole workspace
         let all_deps = get_all_dependencies(graph);
-        let direct_deps: HashSet<(&str, &Version)> = get_direct_dependencies(graph)
+        let direct_deps: HashSet<&PackageId> = get_direct_dependencies(graph)
             .iter()
-            .map(|pkg| (pkg.name(), pkg.version()))
+            .map(|pkg| pkg.id())
             .collect();
-        let dep_kind_map = get_dep_kind_map(graph)?;
+        let dep_kind_map = get_package_dep_kind_map(graph)?;
 
         for package in &all_deps {
-            let is_direct = direct_deps.contains(&(package.name(), package.version()));
+            let is_direct = direct_deps.contains(package.id());
             if only_direct && !is_direct {
                 continue;
             }
             let kind = dep_kind_map
-                .get(&(package.name().to_string(), package.version().clone()))
+                .get(package.id())
                 .ok_or_else(|| {
                     anyhow!(
                         "fatal error in determining dependency kind for {}:{}",
@@ -193,6 +195,7 @@ impl CodeAnalyzer {
             let code_report = CodeReport {
                 name: package.name().to_string(),
                 version: package.version().to_string(),
+                source: package.source().to_string(),
                 is_direct,
                 kind,
                 has_build_script: package.has_build_script(),
@@ -200,6 +203,7 @@ impl CodeAnalyzer {
                 unsafe_report,
                 dep_report: Some(dep_report),
                 exclusive_dep_report: Some(exclusive_dep_report),
+                dependency_paths: Some(get_dependency_paths(package)),
             };
 
             code_reports.push(code_report);
@@ -0,0 +1,252 @@
+//! This module reads the layout of a crate:
+//! its targets as declared in Cargo.toml with Cargo's defaults,
+//! and the files of the modules declared in its source.
+//! Paths are relative to the crate directory with `/` separators,
+//! e.g., `src/foo/mod.rs`
+
+use anyhow::Result;
+use toml::Value;
+
+pub(crate) const DEFAULT_LIB_PATH: &str = "src/lib.rs";
+const DEFAULT_BUILD_SCRIPT_PATH: &str = "build.rs";
+
+/// Targets of a crate as declared in its Cargo.toml
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ManifestTargets {
+    pub lib: String,
+    pub is_proc_macro: bool,
+    pub build_script: Option<String>,
+    pub bins: Vec<String>, // paths of the declared [[bin]] targets
+    pub autobins: bool,    // whether Cargo discovers binaries, e.g., src/main.rs
+}
+
+impl ManifestTargets {
+    /// Reads the targets from a Cargo.toml,
+    /// file_exists checks a path relative to the crate directory
+    /// for the build script Cargo picks up when not declared
+    pub fn from_manifest(manifest: &str, file_exists: impl Fn(&str) -> bool) -> Result<Self> {
+        let toml: Value = toml::from_str(manifest)?;
+        let package = toml.get("package");
+        let lib = toml.get("lib");
+
+        // build = false disables the build script, build.rs is picked up otherwise
+        let build_script = match package.and_then(|package| package.get("build")) {
+            Some(Value::String(path)) => Some(normalize_path(path)),
+            Some(Value::Boolean(false)) => None,
+            _ if file_exists(DEFAULT_BUILD_SCRIPT_PATH) => {
+                Some(DEFAULT_BUILD_SCRIPT_PATH.to_string())
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            lib: normalize_path(&get_lib_path_from_toml(&toml)),
+            is_proc_macro: lib
+                .and_then(|lib| lib.get("proc-macro").or_else(|| lib.get("proc_macro")))
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            build_script,
+            bins: toml
+                .get("bin")
+                .and_then(Value::as_array)
+                .map(|bins| {
+                    bins.iter()
+                        .filter_map(|bin| bin.get("path")?.as_str())
+                        .map(normalize_path)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            autobins: package
+                .and_then(|package| package.get("autobins"))
+                .and_then(Value::as_bool)
+                .unwrap_or(true),
+        })
+    }
+}
+
+/// Returns the library root from the Cargo.toml, src/lib.rs by default
+pub fn get_lib_path(manifest: Option<&str>) -> String {
+    manifest
+        .and_then(|manifest| toml::from_str::<Value>(manifest).ok())
+        .map(|toml| get_lib_path_from_toml(&toml))
+        .unwrap_or_else(|| DEFAULT_LIB_PATH.to_string())
+}
+
+fn get_lib_path_from_toml(toml: &Value) -> String {
+    toml.get("lib")
+        .and_then(|lib| lib.get("path"))
+        .and_then(Value::as_str)
+        .unwrap_or(DEFAULT_LIB_PATH)
+        .to_string()
+}
+
+// The file of an out of line module declared in a file,
+// #[path] is relative to the directory of the declaring file
+pub(crate) fn get_module_file(
+    (file, module_dir): (&str, &str),
+    module: &str,
+    path_attr: Option<&str>,
+    read_file: impl Fn(&str) -> Option<String>,
+) -> String {
+    match path_attr {
+        Some(path) => join_path(get_parent(file), path),
+        None => {
+            let module_file = join_path(module_dir, &format!("{}.rs", module));
+            if read_file(&module_file).is_some() {
+                module_file
+            } else {
+                join_path(module_dir, &format!("{}/mod.rs", module))
+            }
+        }
+    }
+}
+
+pub(crate) fn get_path_attr(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match attr.parse_meta() {
+        Ok(syn::Meta::NameValue(syn::MetaNameValue {
+            path,
+            lit: syn::Lit::Str(lit),
+            ..
+        })) if path.is_ident("path") => Some(lit.value()),
+        _ => None,
+    })
+}
+
+// Directory of the modules declared in a file,
+// e.g., modules of src/foo.rs are in src/foo, and of src/lib.rs in src
+pub(crate) fn get_module_dir(file: &str, is_root: bool) -> String {
+    let parent = get_parent(file);
+    let stem = file
+        .rsplit('/')
+        .next()
+        .unwrap_or(file)
+        .trim_end_matches(".rs");
+    if is_root || stem == "mod" {
+        parent.to_string()
+    } else {
+        join_path(parent, stem)
+    }
+}
+
+fn get_parent(file: &str) -> &str {
+    match file.rfind('/') {
+        Some(idx) => &file[..idx],
+        None => "",
+    }
+}
+
+pub(crate) fn join_path(dir: &str, path: &str) -> String {
+    if dir.is_empty() {
+        normalize_path(path)
+    } else {
+        normalize_path(&format!("{}/{}", dir, path))
+    }
+}
+
+// e.g., ./src\bin/../main.rs to src/main.rs
+pub(crate) fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_crate_layout_manifest_targets() {
+        let manifest = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n";
+        let targets = ManifestTargets::from_manifest(manifest, |_| false).unwrap();
+        assert_eq!(
+            targets,
+            ManifestTargets {
+                lib: "src/lib.rs".to_string(),
+                is_proc_macro: false,
+                build_script: None,
+                bins: Vec::new(),
+                autobins: true,
+            }
+        );
+        let targets = ManifestTargets::from_manifest(manifest, |path| path == "build.rs").unwrap();
+        assert_eq!(targets.build_script, Some("build.rs".to_string()));
+
+        let targets = ManifestTargets::from_manifest(
+            &format!(
+                "{}build = \"./tools\\\\build.rs\"\nautobins = false\n\
+                [lib]\nproc-macro = true\npath = \"macros.rs\"\n\
+                [[bin]]\nname = \"cli\"\npath = \"cli/../src/cli.rs\"\n",
+                manifest
+            ),
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(
+            targets,
+            ManifestTargets {
+                lib: "macros.rs".to_string(),
+                is_proc_macro: true,
+                build_script: Some("tools/build.rs".to_string()),
+                bins: vec!["src/cli.rs".to_string()],
+                autobins: false,
+            }
+        );
+        assert!(
+            ManifestTargets::from_manifest(&format!("{}build = false\n", manifest), |_| true)
+                .unwrap()
+                .build_script
+                .is_none()
+        );
+        assert!(ManifestTargets::from_manifest("[package", |_| true).is_err());
+    }
+
+    #[test]
+    fn test_crate_layout_lib_path() {
+        assert_eq!(get_lib_path(None), "src/lib.rs");
+        assert_eq!(
+            get_lib_path(Some(
+                "[package]\nname = \"foo\"\n[lib]\npath = \"lib.rs\"\n"
+            )),
+            "lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_crate_layout_paths() {
+        assert_eq!(normalize_path("./src\\bin/../main.rs"), "src/main.rs");
+        assert_eq!(join_path("", "./build.rs"), "build.rs");
+        assert_eq!(join_path("src/foo", "../bar.rs"), "src/bar.rs");
+
+        assert_eq!(get_module_dir("src/lib.rs", true), "src");
+        assert_eq!(get_module_dir("src/foo.rs", false), "src/foo");
+        assert_eq!(get_module_dir("src/foo/mod.rs", false), "src/foo");
+
+        let read_file = |path: &str| (path == "src/foo.rs").then(String::new);
+        assert_eq!(
+            get_module_file(("src/lib.rs", "src"), "foo", None, read_file),
+            "src/foo.rs"
+        );
+        assert_eq!(
+            get_module_file(("src/lib.rs", "src"), "bar", None, read_file),
+            "src/bar/mod.rs"
+        );
+        assert_eq!(
+            get_module_file(
+                ("src/foo.rs", "src/foo"),
+                "baz",
+                Some("gen/baz.rs"),
+                read_file
+            ),
+            "src/gen/baz.rs"
+        );
+    }
+}
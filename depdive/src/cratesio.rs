@@ -1,5 +1,6 @@
 //! This module abstracts the communication with crates.io for a given crate
 //! Returns Error if the crate is not hosted on crates_io
+//! Alternative registries with a crates.io compatible web API are supported as well
 
 // TODO: A cheaper way to interact with crates.io can be working with their
 // experimental database dump that is updated daily, https://crates.io/data-access,
@@ -10,10 +11,13 @@
 // as the crate has no direct API for our requirements and will make many extra calls
 
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use guppy::graph::PackageMetadata;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
+use crate::registry::Registry;
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct CratesioReport {
     pub name: String,
@@ -22,9 +26,93 @@ pub struct CratesioReport {
     pub dependents: u64, // Direct dependents
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionPublisher {
+    pub version: Version,
+    pub publisher: Option<String>, // None for versions published before crates.io recorded it
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReleaseInfo {
+    pub created_at: DateTime<Utc>,
+    pub age_days: i64, // days since the release at the time of review
+    pub yanked: bool,
+    pub newer_versions: u64, // versions not yanked with a higher version number
+}
+
+/// The versions of a crate as listed by the registry,
+/// to get the release info and the publishers of many versions from one response
+#[derive(Debug, Clone)]
+pub struct CrateVersions {
+    name: String,
+    versions: Vec<serde_json::Value>,
+}
+
+impl CrateVersions {
+    /// Returns the user who published each version of the crate
+    pub fn get_version_publishers(&self) -> Result<Vec<VersionPublisher>> {
+        self.versions
+            .iter()
+            .map(|version| {
+                Ok(VersionPublisher {
+                    version: Self::get_version_number(version)?,
+                    publisher: version["published_by"]["login"]
+                        .as_str()
+                        .map(|login| login.to_string()),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns when a version was released, whether it is yanked,
+    /// and how many newer versions are available
+    pub fn get_release_info(&self, version: &Version) -> Result<ReleaseInfo> {
+        let mut release: Option<&serde_json::Value> = None;
+        let mut newer_versions: u64 = 0;
+        for entry in &self.versions {
+            let number = Self::get_version_number(entry)?;
+            if &number == version {
+                release = Some(entry);
+            } else if &number > version
+                && !entry["yanked"].as_bool().unwrap_or(false)
+                // Pre-releases are only an upgrade path from another pre-release
+                && (number.pre.is_empty() || !version.pre.is_empty())
+            {
+                newer_versions += 1;
+            }
+        }
+        let release =
+            release.ok_or_else(|| anyhow!("version {} not found for {}", version, self.name))?;
+
+        let created_at = DateTime::parse_from_rfc3339(
+            release["created_at"]
+                .as_str()
+                .ok_or_else(|| anyhow!("version created_at is not a string"))?,
+        )?
+        .with_timezone(&Utc);
+
+        Ok(ReleaseInfo {
+            created_at,
+            age_days: Utc::now().signed_duration_since(created_at).num_days(),
+            yanked: release["yanked"]
+                .as_bool()
+                .ok_or_else(|| anyhow!("version yanked is not a boolean"))?,
+            newer_versions,
+        })
+    }
+
+    fn get_version_number(version: &serde_json::Value) -> Result<Version> {
+        let number = version["num"]
+            .as_str()
+            .ok_or_else(|| anyhow!("version number is not a string"))?;
+        Ok(Version::parse(number)?)
+    }
+}
+
 pub struct CratesioAnalyzer {
     crates_io_api_client: crates_io_api::SyncClient,
     http_client: reqwest::blocking::Client,
+    registry: Registry,
 }
 
 impl CratesioAnalyzer {
@@ -37,9 +125,15 @@ impl CratesioAnalyzer {
             http_client: reqwest::blocking::Client::builder()
                 .user_agent("diem/whackadep")
                 .build()?,
+            registry: Registry::crates_io(),
         })
     }
 
+    /// Looks up stats in the given registry's web API instead of crates.io
+    pub fn with_registry(self, registry: Registry) -> Self {
+        Self { registry, ..self }
+    }
+
     pub fn analyze_cratesio(self, package: &PackageMetadata) -> Result<CratesioReport> {
         let name = package.name();
         let is_hosted = package.source().is_crates_io();
@@ -55,22 +149,50 @@ impl CratesioAnalyzer {
             });
         }
 
-        let crate_info = self.crates_io_api_client.get_crate(name)?.crate_data;
+        let downloads = if self.registry.is_crates_io() {
+            self.crates_io_api_client
+                .get_crate(name)?
+                .crate_data
+                .downloads
+        } else {
+            self.get_crate_downloads(name)?
+        };
         let dependents = self.get_total_dependents(name)?;
 
         let cratesio_report = CratesioReport {
             name: name.to_string(),
             is_hosted,
-            downloads: crate_info.downloads,
+            downloads,
             dependents,
         };
 
         Ok(cratesio_report)
     }
 
+    fn get_crate_downloads(&self, crate_name: &str) -> Result<u64> {
+        let api_endpoint = format!(
+            "{}/api/v1/crates/{}",
+            self.registry.get_api_url()?,
+            crate_name
+        );
+
+        let response = self.http_client.get(api_endpoint).send()?;
+        if !response.status().is_success() {
+            return Err(anyhow!("http request to registry failed: {:?}", response));
+        }
+
+        let response: serde_json::Value = response.json()?;
+        let downloads: u64 = response["crate"]["downloads"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("crate downloads is not an integer"))?;
+
+        Ok(downloads)
+    }
+
     pub fn get_total_dependents(&self, crate_name: &str) -> Result<u64> {
         let api_endpoint = format!(
-            "https://crates.io/api/v1/crates/{}/reverse_dependencies",
+            "{}/api/v1/crates/{}/reverse_dependencies",
+            self.registry.get_api_url()?,
             crate_name
         );
 
@@ -89,7 +211,8 @@ impl CratesioAnalyzer {
 
     pub fn get_version_downloads(&self, crate_name: &str, version: &Version) -> Result<u64> {
         let api_endpoint = format!(
-            "https://crates.io/api/v1/crates/{}/{}",
+            "{}/api/v1/crates/{}/{}",
+            self.registry.get_api_url()?,
             crate_name,
             version.to_string()
         );
@@ -106,12 +229,71 @@ impl CratesioAnalyzer {
 
         Ok(downloads)
     }
+
+    /// Returns the user who published each version of a crate
+    pub fn get_version_publishers(&self, crate_name: &str) -> Result<Vec<VersionPublisher>> {
+        self.get_versions(crate_name)?.get_version_publishers()
+    }
+
+    /// Returns when a version was released, whether it is yanked,
+    /// and how many newer versions are available
+    pub fn get_release_info(&self, crate_name: &str, version: &Version) -> Result<ReleaseInfo> {
+        self.get_versions(crate_name)?.get_release_info(version)
+    }
+
+    /// Returns all versions of a crate in a single request
+    pub fn get_versions(&self, crate_name: &str) -> Result<CrateVersions> {
+        let api_endpoint = format!(
+            "{}/api/v1/crates/{}/versions",
+            self.registry.get_api_url()?,
+            crate_name
+        );
+
+        let response = self.http_client.get(api_endpoint).send()?;
+        if !response.status().is_success() {
+            return Err(anyhow!("http request to Crates.io failed: {:?}", response));
+        }
+
+        let mut response: serde_json::Value = response.json()?;
+        match response["versions"].take() {
+            serde_json::Value::Array(versions) => Ok(CrateVersions {
+                name: crate_name.to_string(),
+                versions,
+            }),
+            _ => Err(anyhow!("crate versions is not an array")),
+        }
+    }
+
+    /// Returns the current owners of a crate, both users and teams
+    pub fn get_owners(&self, crate_name: &str) -> Result<Vec<String>> {
+        let api_endpoint = format!(
+            "{}/api/v1/crates/{}/owners",
+            self.registry.get_api_url()?,
+            crate_name
+        );
+
+        let response = self.http_client.get(api_endpoint).send()?;
+        if !response.status().is_success() {
+            return Err(anyhow!("http request to Crates.io failed: {:?}", response));
+        }
+
+        let response: serde_json::Value = response.json()?;
+        let mut owners: Vec<String> = response["users"]
+            .as_array()
+            .ok_or_else(|| anyhow!("crate owners is not an array"))?
+            .iter()
+            .filter_map(|owner| owner["login"].as_str().map(|login| login.to_string()))
+            .collect();
+        owners.sort();
+        Ok(owners)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use guppy::MetadataCommand;
+    use std::collections::HashMap;
     use std::path::PathBuf;
 
     fn test_cratesio_analyzer() -> CratesioAnalyzer {
@@ -154,4 +336,139 @@ mod tests {
             .unwrap();
         assert!(downloads > 10000);
     }
+
+    #[test]
+    fn test_cratesio_registry_stats() {
+        // A registry stand-in with a crates.io compatible web API
+        let mut routes: HashMap<String, Vec<u8>> = HashMap::new();
+        routes.insert(
+            "/api/v1/crates/internal".to_string(),
+            br#"{"crate": {"downloads": 120}}"#.to_vec(),
+        );
+        routes.insert(
+            "/api/v1/crates/internal/0.1.0".to_string(),
+            br#"{"version": {"downloads": 42}}"#.to_vec(),
+        );
+        routes.insert(
+            "/api/v1/crates/internal/reverse_dependencies".to_string(),
+            br#"{"meta": {"total": 3}}"#.to_vec(),
+        );
+        routes.insert(
+            "/api/v1/crates/internal/versions".to_string(),
+            br#"{"versions": [
+                {"num": "0.3.0", "published_by": {"login": "bob"},
+                 "created_at": "2021-03-01T00:00:00.000000+00:00", "yanked": true},
+                {"num": "0.2.0", "published_by": {"login": "bob"},
+                 "created_at": "2021-02-01T00:00:00.000000+00:00", "yanked": false},
+                {"num": "0.1.0", "published_by": null,
+                 "created_at": "2021-01-01T12:30:00.000000+00:00", "yanked": true}
+            ]}"#
+            .to_vec(),
+        );
+        routes.insert(
+            "/api/v1/crates/internal/owners".to_string(),
+            br#"{"users": [{"login": "bob"}, {"login": "github:org:team"}, {"login": "alice"}]}"#
+                .to_vec(),
+        );
+        let url = crate::registry::test::serve_http(routes);
+
+        let registry = Registry {
+            index: format!("sparse+{}/index/", url),
+            dl: format!("{}/dl", url),
+            api: Some(url),
+        };
+        let cratesio_analyzer = test_cratesio_analyzer().with_registry(registry);
+        let downloads = cratesio_analyzer
+            .get_version_downloads("internal", &Version::parse("0.1.0").unwrap())
+            .unwrap();
+        assert_eq!(downloads, 42);
+
+        let report = cratesio_analyzer
+            .get_cratesio_metrics("internal", true)
+            .unwrap();
+        assert_eq!(report.downloads, 120);
+        assert_eq!(report.dependents, 3);
+
+        assert!(cratesio_analyzer
+            .get_version_downloads("internal", &Version::parse("0.2.0").unwrap())
+            .is_err());
+
+        let publishers = cratesio_analyzer
+            .get_version_publishers("internal")
+            .unwrap();
+        assert_eq!(
+            publishers,
+            vec![
+                VersionPublisher {
+                    version: Version::parse("0.3.0").unwrap(),
+                    publisher: Some("bob".to_string()),
+                },
+                VersionPublisher {
+                    version: Version::parse("0.2.0").unwrap(),
+                    publisher: Some("bob".to_string()),
+                },
+                VersionPublisher {
+                    version: Version::parse("0.1.0").unwrap(),
+                    publisher: None,
+                },
+            ]
+        );
+        assert_eq!(
+            cratesio_analyzer.get_owners("internal").unwrap(),
+            vec!["alice", "bob", "github:org:team"]
+        );
+
+        // The yanked 0.3.0 does not count as a newer version
+        let release_info = cratesio_analyzer
+            .get_release_info("internal", &Version::parse("0.1.0").unwrap())
+            .unwrap();
+        assert_eq!(
+            release_info.created_at.to_rfc3339(),
+            "2021-01-01T12:30:00+00:00"
+        );
+        assert!(release_info.age_days > 100);
+        assert!(release_info.yanked);
+        assert_eq!(release_info.newer_versions, 1);
+        assert!(cratesio_analyzer
+            .get_release_info("internal", &Version::parse("0.4.0").unwrap())
+            .is_err());
+
+        // Release info of both versions of an update and the publishers from one response
+        let crate_versions = cratesio_analyzer.get_versions("internal").unwrap();
+        let old_release_info = crate_versions
+            .get_release_info(&Version::parse("0.1.0").unwrap())
+            .unwrap();
+        let new_release_info = crate_versions
+            .get_release_info(&Version::parse("0.2.0").unwrap())
+            .unwrap();
+        assert_eq!(old_release_info.created_at, release_info.created_at);
+        assert!(!new_release_info.yanked);
+        assert_eq!(new_release_info.newer_versions, 0);
+        assert_eq!(crate_versions.get_version_publishers().unwrap(), publishers);
+    }
+
+    #[test]
+    fn test_cratesio_release_info_pre_releases() {
+        let versions = serde_json::json!([
+            {"num": "2.0.0-beta.1", "created_at": "2021-03-01T00:00:00.000000+00:00", "yanked": false},
+            {"num": "2.0.0-alpha.1", "created_at": "2021-02-01T00:00:00.000000+00:00", "yanked": false},
+            {"num": "1.0.0", "created_at": "2021-01-01T00:00:00.000000+00:00", "yanked": false}
+        ]);
+        let crate_versions = CrateVersions {
+            name: "internal".to_string(),
+            versions: versions.as_array().unwrap().clone(),
+        };
+
+        // Pre-releases are not newer versions for a stable release
+        let release_info = crate_versions
+            .get_release_info(&Version::parse("1.0.0").unwrap())
+            .unwrap();
+        assert_eq!(release_info.newer_versions, 0);
+
+        // but are for a pre-release
+        let release_info = crate_versions
+            .get_release_info(&Version::parse("2.0.0-alpha.1").unwrap())
+            .unwrap();
+        assert_eq!(release_info.newer_versions, 1);
+    }
 }
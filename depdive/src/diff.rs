@@ -5,7 +5,8 @@ use camino::Utf8Path;
 use flate2::read::GzDecoder;
 use git2::{
     build::CheckoutBuilder, AutotagOption, Commit, Delta, Diff, DiffOptions, Direction,
-    FetchOptions, IndexAddOption, Oid, Repository, Signature, Tree,
+    FetchOptions, IndexAddOption, ObjectType, Oid, Repository, Signature, Tree, TreeWalkMode,
+    TreeWalkResult,
 };
 use regex::Regex;
 use reqwest::blocking::Client;
@@ -13,10 +14,11 @@ use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::{
-    collections::{HashMap, HashSet},
-    fs::{read_dir, DirEntry, File},
+    collections::{BTreeSet, HashMap, HashSet},
+    fs::{read_dir, rename, DirEntry, File},
     io::copy,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 use tar::Archive;
 use tempfile::{tempdir, TempDir};
@@ -24,6 +26,12 @@ use thiserror::Error;
 use url::Url;
 use walkdir::WalkDir;
 
+use crate::cache::DepdiveCache;
+use crate::file_kind::{
+    classify_file, CrateTargets, DiffSeverity, NativeArtifacts, SourceFileKind,
+};
+use crate::manifest_diff::{get_manifest_diff, ManifestDiff};
+use crate::registry::Registry;
 use crate::super_toml::{CargoTomlParser, CargoTomlType};
 
 /// This type presents information on the difference
@@ -38,6 +46,35 @@ pub struct CrateSourceDiffReport {
     pub release_commit_analyzed: Option<bool>,
     pub is_different: Option<bool>,
     pub file_diff_stats: Option<FileDiffStats>,
+    // None when the crates.io package has no Cargo.toml.orig to compare
+    pub manifest_diff: Option<ManifestDiff>,
+    // Binaries and native code in crates.io that are not in the git source
+    pub native_artifacts: Option<NativeArtifacts>,
+}
+
+impl CrateSourceDiffReport {
+    /// Highest severity among the differences,
+    /// None when no difference is found or the comparison failed
+    pub fn get_severity(&self) -> Option<DiffSeverity> {
+        if self.is_different != Some(true) {
+            return None;
+        }
+        let manifest_severity = self
+            .manifest_diff
+            .as_ref()
+            .filter(|diff| !diff.is_empty())
+            .map(|_| DiffSeverity::High);
+        match &self.file_diff_stats {
+            Some(stats) => stats
+                .classified_files
+                .iter()
+                .map(|file| file.severity)
+                .max()
+                .max(manifest_severity),
+            // unclassified differences are assumed to be code-relevant
+            None => Some(DiffSeverity::High),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -45,13 +82,29 @@ pub struct FileDiffStats {
     pub files_added: HashSet<String>,
     pub files_modified: HashSet<String>,
     pub files_deleted: HashSet<String>,
+    // Added and modified files, sorted by descending severity
+    pub classified_files: Vec<ClassifiedFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClassifiedFile {
+    pub path: String,
+    pub kind: SourceFileKind,
+    pub severity: DiffSeverity,
 }
 
 pub struct DiffAnalyzer {
-    dir: TempDir,   // hold temporary code files
-    client: Client, // for downloading files
+    dir: TempDir,                // hold temporary code files
+    client: Client,              // for downloading files
+    cache: Option<DepdiveCache>, // persistent cache for tarballs and git repos
+    registry: Registry,          // to download crate tarballs from
+    fetched_git_repos: FetchedGitRepos,
 }
 
+/// Cached git repos already fetched in a run,
+/// shared by the analyzers of the run so that each repo is fetched once
+pub(crate) type FetchedGitRepos = Arc<Mutex<HashSet<PathBuf>>>;
+
 #[derive(Debug, Error)]
 #[error("Head commit not found in the repository for {crate_name}:{version}")]
 pub struct HeadCommitNotFoundError {
@@ -63,6 +116,10 @@ pub(crate) struct VersionDiffInfo<'a> {
     pub repo: &'a Repository,
     pub commit_a: Oid,
     pub commit_b: Oid,
+    // crate directory within the repository at each commit,
+    // the paths in the diff are relative to these
+    pub crate_path_a: PathBuf,
+    pub crate_path_b: PathBuf,
     pub diff: Diff<'a>,
 }
 
@@ -111,13 +168,40 @@ pub(crate) fn get_all_paths_for_filename(dir_path: &Path, file_name: &str) -> Re
 }
 
 impl DiffAnalyzer {
+    /// Uses the persistent cache if DEPDIVE_CACHE_DIR is set
     pub fn new() -> Result<Self> {
         Ok(Self {
             dir: tempdir()?,
             client: Client::new(),
+            cache: DepdiveCache::from_env()?,
+            registry: Registry::crates_io(),
+            fetched_git_repos: FetchedGitRepos::default(),
         })
     }
 
+    pub fn with_cache(cache: DepdiveCache) -> Result<Self> {
+        Ok(Self {
+            dir: tempdir()?,
+            client: Client::new(),
+            cache: Some(cache),
+            registry: Registry::crates_io(),
+            fetched_git_repos: FetchedGitRepos::default(),
+        })
+    }
+
+    /// Downloads crates from the given registry instead of crates.io
+    pub fn with_registry(self, registry: Registry) -> Self {
+        Self { registry, ..self }
+    }
+
+    /// Shares the record of fetched git repos with other analyzers of the run
+    pub(crate) fn with_fetched_git_repos(self, fetched_git_repos: FetchedGitRepos) -> Self {
+        Self {
+            fetched_git_repos,
+            ..self
+        }
+    }
+
     /// Given a crate version and its source repository,
     /// returns a report on differences between the source
     /// and code hosted on crates.io
@@ -166,7 +250,15 @@ impl DiffAnalyzer {
             };
 
         // Add git repo as a remote to crate repo
-        self.setup_remote(&crate_repo, &repository, &head_commit_oid.to_string())?;
+        // from the local clone to avoid downloading the repo again
+        self.setup_remote(
+            &crate_repo,
+            git_repo
+                .path()
+                .to_str()
+                .ok_or_else(|| anyhow!("no local path found for repository"))?,
+            &head_commit_oid.to_string(),
+        )?;
 
         // At this point, crate_repo contains crate.io hosted source with a single commit
         //                and git source as a remote
@@ -207,7 +299,24 @@ impl DiffAnalyzer {
             Some(&mut DiffOptions::new()),
         )?;
 
-        let file_diff_stats = self.get_crate_source_file_diff_report(&diff)?;
+        // Build targets as published on crates.io
+        let crate_targets =
+            match Self::get_tree_file_content(&crate_repo, &cratesio_tree, "Cargo.toml")? {
+                Some(toml) => CrateTargets::from_crate(
+                    &toml,
+                    &Self::get_tree_files(&cratesio_tree)?,
+                    |path| {
+                        Self::get_tree_file_content(&crate_repo, &cratesio_tree, path)
+                            .ok()
+                            .flatten()
+                    },
+                )?,
+                None => CrateTargets::default(),
+            };
+        let (file_diff_stats, native_artifacts) =
+            self.get_crate_source_file_diff_report(&crate_repo, &diff, &crate_targets)?;
+        let manifest_diff =
+            self.get_crate_source_manifest_diff(&crate_repo, &crate_git_tree, &cratesio_tree)?;
 
         // reset repo
         git_repo.checkout_tree(
@@ -224,9 +333,12 @@ impl DiffAnalyzer {
                 // Ignoring files from source not included in crates.io, possibly ignored
                 is_different: Some(
                     !file_diff_stats.files_added.is_empty()
-                        || !file_diff_stats.files_modified.is_empty(),
+                        || !file_diff_stats.files_modified.is_empty()
+                        || manifest_diff.as_ref().is_some_and(|diff| !diff.is_empty()),
                 ),
                 file_diff_stats: Some(file_diff_stats),
+                manifest_diff,
+                native_artifacts: Some(native_artifacts),
             }
         })
     }
@@ -240,25 +352,148 @@ impl DiffAnalyzer {
         self.init_git(&path)
     }
 
-    fn get_cratesio_version(&self, name: &str, version: &str) -> Result<PathBuf> {
-        let download_path = format!(
-            "https://crates.io/api/v1/crates/{}/{}/download",
-            name, version
-        );
+    pub(crate) fn get_git_repo_for_registry_version(
+        &self,
+        registry: &Registry,
+        name: &str,
+        version: &str,
+    ) -> Result<Repository> {
+        let path = self.get_registry_version(registry, name, version)?;
+        self.init_git(&path)
+    }
+
+    /// Downloads from the analyzer's registry, crates.io by default
+    pub(crate) fn get_cratesio_version(&self, name: &str, version: &str) -> Result<PathBuf> {
+        self.get_registry_version(&self.registry, name, version)
+    }
+
+    pub(crate) fn get_registry_version(
+        &self,
+        registry: &Registry,
+        name: &str,
+        version: &str,
+    ) -> Result<PathBuf> {
+        let download_path = registry.get_download_url(name, version)?;
         let dest_file = format!("{}-{}-cratesio", name, version);
-        self.download_file(&download_path, &dest_file)
+
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.download_file(&download_path, &dest_file),
+        };
+
+        // Unpack the cached tarball in tempdir
+        // as the unpacked code may get modified, e.g., by init_git
+        let dest_path = self.dir.path().join(&dest_file);
+        if !dest_path.exists() {
+            let targz_path = cache.get_crate_tarball_path(&registry.index, name, version);
+            if !targz_path.exists() {
+                self.fetch_file(&download_path, &targz_path)?;
+            }
+            cache.touch(&targz_path)?;
+            self.decompress_targz(&targz_path, &dest_path)?;
+        }
+        self.get_unpacked_dir(&dest_path)
     }
 
     pub(crate) fn get_git_repo(&self, name: &str, url: &str) -> Result<Repository> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => {
+                let dest_file = format!("{}-source", name);
+                let dest_path = self.dir.path().join(&dest_file);
+                if !dest_path.exists() {
+                    Repository::clone(url, &dest_path)?;
+                }
+                let repo = Repository::open(dest_path)?;
+                return Ok(repo);
+            }
+        };
+
+        // Fetch into a cached clone instead of cloning again
+        let cached_path = cache.get_git_repo_path(name, url);
+        {
+            let lock = cache.get_git_repo_lock(&cached_path);
+            let guard = lock.lock().unwrap();
+            if !cached_path.exists() {
+                // Clone in a separate path first so that
+                // an interrupted clone is not left in the cache
+                let partial_dir = tempfile::Builder::new()
+                    .prefix(".partial")
+                    .tempdir_in(cached_path.parent().unwrap_or(&cached_path))?;
+                Repository::clone(url, partial_dir.path())?;
+                rename(partial_dir.into_path(), &cached_path)?;
+            } else if !self
+                .fetched_git_repos
+                .lock()
+                .unwrap()
+                .contains(&cached_path)
+            {
+                self.update_git_repo(&Repository::open(&cached_path)?)?;
+            }
+            self.fetched_git_repos
+                .lock()
+                .unwrap()
+                .insert(cached_path.clone());
+            cache.touch(&cached_path)?;
+            drop(guard);
+            cache.release_git_repo_lock(&cached_path, lock);
+        }
+
+        // Analyses checkout trees in the repo,
+        // therefore, work on a local clone of the cached repo
+        // to not interfere with other analyzers using the cache
         let dest_file = format!("{}-source", name);
         let dest_path = self.dir.path().join(&dest_file);
         if !dest_path.exists() {
-            Repository::clone(url, &dest_path)?;
+            let repo = Repository::clone(
+                cached_path
+                    .to_str()
+                    .ok_or_else(|| anyhow!("invalid unicode in path: {:?}", cached_path))?,
+                &dest_path,
+            )?;
+            // A clone only has the checked out branch of the cached repo,
+            // fetch its other branches and tags as a clone from origin has
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.download_tags(AutotagOption::All);
+            repo.find_remote("origin")?.fetch(
+                &[
+                    "+refs/remotes/origin/*:refs/remotes/origin/*",
+                    "+refs/tags/*:refs/tags/*",
+                ],
+                Some(&mut fetch_options),
+                None,
+            )?;
         }
         let repo = Repository::open(dest_path)?;
         Ok(repo)
     }
 
+    // Fetch a cached clone from origin
+    // and fast-forward the checked out branch
+    fn update_git_repo(&self, repo: &Repository) -> Result<()> {
+        let mut remote = repo.find_remote("origin")?;
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.download_tags(AutotagOption::All);
+        remote.fetch(
+            &["+refs/heads/*:refs/remotes/origin/*"],
+            Some(&mut fetch_options),
+            None,
+        )?;
+
+        let head = repo.head()?;
+        let (head_name, branch) = match (head.name(), head.shorthand()) {
+            (Some(head_name), Some(branch)) if head.is_branch() => (head_name, branch),
+            _ => return Ok(()),
+        };
+        let remote_oid = repo.refname_to_id(&format!("refs/remotes/origin/{}", branch))?;
+        repo.reference(head_name, remote_oid, true, "depdive: fast-forward cache")?;
+
+        let mut checkout_builder = CheckoutBuilder::new();
+        checkout_builder.force();
+        repo.checkout_head(Some(&mut checkout_builder))?;
+        Ok(())
+    }
+
     fn get_repo_dir(&self, repo: &Repository) -> Result<PathBuf> {
         Ok(PathBuf::from(repo.path().parent().ok_or_else(|| {
             anyhow!("Fatal: .git file has no parent")
@@ -273,14 +508,28 @@ impl DiffAnalyzer {
         if !dest_path.exists() {
             // First download the file as tar_gz
             let targz_path = self.dir.path().join(format!("{}.targ.gz", dest_file));
-            let mut targz_file = File::create(&targz_path)?;
-            let mut response = self.client.get(download_path).send()?;
-            copy(&mut response, &mut targz_file)?;
+            self.fetch_file(download_path, &targz_path)?;
 
             // Then decompress the file
             self.decompress_targz(&targz_path, &dest_path)?;
         }
 
+        self.get_unpacked_dir(&dest_path)
+    }
+
+    fn fetch_file(&self, download_path: &str, dest_path: &Path) -> Result<()> {
+        // Download in a separate file first so that
+        // a failed or concurrent download does not leave a corrupt file behind
+        let mut file = tempfile::Builder::new()
+            .prefix(".partial")
+            .tempfile_in(dest_path.parent().unwrap_or(dest_path))?;
+        let mut response = self.client.get(download_path).send()?.error_for_status()?;
+        copy(&mut response, &mut file)?;
+        file.persist(dest_path)?;
+        Ok(())
+    }
+
+    fn get_unpacked_dir(&self, dest_path: &Path) -> Result<PathBuf> {
         // Get the only directory within dest_path where files are unpacked
         let entries: Vec<DirEntry> = read_dir(dest_path)?
             .filter_map(|entry| entry.ok())
@@ -572,13 +821,57 @@ impl DiffAnalyzer {
         Ok(tree)
     }
 
-    fn get_crate_source_file_diff_report(&self, diff: &Diff) -> Result<FileDiffStats> {
+    fn get_crate_source_manifest_diff(
+        &self,
+        repo: &Repository,
+        git_tree: &Tree,
+        cratesio_tree: &Tree,
+    ) -> Result<Option<ManifestDiff>> {
+        let cratesio_toml =
+            match Self::get_tree_file_content(repo, cratesio_tree, "Cargo.toml.orig")? {
+                Some(toml) => toml,
+                None => return Ok(None),
+            };
+        let git_toml = Self::get_tree_file_content(repo, git_tree, "Cargo.toml")?
+            .ok_or_else(|| anyhow!("Cargo.toml not found in the git source"))?;
+        Ok(Some(get_manifest_diff(&git_toml, &cratesio_toml)?))
+    }
+
+    fn get_tree_file_content(repo: &Repository, tree: &Tree, path: &str) -> Result<Option<String>> {
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(_) => return Ok(None),
+        };
+        let blob = entry.to_object(repo)?.peel_to_blob()?;
+        Ok(Some(String::from_utf8_lossy(blob.content()).to_string()))
+    }
+
+    // Paths of all files in a tree
+    fn get_tree_files(tree: &Tree) -> Result<BTreeSet<String>> {
+        let mut files: BTreeSet<String> = BTreeSet::new();
+        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if let (Some(ObjectType::Blob), Some(name)) = (entry.kind(), entry.name()) {
+                files.insert(format!("{}{}", dir, name));
+            }
+            TreeWalkResult::Ok
+        })?;
+        Ok(files)
+    }
+
+    fn get_crate_source_file_diff_report(
+        &self,
+        repo: &Repository,
+        diff: &Diff,
+        crate_targets: &CrateTargets,
+    ) -> Result<(FileDiffStats, NativeArtifacts)> {
         let mut files_added: HashSet<String> = HashSet::new();
         let mut files_modified: HashSet<String> = HashSet::new();
         let mut files_deleted: HashSet<String> = HashSet::new();
+        let mut classified_files: Vec<ClassifiedFile> = Vec::new();
+        let mut native_artifacts = NativeArtifacts::default();
 
         // Ignore below files as they are changed whenever publishing to crates.io
-        // TODO: compare Cargo.toml.orig in crates.io with Cargo.toml in git
+        // Cargo.toml.orig is compared with Cargo.toml in git separately
         let ignore_paths: HashSet<&str> = vec![
             ".cargo_vcs_info.json",
             "Cargo.toml",
@@ -607,10 +900,22 @@ impl DiffAnalyzer {
                 continue;
             }
 
-            // TODO: Many times files like README are added/modified
-            // by having only a single line in crates.io and deleting original contents
-            // Also, we need to distinguish non source-code file here
-            // to avoid noise in warning
+            // Files like README are often replaced with a stub in crates.io,
+            // therefore, added and modified files are classified
+            // to tell code-relevant differences from the noise
+            if matches!(diff_delta.status(), Delta::Added | Delta::Modified) {
+                let blob = repo.find_blob(diff_delta.new_file().id()).ok();
+                if let (Delta::Added, Some(blob)) = (diff_delta.status(), &blob) {
+                    native_artifacts.add_file(&path, blob.content());
+                }
+                let kind = classify_file(&path, blob.as_ref().map(|b| b.content()), crate_targets);
+                classified_files.push(ClassifiedFile {
+                    path: path.clone(),
+                    kind,
+                    severity: kind.get_severity(),
+                });
+            }
+
             match diff_delta.status() {
                 Delta::Added => {
                     files_added.insert(path);
@@ -626,11 +931,21 @@ impl DiffAnalyzer {
             }
         }
 
-        Ok(FileDiffStats {
-            files_added,
-            files_modified,
-            files_deleted,
-        })
+        classified_files.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        Ok((
+            FileDiffStats {
+                files_added,
+                files_modified,
+                files_deleted,
+                classified_files,
+            },
+            native_artifacts,
+        ))
     }
 
     pub(crate) fn get_git_source_version_diff_info<'a>(
@@ -672,10 +987,86 @@ impl DiffAnalyzer {
             repo,
             commit_a: commit_oid_a,
             commit_b: commit_oid_b,
+            crate_path_a: toml_path.to_path_buf(),
+            crate_path_b: toml_path.to_path_buf(),
             diff,
         })
     }
 
+    /// Fetches a commit from the given url
+    /// if it is not already present in the repository,
+    /// e.g., a git dependency pinned to a commit not on any branch
+    pub(crate) fn fetch_git_commit(
+        &self,
+        repo: &Repository,
+        url: &str,
+        commit: &str,
+    ) -> Result<()> {
+        if repo.find_commit(Oid::from_str(commit)?).is_ok() {
+            return Ok(());
+        }
+        repo.remote_anonymous(url)?.fetch(&[commit], None, None)?;
+        repo.find_commit(Oid::from_str(commit)?)?;
+        Ok(())
+    }
+
+    /// Returns the diff of a crate directory between two commits of its repository,
+    /// e.g., when a git dependency moves from one revision to another
+    pub(crate) fn get_git_commit_diff_info<'a>(
+        &'a self,
+        name: &str,
+        repo: &'a Repository,
+        commit_a: &str,
+        commit_b: &str,
+    ) -> Result<VersionDiffInfo<'a>> {
+        // The crate can be moved within the repository between the commits
+        let commit_oid_a = Oid::from_str(commit_a)?;
+        let crate_path_a = self.locate_package_dir_at_commit(repo, name, commit_oid_a)?;
+        let tree_a = repo.find_commit(commit_oid_a)?.tree()?;
+        let tree_a = self.get_subdirectory_tree(repo, &tree_a, &crate_path_a)?;
+
+        let commit_oid_b = Oid::from_str(commit_b)?;
+        let crate_path_b = self.locate_package_dir_at_commit(repo, name, commit_oid_b)?;
+        let tree_b = repo.find_commit(commit_oid_b)?.tree()?;
+        let tree_b = self.get_subdirectory_tree(repo, &tree_b, &crate_path_b)?;
+
+        let diff =
+            repo.diff_tree_to_tree(Some(&tree_a), Some(&tree_b), Some(&mut DiffOptions::new()))?;
+
+        Ok(VersionDiffInfo {
+            repo,
+            commit_a: commit_oid_a,
+            commit_b: commit_oid_b,
+            crate_path_a,
+            crate_path_b,
+            diff,
+        })
+    }
+
+    fn locate_package_dir_at_commit(
+        &self,
+        repo: &Repository,
+        name: &str,
+        commit: Oid,
+    ) -> Result<PathBuf> {
+        let starter_commit = repo.head()?.peel_to_commit()?;
+        let mut checkout_builder = CheckoutBuilder::new();
+        checkout_builder.force();
+
+        repo.checkout_tree(
+            &repo.find_object(commit, None)?,
+            Some(&mut checkout_builder),
+        )?;
+        let toml_path = self.locate_package_toml(repo, name);
+
+        // reset head before return
+        repo.checkout_tree(starter_commit.as_object(), Some(&mut checkout_builder))?;
+        Ok(toml_path?
+            .parent()
+            .ok_or_else(|| anyhow!("Cannot find crate directory"))?
+            .to_path_buf())
+    }
+
     // This method takes two local repositories as input,
     //     Presumably two different versions of the same code base initiated in different repos
     //     For example, when comparing code for two versions of a crate hosted on crates.io;
@@ -714,6 +1105,8 @@ impl DiffAnalyzer {
             repo: repo_version_a,
             commit_a: version_a_commit.id(),
             commit_b: version_b_commit.id(),
+            crate_path_a: PathBuf::new(),
+            crate_path_b: PathBuf::new(),
             diff,
         })
     }
@@ -817,6 +1210,145 @@ mod test {
         assert!(repo.path().exists());
     }
 
+    #[test]
+    fn test_diff_cached_cratesio_version() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = DepdiveCache::new(cache_dir.path()).unwrap();
+
+        // Put a crate tarball in the cache so that no download is required
+        let targz_path =
+            cache.get_crate_tarball_path(crate::registry::CRATES_IO_INDEX, "unsafe_code", "0.1.0");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&targz_path).unwrap(),
+            flate2::Compression::default(),
+        ));
+        builder
+            .append_dir_all("unsafe_code-0.1.0", "resources/test/unsafe_code")
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        for _ in 0..2 {
+            let diff_analyzer = DiffAnalyzer::with_cache(cache.clone()).unwrap();
+            let path = diff_analyzer
+                .get_cratesio_version("unsafe_code", "0.1.0")
+                .unwrap();
+            assert!(path.ends_with("unsafe_code-0.1.0"));
+            assert!(path.join("src/lib.rs").exists());
+            // the cached tarball is not modified by analysis
+            diff_analyzer.init_git(&path).unwrap();
+        }
+        assert!(targz_path.exists());
+    }
+
+    #[test]
+    fn test_diff_registry_version() {
+        // A registry stand-in serving a crate tarball
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        builder
+            .append_dir_all("unsafe_code-0.1.0", "resources/test/unsafe_code")
+            .unwrap();
+        let targz = builder.into_inner().unwrap().finish().unwrap();
+        let mut routes: HashMap<String, Vec<u8>> = HashMap::new();
+        routes.insert("/dl/un/sa/unsafe_code/0.1.0".to_string(), targz);
+        let url = crate::registry::test::serve_http(routes);
+
+        let registry = Registry {
+            index: format!("sparse+{}/index/", url),
+            dl: format!("{}/dl/{{prefix}}/{{crate}}/{{version}}", url),
+            api: None,
+        };
+        let diff_analyzer = DiffAnalyzer::new().unwrap().with_registry(registry);
+        let path = diff_analyzer
+            .get_cratesio_version("unsafe_code", "0.1.0")
+            .unwrap();
+        assert!(path.join("src/lib.rs").exists());
+        assert!(diff_analyzer
+            .get_cratesio_version("unsafe_code", "0.2.0")
+            .is_err());
+    }
+
+    #[test]
+    fn test_diff_cached_git_repo() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = DepdiveCache::new(cache_dir.path()).unwrap();
+
+        // A local repository as the remote
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::write(source_dir.path().join("lib.rs"), "fn a() {}").unwrap();
+        let fetched_git_repos = FetchedGitRepos::default();
+        let diff_analyzer = DiffAnalyzer::with_cache(cache.clone())
+            .unwrap()
+            .with_fetched_git_repos(fetched_git_repos.clone());
+        let source = diff_analyzer.init_git(source_dir.path()).unwrap();
+        let url = source_dir.path().to_str().unwrap();
+
+        // A tagged commit on a branch other than the checked out one
+        let signature = Signature::now("user", "email@domain.com").unwrap();
+        let head = source.head().unwrap().peel_to_commit().unwrap();
+        let side_commit = source
+            .commit(
+                Some("refs/heads/side"),
+                &signature,
+                &signature,
+                "side",
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap();
+        source
+            .tag_lightweight(
+                "v0.1.0",
+                &source.find_object(side_commit, None).unwrap(),
+                false,
+            )
+            .unwrap();
+
+        let repo = diff_analyzer.get_git_repo("source", url).unwrap();
+        let cached_path = cache.get_git_repo_path("source", url);
+        assert!(cached_path.exists());
+        // the clone of the cached repo has all branches and tags of the remote
+        assert!(repo.find_commit(side_commit).is_ok());
+        assert_eq!(repo.revparse_single("v0.1.0").unwrap().id(), side_commit);
+        assert!(repo.find_reference("refs/remotes/origin/side").is_ok());
+        // the analyzer works on its own clone of the cached repo
+        assert!(!repo.workdir().unwrap().starts_with(cache_dir.path()));
+        assert_eq!(
+            repo.head().unwrap().peel_to_commit().unwrap().id(),
+            source.head().unwrap().peel_to_commit().unwrap().id()
+        );
+
+        // Add a commit to the remote
+        std::fs::write(source_dir.path().join("lib.rs"), "fn b() {}").unwrap();
+        let mut index = source.index().unwrap();
+        index
+            .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = source.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = source.head().unwrap().peel_to_commit().unwrap();
+        let commit = source
+            .commit(Some("HEAD"), &signature, &signature, "b", &tree, &[&parent])
+            .unwrap();
+
+        // The cached repo is fetched once per run
+        let diff_analyzer = DiffAnalyzer::with_cache(cache.clone())
+            .unwrap()
+            .with_fetched_git_repos(fetched_git_repos);
+        let repo = diff_analyzer.get_git_repo("source", url).unwrap();
+        assert_ne!(repo.head().unwrap().peel_to_commit().unwrap().id(), commit);
+
+        // A new run fetches the cached clone instead of cloning
+        let diff_analyzer = DiffAnalyzer::with_cache(cache).unwrap();
+        let repo = diff_analyzer.get_git_repo("source", url).unwrap();
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().id(), commit);
+        assert_eq!(
+            std::fs::read_to_string(repo.workdir().unwrap().join("lib.rs")).unwrap(),
+            "fn b() {}"
+        );
+    }
+
     #[test]
     fn test_diff_head_commit_oid_for_version_from_tags() {
         let diff_analyzer = get_test_diff_analyzer();
@@ -929,6 +1461,7 @@ mod test {
 
                 if package.name() == "guppy" {
                     assert!(!report.is_different.unwrap());
+                    assert!(report.manifest_diff.unwrap().is_empty());
                 }
             }
         }
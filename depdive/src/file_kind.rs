@@ -0,0 +1,636 @@
+//! This module classifies the files of a crate by their kind,
+//! e.g., rust source, build script, docs, or tests,
+//! to tell the differences that affect the compiled code
+//! from the noise, e.g., a README stub added while publishing
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::crate_layout::{
+    get_module_dir, get_module_file, get_path_attr, join_path, normalize_path, ManifestTargets,
+};
+
+const DOCS_EXTENSIONS: [&str; 10] = [
+    "md", "markdown", "txt", "rst", "adoc", "html", "png", "jpg", "gif", "svg",
+];
+const DOCS_FILE_NAMES: [&str; 10] = [
+    "README",
+    "LICENSE",
+    "LICENSE-MIT",
+    "LICENSE-APACHE",
+    "LICENCE",
+    "COPYING",
+    "COPYRIGHT",
+    "CHANGELOG",
+    "NOTICE",
+    "AUTHORS",
+];
+const DOCS_DIRS: [&str; 2] = ["doc", "docs"];
+const BINARY_EXTENSIONS: [&str; 12] = [
+    "a", "so", "dylib", "dll", "lib", "o", "obj", "exe", "wasm", "bin", "rlib", "jar",
+];
+const NATIVE_SOURCE_EXTENSIONS: [&str; 9] = ["c", "h", "cc", "cpp", "hpp", "cxx", "s", "S", "asm"];
+const VENDORED_DIRS: [&str; 4] = ["vendor", "vendored", "third_party", "third-party"];
+const TEST_DIRS: [&str; 5] = ["tests", "test", "benches", "examples", "fuzz"];
+const GENERATED_MARKERS: [&str; 3] = ["@generated", "automatically generated", "auto-generated"];
+
+// A single native file smaller than this, e.g., a C shim, is not a vendored library
+const MIN_VENDORED_SIZE: u64 = 16 * 1024;
+// Same as git, a file with a NUL byte in the first 8000 bytes is binary
+const BINARY_CHECK_LEN: usize = 8000;
+// Generated markers are looked for at the top of a file
+const GENERATED_CHECK_LEN: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SourceFileKind {
+    BuildScript,
+    ProcMacroEntry,
+    RustSource,
+    BinaryOrVendored, // binaries, native code, and files in vendored directories
+    Generated,
+    Docs,
+    Test, // tests, benches, examples, and fuzz targets
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DiffSeverity {
+    Low,    // cannot affect the compiled code, e.g., docs and tests
+    Medium, // may affect the compiled code, e.g., generated code and data files
+    High,   // code-relevant, e.g., rust source and build scripts
+}
+
+impl SourceFileKind {
+    pub fn get_severity(&self) -> DiffSeverity {
+        match self {
+            Self::BuildScript
+            | Self::ProcMacroEntry
+            | Self::RustSource
+            | Self::BinaryOrVendored => DiffSeverity::High,
+            Self::Generated | Self::Other => DiffSeverity::Medium,
+            Self::Docs | Self::Test => DiffSeverity::Low,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BinaryFormat {
+    Elf,
+    MachO,
+    Pe,
+    Archive, // static libraries, e.g., .a, .lib, and .rlib
+    Wasm,
+    Compressed,
+    Other, // not valid UTF-8 text
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BinaryFile {
+    pub path: String,
+    pub format: BinaryFormat,
+    pub size: u64, // in bytes
+}
+
+/// A directory of added native, e.g., C/C++, source files
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VendoredNativeDir {
+    pub path: String,
+    pub files: u64,
+    pub size: u64, // in bytes
+}
+
+/// Binary files and vendored native code added to a crate
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct NativeArtifacts {
+    pub binary_files: Vec<BinaryFile>,
+    pub vendored_native_dirs: Vec<VendoredNativeDir>,
+    // all directories with added native files, vendored or not
+    #[serde(skip)]
+    native_dirs: Vec<VendoredNativeDir>,
+}
+
+impl NativeArtifacts {
+    pub fn is_empty(&self) -> bool {
+        self.binary_files.is_empty() && self.vendored_native_dirs.is_empty()
+    }
+
+    /// Records an added file if it is a binary or a native source file
+    pub fn add_file(&mut self, path: &str, content: &[u8]) {
+        let path = normalize_path(path);
+        let size = content.len() as u64;
+        if let Some(format) = get_binary_format(content) {
+            self.binary_files.push(BinaryFile { path, format, size });
+            return;
+        }
+
+        let extension = Path::new(&path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
+        if !NATIVE_SOURCE_EXTENSIONS.contains(&extension) {
+            return;
+        }
+        let dir = get_vendored_dir(&path);
+        match self
+            .native_dirs
+            .iter_mut()
+            .find(|native_dir| native_dir.path == dir)
+        {
+            Some(native_dir) => {
+                native_dir.files += 1;
+                native_dir.size += size;
+            }
+            None => self.native_dirs.push(VendoredNativeDir {
+                path: dir,
+                files: 1,
+                size,
+            }),
+        }
+        self.vendored_native_dirs = self
+            .native_dirs
+            .iter()
+            .filter(|native_dir| native_dir.files > 1 || native_dir.size >= MIN_VENDORED_SIZE)
+            .cloned()
+            .collect();
+    }
+}
+
+// Native files are grouped by their top directory in the crate,
+// or the one under a vendor directory, e.g., vendor/zlib
+fn get_vendored_dir(path: &str) -> String {
+    let dirs: Vec<&str> = path.split('/').collect();
+    let dirs = &dirs[..dirs.len() - 1];
+    match dirs {
+        [] => ".".to_string(),
+        [vendor, dir, ..] if VENDORED_DIRS.contains(vendor) => format!("{}/{}", vendor, dir),
+        [dir, ..] => dir.to_string(),
+    }
+}
+
+/// Build targets of a crate that are riskier than the rest of its code,
+/// and the rust files compiled into any of its targets
+#[derive(Debug, Default, Clone)]
+pub struct CrateTargets {
+    pub build_script: Option<String>,
+    pub proc_macro_entry: Option<String>,
+    pub target_files: BTreeSet<String>, // lib, bin, build, and proc-macro roots and their modules
+}
+
+impl CrateTargets {
+    /// Targets as declared in a package Cargo.toml,
+    /// with Cargo's defaults when not declared
+    pub fn from_manifest(toml: &str) -> Result<Self> {
+        // build.rs is the build script in any version of the crate a diff is against
+        let targets = ManifestTargets::from_manifest(toml, |_| true)?;
+        Ok(Self {
+            build_script: targets.build_script,
+            proc_macro_entry: if targets.is_proc_macro {
+                Some(targets.lib)
+            } else {
+                None
+            },
+            target_files: BTreeSet::new(),
+        })
+    }
+
+    /// Targets of a crate along with the rust files reachable from them,
+    /// files lists the files of the crate and
+    /// read_file returns the content of a file relative to the crate directory
+    pub fn from_crate(
+        toml: &str,
+        files: &BTreeSet<String>,
+        read_file: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let mut targets = Self::from_manifest(toml)?;
+        let manifest_targets = ManifestTargets::from_manifest(toml, |file| files.contains(file))?;
+
+        let mut roots: Vec<String> = vec![manifest_targets.lib];
+        roots.extend(manifest_targets.build_script);
+        roots.extend(manifest_targets.bins);
+        // Binaries discovered by Cargo, unless autobins = false
+        if manifest_targets.autobins {
+            roots.extend(files.iter().filter(|file| is_default_bin(file)).cloned());
+        }
+
+        let mut visited: BTreeSet<String> = BTreeSet::new();
+        // (file, directory of the modules it declares)
+        let mut pending: Vec<(String, String)> = roots
+            .into_iter()
+            .filter(|root| files.contains(root))
+            .map(|root| {
+                let module_dir = get_module_dir(&root, true);
+                (root, module_dir)
+            })
+            .collect();
+        while let Some((file, module_dir)) = pending.pop() {
+            if !visited.insert(file.clone()) {
+                continue;
+            }
+            match read_file(&file).and_then(|source| syn::parse_file(&source).ok()) {
+                Some(syntax) => {
+                    let mut modules: Vec<(String, String)> = Vec::new();
+                    get_module_files(
+                        &syntax.items,
+                        (&file, &module_dir),
+                        &read_file,
+                        &mut modules,
+                    );
+                    pending.extend(modules.into_iter().filter(|(file, _)| files.contains(file)));
+                }
+                // A file that cannot be parsed may declare any module in its directory
+                None => pending.extend(
+                    files
+                        .iter()
+                        .filter(|file| {
+                            file.ends_with(".rs") && file.starts_with(&format!("{}/", module_dir))
+                        })
+                        .map(|file| (file.clone(), get_module_dir(file, false))),
+                ),
+            }
+        }
+
+        targets.target_files = visited;
+        Ok(targets)
+    }
+}
+
+// src/main.rs, src/bin/*.rs, and src/bin/*/main.rs
+fn is_default_bin(file: &str) -> bool {
+    let components: Vec<&str> = file.split('/').collect();
+    match components.as_slice() {
+        ["src", "main.rs"] | ["src", "bin", _, "main.rs"] => true,
+        ["src", "bin", name] => name.ends_with(".rs"),
+        _ => false,
+    }
+}
+
+// Out of line modules declared in the items of a file, including within inline modules
+fn get_module_files(
+    items: &[syn::Item],
+    (file, module_dir): (&str, &str),
+    read_file: &impl Fn(&str) -> Option<String>,
+    modules: &mut Vec<(String, String)>,
+) {
+    for item in items {
+        if let syn::Item::Mod(i) = item {
+            match &i.content {
+                Some((_, items)) => get_module_files(
+                    items,
+                    (file, &join_path(module_dir, &i.ident.to_string())),
+                    read_file,
+                    modules,
+                ),
+                None => {
+                    let module_file = get_module_file(
+                        (file, module_dir),
+                        &i.ident.to_string(),
+                        get_path_attr(&i.attrs).as_deref(),
+                        read_file,
+                    );
+                    let module_dir = get_module_dir(&module_file, false);
+                    modules.push((module_file, module_dir));
+                }
+            }
+        }
+    }
+}
+
+/// Classifies a file by its path relative to the crate root,
+/// and its content when available
+pub fn classify_file(path: &str, content: Option<&[u8]>, targets: &CrateTargets) -> SourceFileKind {
+    let path = normalize_path(path);
+    let file_path = Path::new(&path);
+    let extension = file_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let dirs: Vec<&str> = path.split('/').rev().skip(1).collect();
+    let in_dirs = |names: &[&str]| dirs.iter().any(|dir| names.contains(dir));
+
+    // A binary is not what its name says, e.g., a blob under docs/
+    if BINARY_EXTENSIONS.contains(&extension)
+        || content.is_some_and(|content| get_binary_format(content).is_some())
+    {
+        return SourceFileKind::BinaryOrVendored;
+    }
+
+    if targets.build_script.as_deref() == Some(path.as_str()) {
+        return SourceFileKind::BuildScript;
+    }
+    if targets.proc_macro_entry.as_deref() == Some(path.as_str()) {
+        return SourceFileKind::ProcMacroEntry;
+    }
+    // Compiled code, whatever its name or directory
+    if targets.target_files.contains(&path) {
+        return SourceFileKind::RustSource;
+    }
+
+    if DOCS_EXTENSIONS.contains(&extension)
+        || DOCS_FILE_NAMES.contains(&file_name.to_uppercase().as_str())
+        || in_dirs(&DOCS_DIRS)
+    {
+        return SourceFileKind::Docs;
+    }
+
+    if NATIVE_SOURCE_EXTENSIONS.contains(&extension) || in_dirs(&VENDORED_DIRS) {
+        return SourceFileKind::BinaryOrVendored;
+    }
+
+    // Generated markers are only trusted for non-rust files,
+    // as anyone can put the marker on top of rust code
+    if file_name.contains(".generated.")
+        || file_name.contains("_generated.")
+        || in_dirs(&["generated"])
+        || (extension != "rs" && content.is_some_and(is_generated))
+    {
+        return SourceFileKind::Generated;
+    }
+
+    if in_dirs(&TEST_DIRS) || file_name.ends_with("_test.rs") || file_name.ends_with("_tests.rs") {
+        return SourceFileKind::Test;
+    }
+
+    if extension == "rs" {
+        return SourceFileKind::RustSource;
+    }
+    SourceFileKind::Other
+}
+
+/// Detects a binary file by its magic bytes,
+/// or a NUL byte or invalid UTF-8 in its content
+pub fn get_binary_format(content: &[u8]) -> Option<BinaryFormat> {
+    let magic_bytes: [(&[u8], BinaryFormat); 15] = [
+        (b"\x7fELF", BinaryFormat::Elf),
+        (b"\xfe\xed\xfa\xce", BinaryFormat::MachO),
+        (b"\xfe\xed\xfa\xcf", BinaryFormat::MachO),
+        (b"\xce\xfa\xed\xfe", BinaryFormat::MachO),
+        (b"\xcf\xfa\xed\xfe", BinaryFormat::MachO),
+        (b"\xca\xfe\xba\xbe", BinaryFormat::MachO),
+        (b"MZ", BinaryFormat::Pe),
+        (b"!<arch>\n", BinaryFormat::Archive),
+        (b"\0asm", BinaryFormat::Wasm),
+        (b"\x1f\x8b", BinaryFormat::Compressed),
+        (b"PK\x03\x04", BinaryFormat::Compressed),
+        (b"\xfd7zXZ\0", BinaryFormat::Compressed),
+        (b"\x28\xb5\x2f\xfd", BinaryFormat::Compressed),
+        (b"BZh", BinaryFormat::Compressed),
+        (b"7z\xbc\xaf\x27\x1c", BinaryFormat::Compressed),
+    ];
+    let is_text = !is_binary(content) && std::str::from_utf8(content).is_ok();
+    for (magic, format) in magic_bytes.iter() {
+        // Text files may start with the short magic bytes, e.g., MZ or BZh
+        if content.starts_with(magic) && (magic.len() > 3 || !is_text) {
+            return Some(*format);
+        }
+    }
+
+    if is_text {
+        None
+    } else {
+        Some(BinaryFormat::Other)
+    }
+}
+
+fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+fn is_generated(content: &[u8]) -> bool {
+    let head =
+        String::from_utf8_lossy(&content[..content.len().min(GENERATED_CHECK_LEN)]).to_lowercase();
+    GENERATED_MARKERS.iter().any(|marker| head.contains(marker))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_kind_crate_targets() {
+        let targets = CrateTargets::from_manifest(
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(targets.build_script, Some("build.rs".to_string()));
+        assert_eq!(targets.proc_macro_entry, None);
+
+        let targets = CrateTargets::from_manifest(
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            build = "./tools/build.rs"
+
+            [lib]
+            proc-macro = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(targets.build_script, Some("tools/build.rs".to_string()));
+        assert_eq!(targets.proc_macro_entry, Some("src/lib.rs".to_string()));
+    }
+
+    #[test]
+    fn test_file_kind_classify_file() {
+        let targets = CrateTargets {
+            build_script: Some("build.rs".to_string()),
+            proc_macro_entry: Some("src/lib.rs".to_string()),
+            target_files: BTreeSet::new(),
+        };
+        let cases: Vec<(&str, Option<&[u8]>, SourceFileKind)> = vec![
+            ("build.rs", None, SourceFileKind::BuildScript),
+            ("src/lib.rs", None, SourceFileKind::ProcMacroEntry),
+            (
+                "src/parser.rs",
+                Some(b"fn main() {}"),
+                SourceFileKind::RustSource,
+            ),
+            ("README.md", None, SourceFileKind::Docs),
+            ("LICENSE-MIT", None, SourceFileKind::Docs),
+            ("docs/guide.rs", None, SourceFileKind::Docs),
+            (
+                "docs/logo.svg",
+                Some(b"\x7fELF\0\0"),
+                SourceFileKind::BinaryOrVendored,
+            ),
+            (
+                "tests/data/blob",
+                Some(b"\x7fELF\0\0"),
+                SourceFileKind::BinaryOrVendored,
+            ),
+            ("libfoo.a", None, SourceFileKind::BinaryOrVendored),
+            (
+                "vendor/zlib/inflate.c",
+                None,
+                SourceFileKind::BinaryOrVendored,
+            ),
+            (
+                "src/tables.rs",
+                Some(b"// @generated by build tool\n"),
+                SourceFileKind::RustSource,
+            ),
+            (
+                "data/tables.json",
+                Some(b"// @generated by build tool\n"),
+                SourceFileKind::Generated,
+            ),
+            ("tests/integration.rs", None, SourceFileKind::Test),
+            ("examples/demo.rs", None, SourceFileKind::Test),
+            (
+                "data/latin1.in",
+                Some(b"caf\xe9"),
+                SourceFileKind::BinaryOrVendored,
+            ),
+            ("data/words.json", Some(b"[]"), SourceFileKind::Other),
+        ];
+        for (path, content, kind) in cases {
+            assert_eq!(classify_file(path, content, &targets), kind, "{}", path);
+        }
+
+        assert_eq!(
+            SourceFileKind::RustSource.get_severity(),
+            DiffSeverity::High
+        );
+        assert_eq!(
+            SourceFileKind::Generated.get_severity(),
+            DiffSeverity::Medium
+        );
+        assert_eq!(SourceFileKind::Docs.get_severity(), DiffSeverity::Low);
+    }
+
+    #[test]
+    fn test_file_kind_target_files() {
+        let files: Vec<(&str, &str)> =
+            vec![
+            ("Cargo.toml", "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n"),
+            (
+                "src/lib.rs",
+                "// @generated by build tool\nmod license;\nmod doc;\nmod inline { mod nested; }\n",
+            ),
+            ("src/license.rs", "pub fn check() {}"),
+            ("src/doc/mod.rs", "#[path = \"../readme.rs\"] mod readme;"),
+            ("src/readme.rs", "pub fn read() {}"),
+            ("src/inline/nested.rs", "pub fn nested() {}"),
+            ("src/main.rs", "mod cli_test;"),
+            ("src/cli_test.rs", "fn main() {}"),
+            ("src/bin/tool.rs", "fn main() {}"),
+            ("src/unused.rs", "pub fn unused() {}"),
+            ("docs/example.rs", "fn main() {}"),
+            ("README", "foo"),
+        ];
+        let read_file = |path: &str| {
+            files
+                .iter()
+                .find(|(file, _)| *file == path)
+                .map(|(_, content)| content.to_string())
+        };
+        let file_paths: BTreeSet<String> = files.iter().map(|(file, _)| file.to_string()).collect();
+        let targets =
+            CrateTargets::from_crate(&read_file("Cargo.toml").unwrap(), &file_paths, read_file)
+                .unwrap();
+        assert_eq!(
+            targets.target_files,
+            vec![
+                "src/bin/tool.rs",
+                "src/cli_test.rs",
+                "src/doc/mod.rs",
+                "src/inline/nested.rs",
+                "src/lib.rs",
+                "src/license.rs",
+                "src/main.rs",
+                "src/readme.rs",
+            ]
+            .into_iter()
+            .map(|file| file.to_string())
+            .collect::<BTreeSet<String>>()
+        );
+
+        // Code in a target is high severity regardless of its name, directory, or markers
+        let classify = |path: &str| {
+            let kind = classify_file(
+                path,
+                read_file(path).as_deref().map(str::as_bytes),
+                &targets,
+            );
+            (kind, kind.get_severity())
+        };
+        for path in &[
+            "src/license.rs",
+            "src/doc/mod.rs",
+            "src/readme.rs",
+            "src/lib.rs",
+            "src/cli_test.rs",
+        ] {
+            assert_eq!(
+                classify(path),
+                (SourceFileKind::RustSource, DiffSeverity::High),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            classify("docs/example.rs"),
+            (SourceFileKind::Docs, DiffSeverity::Low)
+        );
+        assert_eq!(
+            classify("README"),
+            (SourceFileKind::Docs, DiffSeverity::Low)
+        );
+    }
+
+    #[test]
+    fn test_file_kind_native_artifacts() {
+        let mut native_artifacts = NativeArtifacts::default();
+        native_artifacts.add_file("src/lib.rs", b"pub fn a() {}");
+        native_artifacts.add_file("README", b"MZ is a text file");
+        assert!(native_artifacts.is_empty());
+
+        native_artifacts.add_file("lib/libfoo.so", b"\x7fELF\x02\x01\x01\0");
+        native_artifacts.add_file("lib/foo.a", b"!<arch>\nfoo.o/");
+        native_artifacts.add_file("vendor/zlib/inflate.c", b"int inflate();");
+        native_artifacts.add_file("vendor/zlib/include/zlib.h", b"int inflate();\n");
+        native_artifacts.add_file("shim.c", b"void shim() {}");
+        native_artifacts.add_file("sqlite/sqlite3.c", &vec![b' '; 20 * 1024]);
+        assert_eq!(
+            native_artifacts.binary_files,
+            vec![
+                BinaryFile {
+                    path: "lib/libfoo.so".to_string(),
+                    format: BinaryFormat::Elf,
+                    size: 8,
+                },
+                BinaryFile {
+                    path: "lib/foo.a".to_string(),
+                    format: BinaryFormat::Archive,
+                    size: 14,
+                },
+            ]
+        );
+        assert_eq!(
+            native_artifacts.vendored_native_dirs,
+            vec![
+                VendoredNativeDir {
+                    path: "vendor/zlib".to_string(),
+                    files: 2,
+                    size: 29,
+                },
+                // a single large file, while the single small shim.c is not vendored
+                VendoredNativeDir {
+                    path: "sqlite".to_string(),
+                    files: 1,
+                    size: 20 * 1024,
+                },
+            ]
+        );
+    }
+}
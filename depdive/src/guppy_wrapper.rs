@@ -1,20 +1,75 @@
 //! Wrapper for some commonly used guppy calls
 
-use anyhow::Result;
-use guppy::graph::{DependencyDirection, PackageGraph, PackageMetadata};
+use crate::build_scan::{BuildTimeTarget, BuildTimeTargetKind};
+use anyhow::{anyhow, Result};
+use guppy::graph::{
+    BuildTargetId, BuildTargetKind, DependencyDirection, PackageGraph, PackageLink, PackageMetadata,
+};
 use guppy::PackageId;
 use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::iter;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::{fmt, iter};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Max number of paths collected to a package,
+/// as the number of paths grows exponentially with the depth of the graph
+pub const MAX_DEPENDENCY_PATHS: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum DependencyKind {
     Normal,
     Build,
     Dev,
 }
 
+impl fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependencyKind::Normal => write!(f, "normal"),
+            DependencyKind::Build => write!(f, "build"),
+            DependencyKind::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+/// A package on a dependency path,
+/// with the kind of the dependency on it from the previous package
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DependencyPathStep {
+    pub name: String,
+    pub version: Version,
+    pub kind: DependencyKind,
+}
+
+/// A chain of dependencies from a workspace member to a package,
+/// the last step being the package itself
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DependencyPath {
+    pub member: String,
+    pub steps: Vec<DependencyPathStep>,
+}
+
+impl fmt::Display for DependencyPath {
+    // e.g., app -> hyper 0.14.0 (normal) -> cc 1.0.0 (build)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.member)?;
+        for step in &self.steps {
+            write!(f, " -> {} {} ({})", step.name, step.version, step.kind)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the workspace members pull in a package
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DependencyPaths {
+    pub name: String,
+    pub version: Version,
+    pub shortest: Option<DependencyPath>, // None for a workspace member
+    pub all: Vec<DependencyPath>,         // ordered by length
+    pub truncated: bool,                  // paths beyond MAX_DEPENDENCY_PATHS are left out
+}
+
 pub(crate) fn get_direct_dependencies(graph: &PackageGraph) -> Vec<PackageMetadata> {
     graph
         .query_workspace()
@@ -49,6 +104,139 @@ pub(crate) fn get_package_dependencies<'a>(
     Ok(dependencies)
 }
 
+/// This function returns the workspace members
+/// that depend on a package other than through another member
+pub(crate) fn get_workspace_dependents(
+    graph: &PackageGraph,
+    package: &PackageMetadata,
+) -> Result<Vec<String>> {
+    let members: BTreeSet<String> = graph
+        .query_reverse(iter::once(package.id()))?
+        .resolve_with_fn(|_, link| !link.to().in_workspace())
+        .packages(DependencyDirection::Reverse)
+        .filter(|pkg| pkg.in_workspace())
+        .map(|pkg| pkg.name().to_string())
+        .collect();
+    Ok(members.into_iter().collect())
+}
+
+/// This function returns the paths from workspace members to a package
+/// through the reverse dependency links,
+/// a path ends at the first workspace member on it
+pub(crate) fn get_dependency_paths(package: &PackageMetadata) -> DependencyPaths {
+    let mut all: Vec<DependencyPath> = Vec::new();
+    let mut on_path: HashSet<&PackageId> = iter::once(package.id()).collect();
+    let truncated = !package.in_workspace()
+        && !collect_dependency_paths(*package, &mut Vec::new(), &mut on_path, &mut all);
+    all.sort_by(|a, b| {
+        a.steps
+            .len()
+            .cmp(&b.steps.len())
+            .then_with(|| a.to_string().cmp(&b.to_string()))
+    });
+
+    let shortest = if truncated {
+        get_shortest_dependency_path(package)
+    } else {
+        all.first().cloned()
+    };
+    DependencyPaths {
+        name: package.name().to_string(),
+        version: package.version().clone(),
+        shortest,
+        all,
+        truncated,
+    }
+}
+
+// Depth first over the reverse links, steps are kept from the package backwards,
+// returns false once MAX_DEPENDENCY_PATHS paths are collected
+fn collect_dependency_paths<'g>(
+    package: PackageMetadata<'g>,
+    steps: &mut Vec<DependencyPathStep>,
+    on_path: &mut HashSet<&'g PackageId>,
+    paths: &mut Vec<DependencyPath>,
+) -> bool {
+    for link in package.reverse_direct_links() {
+        if paths.len() >= MAX_DEPENDENCY_PATHS {
+            return false;
+        }
+        let from = link.from();
+        // dev dependencies can form cycles
+        if on_path.contains(from.id()) {
+            continue;
+        }
+
+        steps.push(get_dependency_path_step(&link));
+        let complete = if from.in_workspace() {
+            paths.push(DependencyPath {
+                member: from.name().to_string(),
+                steps: steps.iter().rev().cloned().collect(),
+            });
+            true
+        } else {
+            on_path.insert(from.id());
+            let complete = collect_dependency_paths(from, steps, on_path, paths);
+            on_path.remove(from.id());
+            complete
+        };
+        steps.pop();
+        if !complete {
+            return false;
+        }
+    }
+    true
+}
+
+// Breadth first over the reverse links,
+// keeping the link each package is first reached through
+fn get_shortest_dependency_path(package: &PackageMetadata) -> Option<DependencyPath> {
+    let mut reached_through: HashMap<&PackageId, PackageLink> = HashMap::new();
+    let mut queue: VecDeque<PackageMetadata> = iter::once(*package).collect();
+    while let Some(current) = queue.pop_front() {
+        for link in current.reverse_direct_links() {
+            let from = link.from();
+            if from.id() == package.id() || reached_through.contains_key(from.id()) {
+                continue;
+            }
+            reached_through.insert(from.id(), link);
+            if !from.in_workspace() {
+                queue.push_back(from);
+                continue;
+            }
+
+            let mut steps: Vec<DependencyPathStep> = Vec::new();
+            let mut id = from.id();
+            while let Some(link) = reached_through.get(id) {
+                steps.push(get_dependency_path_step(link));
+                id = link.to().id();
+            }
+            return Some(DependencyPath {
+                member: from.name().to_string(),
+                steps,
+            });
+        }
+    }
+    None
+}
+
+// The step to the dependency of a link,
+// a link with several kinds counts as normal, then build, then dev
+fn get_dependency_path_step(link: &PackageLink) -> DependencyPathStep {
+    let kind = if link.normal().is_present() {
+        DependencyKind::Normal
+    } else if link.build().is_present() {
+        DependencyKind::Build
+    } else {
+        DependencyKind::Dev
+    };
+    DependencyPathStep {
+        name: link.to().name().to_string(),
+        version: link.to().version().clone(),
+        kind,
+    }
+}
+
 pub(crate) fn filter_exclusive_deps<'a>(
     package: &'a PackageMetadata,
     pacakge_dependencies: &[PackageMetadata<'a>],
@@ -90,12 +278,38 @@ pub(crate) fn get_dep_kind_map(
 ) -> Result<HashMap<(String, Version), DependencyKind>> {
     let mut hm: HashMap<(String, Version), DependencyKind> = HashMap::new();
 
+    // The same `crate:version` can come from more than one source,
+    // in which case the kind closest to the build wins
+    let rank = |kind: &DependencyKind| match kind {
+        DependencyKind::Normal => 0,
+        DependencyKind::Build => 1,
+        DependencyKind::Dev => 2,
+    };
+    for (id, kind) in get_package_dep_kind_map(graph)? {
+        let package = graph.metadata(&id)?;
+        let entry = hm
+            .entry((package.name().to_string(), package.version().clone()))
+            .or_insert_with(|| kind.clone());
+        if rank(&kind) < rank(entry) {
+            *entry = kind;
+        }
+    }
+
+    Ok(hm)
+}
+
+/// This function takes a guppy graph
+/// and returns a map indicating the dependency kind
+/// for all packages in the dep graph,
+/// telling apart the same `crate:version` from different sources
+pub(crate) fn get_package_dep_kind_map(
+    graph: &PackageGraph,
+) -> Result<HashMap<PackageId, DependencyKind>> {
+    let mut hm: HashMap<PackageId, DependencyKind> = HashMap::new();
+
     let normal_deps = get_normal_dependencies(graph);
     normal_deps.iter().for_each(|dep| {
-        hm.insert(
-            (dep.name().to_string(), dep.version().clone()),
-            DependencyKind::Normal,
-        );
+        hm.insert(dep.id().clone(), DependencyKind::Normal);
     });
 
     // Get build deps
@@ -108,16 +322,10 @@ pub(crate) fn get_dep_kind_map(
             !link.to().in_workspace() && (link.build().is_present() || link.normal().is_present())
         })
         .packages(guppy::graph::DependencyDirection::Forward)
-        .filter(|pkg| {
-            !pkg.in_workspace()
-                && !hm.contains_key(&(pkg.name().to_string(), pkg.version().clone()))
-        })
+        .filter(|pkg| !pkg.in_workspace() && !hm.contains_key(pkg.id()))
         .collect();
     build_deps.iter().for_each(|dep| {
-        hm.insert(
-            (dep.name().to_string(), dep.version().clone()),
-            DependencyKind::Build,
-        );
+        hm.insert(dep.id().clone(), DependencyKind::Build);
     });
 
     // Get dev deps
@@ -128,36 +336,58 @@ pub(crate) fn get_dep_kind_map(
         .query_workspace()
         .resolve_with_fn(|_, link| !link.to().in_workspace() && link.dev().is_present())
         .packages(guppy::graph::DependencyDirection::Forward)
-        .filter(|pkg| {
-            !pkg.in_workspace()
-                && !hm.contains_key(&(pkg.name().to_string(), pkg.version().clone()))
-        })
+        .filter(|pkg| !pkg.in_workspace() && !hm.contains_key(pkg.id()))
         .collect();
     direct_dev_deps.iter().for_each(|dep| {
-        hm.insert(
-            (dep.name().to_string(), dep.version().clone()),
-            DependencyKind::Dev,
-        );
+        hm.insert(dep.id().clone(), DependencyKind::Dev);
     });
     for dep in &direct_dev_deps {
         let indirect_dev_deps: Vec<PackageMetadata> = get_package_dependencies(graph, dep)?
             .into_iter()
-            .filter(|pkg| {
-                !pkg.in_workspace()
-                    && !hm.contains_key(&(pkg.name().to_string(), pkg.version().clone()))
-            })
+            .filter(|pkg| !pkg.in_workspace() && !hm.contains_key(pkg.id()))
             .collect();
         indirect_dev_deps.iter().for_each(|dep| {
-            hm.insert(
-                (dep.name().to_string(), dep.version().clone()),
-                DependencyKind::Dev,
-            );
+            hm.insert(dep.id().clone(), DependencyKind::Dev);
         });
     }
 
     Ok(hm)
 }
 
+/// This function returns the targets of a package that run at build time:
+/// its build script, its proc-macro library,
+/// and its library if it is a build dependency as per get_dep_kind_map
+pub(crate) fn get_build_time_targets(
+    package: &PackageMetadata,
+    dep_kind: Option<&DependencyKind>,
+) -> Result<BTreeSet<BuildTimeTarget>> {
+    let package_path = package
+        .manifest_path()
+        .parent()
+        .ok_or_else(|| anyhow!("invalid Cargo.toml path"))?;
+
+    let mut targets: BTreeSet<BuildTimeTarget> = BTreeSet::new();
+    for build_target in package.build_targets() {
+        let kind = match (build_target.id(), build_target.kind()) {
+            (BuildTargetId::BuildScript, _) => BuildTimeTargetKind::BuildScript,
+            (BuildTargetId::Library, BuildTargetKind::ProcMacro) => BuildTimeTargetKind::ProcMacro,
+            (BuildTargetId::Library, _) if matches!(dep_kind, Some(DependencyKind::Build)) => {
+                BuildTimeTargetKind::BuildDependency
+            }
+            _ => continue,
+        };
+        targets.insert(BuildTimeTarget {
+            kind,
+            path: build_target
+                .path()
+                .strip_prefix(package_path)?
+                .as_str()
+                .to_string(),
+        });
+    }
+    Ok(targets)
+}
+
 pub(crate) fn get_normal_dependencies(graph: &PackageGraph) -> Vec<PackageMetadata> {
     graph
         .query_workspace()
@@ -223,5 +453,124 @@ mod test {
                 .filter(|k| matches!(k, DependencyKind::Dev))
                 .count()
         );
+
+        // Keyed by package, the kinds agree with the `crate:version` map
+        let package_hm = get_package_dep_kind_map(&graph).unwrap();
+        assert_eq!(
+            package_hm.len(),
+            graph.packages().filter(|pkg| !pkg.in_workspace()).count()
+        );
+        for (id, kind) in &package_hm {
+            let package = graph.metadata(id).unwrap();
+            assert_eq!(
+                hm.get(&(package.name().to_string(), package.version().clone())),
+                Some(kind)
+            );
+        }
+    }
+
+    #[test]
+    fn test_guppy_wrapper_build_time_targets() {
+        let metadata =
+            CargoMetadata::parse_json(include_str!("../resources/test/depkind_metadata.json"))
+                .unwrap();
+        let graph = metadata.build_graph().unwrap();
+        let hm = get_dep_kind_map(&graph).unwrap();
+
+        let package = graph
+            .packages()
+            .find(|p| p.name() == "serde_derive")
+            .unwrap();
+        let targets = get_build_time_targets(&package, None).unwrap();
+        let expected: BTreeSet<BuildTimeTarget> = vec![
+            BuildTimeTarget {
+                kind: BuildTimeTargetKind::BuildScript,
+                path: "build.rs".to_string(),
+            },
+            BuildTimeTarget {
+                kind: BuildTimeTargetKind::ProcMacro,
+                path: "src/lib.rs".to_string(),
+            },
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(targets, expected);
+
+        // The library of a build dependency runs at build time
+        let package = graph
+            .packages()
+            .find(|p| {
+                matches!(
+                    hm.get(&(p.name().to_string(), p.version().clone())),
+                    Some(DependencyKind::Build)
+                ) && p.build_target(&BuildTargetId::Library).is_some()
+            })
+            .unwrap();
+        let dep_kind = hm.get(&(package.name().to_string(), package.version().clone()));
+        let targets = get_build_time_targets(&package, dep_kind).unwrap();
+        assert!(targets
+            .iter()
+            .any(|target| target.kind == BuildTimeTargetKind::BuildDependency));
+    }
+
+    #[test]
+    fn test_guppy_wrapper_dependency_paths() {
+        let metadata =
+            CargoMetadata::parse_json(include_str!("../resources/test/depkind_metadata.json"))
+                .unwrap();
+        let graph = metadata.build_graph().unwrap();
+        let hm = get_dep_kind_map(&graph).unwrap();
+
+        let package = graph
+            .packages()
+            .find(|p| p.name() == "serde_derive")
+            .unwrap();
+        let paths = get_dependency_paths(&package);
+        assert!(!paths.truncated);
+        assert!(!paths.all.is_empty());
+        assert_eq!(paths.shortest.as_ref(), paths.all.first());
+        assert!(paths
+            .all
+            .windows(2)
+            .all(|w| w[0].steps.len() <= w[1].steps.len()));
+        for path in &paths.all {
+            assert_eq!(path.member, "valid_dep");
+            assert_eq!(path.steps.last().unwrap().name, "serde_derive");
+            // each step is a direct dependency of the previous one
+            let mut from = graph.packages().find(|p| p.name() == path.member).unwrap();
+            for step in &path.steps {
+                from = from
+                    .direct_links()
+                    .map(|link| link.to())
+                    .find(|to| to.name() == step.name && to.version() == &step.version)
+                    .unwrap();
+            }
+        }
+
+        // The shortest path to a build dependency goes through a build link
+        let package = graph
+            .packages()
+            .find(|p| {
+                matches!(
+                    hm.get(&(p.name().to_string(), p.version().clone())),
+                    Some(DependencyKind::Build)
+                )
+            })
+            .unwrap();
+        let shortest = get_dependency_paths(&package).shortest.unwrap();
+        assert!(shortest
+            .steps
+            .iter()
+            .any(|step| step.kind == DependencyKind::Build));
+        assert!(shortest.to_string().starts_with("valid_dep -> "));
+        assert_eq!(
+            get_shortest_dependency_path(&package).unwrap().steps.len(),
+            shortest.steps.len()
+        );
+
+        // Workspace members are not pulled in by anything
+        let package = graph.packages().find(|p| p.in_workspace()).unwrap();
+        let paths = get_dependency_paths(&package);
+        assert!(paths.shortest.is_none() && paths.all.is_empty());
     }
 }
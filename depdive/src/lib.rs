@@ -15,6 +15,18 @@
 //! 3. Change in unsafe files
 //! 4. If code hosted on crates.io differs from the git source
 //! 5. Version diff summary, list of changed files.
+//! 6. Binary files and vendored native code added to the crate.
+//! 7. Processes, network, file writes, and env reads in the code run at build time.
+//! 8. Crates newly introduced or dropped in the dependency tree by the update.
+//! 9. Review of newly added dependencies, and a list of removed dependencies.
+//! 10. Review of version downgrades, highlighting any re-introduced advisories.
+//! 11. Review of git dependencies updated from one commit to another,
+//!     and of crates moved to another source, e.g., from crates.io to git, at the same version.
+//! 12. Breaking changes in the public API under a semver compatible version bump.
+//! 13. Changes in the publisher, owners, and repository of the crate.
+//! 14. Duplicate copies of a crate in the graph and the workspace members requiring them.
+//! 15. Release age, yanked status, and newer versions of the prior and updated versions.
+//! 16. Dependency paths from the workspace members to the updated version.
 //! Depdive also offer the update review report in a markdown formatted string
 //! so that when integrated into CI tooling,
 //! you can use the output string as it is
@@ -30,41 +42,189 @@
 //! Note that, code-mterics use (cargo-geiger)[https://github.com/rust-secure-code/cargo-geiger] which cannot be run more than once at a time.
 
 use anyhow::{anyhow, Result};
-use git2::{build::CheckoutBuilder, Oid, Repository};
-use guppy::graph::PackageGraph;
-use guppy::MetadataCommand;
+use git2::{build::CheckoutBuilder, Repository};
+use guppy::graph::{PackageGraph, PackageMetadata};
+use guppy::{MetadataCommand, PackageId};
 use semver::Version;
 use separator::Separatable;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::iter;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
 
 pub mod advisory;
+pub mod build_scan;
+pub mod cache;
 pub mod code;
+pub mod crate_layout;
 pub mod cratesio;
 pub mod diff;
+pub mod file_kind;
 pub mod ghcomment;
 pub mod github;
 mod guppy_wrapper;
+pub mod lockfile;
+pub mod manifest_diff;
+pub mod policy;
+pub mod registry;
+pub mod risk;
+pub mod semver_check;
 pub mod super_toml;
+pub mod unsafe_items;
 pub mod update;
 
-use cratesio::CratesioReport;
+use advisory::AdvisoryLookup;
+use build_scan::{BuildBehaviour, BuildScanFinding, BuildTimeTargetKind};
+use code::CodeReport;
+use cratesio::{CratesioReport, ReleaseInfo};
+use diff::CrateSourceDiffReport;
+use file_kind::{DiffSeverity, NativeArtifacts, SourceFileKind};
 use ghcomment::{Emoji::*, GitHubCommentGenerator, TextStyle::*};
 use github::GitHubReport;
 use guppy_wrapper::{
-    get_all_dependencies, get_dep_kind_map, get_direct_dependencies, DependencyKind,
+    get_all_dependencies, get_dep_kind_map, get_dependency_paths, get_direct_dependencies,
+    get_package_dep_kind_map, DependencyKind, DependencyPaths, MAX_DEPENDENCY_PATHS,
+};
+use lockfile::Lockfile;
+use manifest_diff::ManifestChange;
+use risk::{RiskInput, RiskModel, RiskScore};
+use semver_check::{ApiChange, ApiChangeType, ApiItemKind};
+use unsafe_items::{UnsafeItemChange, UnsafeItemChangeType, UnsafeItemKind};
+use update::{
+    ChurnedDependency, ConflictingCopy, CrateVersionRustSecAdvisory, DepUpdateReviewReport,
+    FileUnsafeCodeChangeStatus, OwnershipReport, ReviewOptions, UpdateReviewReport,
+    VersionConflict, VersionInfo,
 };
-use update::{CrateVersionRustSecAdvisory, UpdateReviewReport, VersionConflict};
 
 /// Usage and Activity metrics for a crate
 #[derive(Serialize, Deserialize)]
 pub struct PackageMetrics {
     pub name: String,
+    pub source: String,
+    pub is_direct: bool,
+    pub kind: DependencyKind,
+    pub cratesio_metrics: Option<CratesioReport>,
+    pub github_metrics: Option<GitHubReport>,
+}
+
+/// All the dependency monitoring metrics of a crate version,
+/// with the error of each metric that could not be collected
+#[derive(Serialize, Deserialize)]
+pub struct DependencyRecord {
+    pub name: String,
+    pub version: Version,
+    pub source: String,
     pub is_direct: bool,
     pub kind: DependencyKind,
     pub cratesio_metrics: Option<CratesioReport>,
+    pub cratesio_error: Option<String>,
     pub github_metrics: Option<GitHubReport>,
+    pub github_error: Option<String>,
+    pub code_report: Option<CodeReport>,
+    pub code_error: Option<String>,
+    pub advisories: Option<Vec<String>>, // ids of the known advisories
+    pub advisories_error: Option<String>,
+}
+
+const DEPENDENCY_RECORD_CSV_HEADER: [&str; 26] = [
+    "name",
+    "version",
+    "source",
+    "is_direct",
+    "kind",
+    "downloads",
+    "dependents",
+    "cratesio_error",
+    "repository",
+    "stars",
+    "forks",
+    "open_issues",
+    "days_since_last_commit",
+    "recent_commits",
+    "recent_committers",
+    "github_error",
+    "total_loc",
+    "rust_loc",
+    "has_build_script",
+    "forbids_unsafe",
+    "used_unsafe",
+    "total_deps",
+    "exclusive_deps",
+    "code_error",
+    "advisories",
+    "advisories_error",
+];
+
+impl DependencyRecord {
+    // A flat row in the order of DEPENDENCY_RECORD_CSV_HEADER,
+    // empty for metrics that are not available
+    fn get_csv_row(&self) -> Vec<String> {
+        fn field<T: ToString>(value: Option<T>) -> String {
+            value.map(|value| value.to_string()).unwrap_or_default()
+        }
+        let cratesio = self.cratesio_metrics.as_ref();
+        let github = self
+            .github_metrics
+            .as_ref()
+            .filter(|github_metrics| github_metrics.is_github_repo);
+        let code = self.code_report.as_ref();
+        let unsafe_report = code.and_then(|code| code.unsafe_report.as_ref());
+
+        vec![
+            self.name.clone(),
+            self.version.to_string(),
+            self.source.clone(),
+            self.is_direct.to_string(),
+            format!("{:?}", self.kind),
+            field(cratesio.map(|c| c.downloads)),
+            field(cratesio.map(|c| c.dependents)),
+            field(self.cratesio_error.as_ref()),
+            field(
+                self.github_metrics
+                    .as_ref()
+                    .and_then(|g| g.repository.as_ref()),
+            ),
+            field(github.map(|g| g.repo_stats.stargazers_count)),
+            field(github.map(|g| g.repo_stats.forks)),
+            field(github.map(|g| g.repo_stats.open_issues)),
+            field(github.map(|g| g.activity_metrics.days_since_last_commit)),
+            field(github.map(|g| g.activity_metrics.recent_activity.commits)),
+            field(github.map(|g| g.activity_metrics.recent_activity.committers)),
+            field(self.github_error.as_ref()),
+            field(
+                code.and_then(|c| c.loc_report.as_ref())
+                    .map(|l| l.total_loc),
+            ),
+            field(code.and_then(|c| c.loc_report.as_ref()).map(|l| l.rust_loc)),
+            field(code.map(|c| c.has_build_script)),
+            field(unsafe_report.map(|u| u.forbids_unsafe)),
+            field(unsafe_report.map(|u| {
+                let used = &u.used_unsafe_count;
+                used.functions + used.expressions + used.impls + used.traits + used.methods
+            })),
+            field(
+                code.and_then(|c| c.dep_report.as_ref())
+                    .map(|d| d.total_deps),
+            ),
+            field(
+                code.and_then(|c| c.exclusive_dep_report.as_ref())
+                    .map(|d| d.total_deps),
+            ),
+            field(self.code_error.as_ref()),
+            field(self.advisories.as_ref().map(|ids| ids.join(" "))),
+            field(self.advisories_error.as_ref()),
+        ]
+    }
+
+    // Quotes a field containing a delimiter, a quote, or a line break
+    fn get_csv_field(field: &str) -> String {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
 }
 
 pub struct DependencyAnalyzer;
@@ -81,6 +241,181 @@ impl DependencyAnalyzer {
 
     /// Given a guppy graph, outputs usage and activity metrics
     fn get_dep_package_metrics_in_json(graph: &PackageGraph, only_direct: bool) -> Result<String> {
+        let output = Self::get_dep_package_metrics(graph, only_direct)?;
+        let json_output = serde_json::to_string(&output)?;
+
+        Ok(json_output)
+    }
+
+    /// Given a cargo project path,
+    /// returns one record per dependency with all the metrics joined
+    pub fn get_dep_records_from_path(
+        path: &Path,
+        only_direct: bool,
+    ) -> Result<Vec<DependencyRecord>> {
+        let graph = MetadataCommand::new().current_dir(path).build_graph()?;
+        Self::get_dep_records(&graph, only_direct)
+    }
+
+    /// Given a guppy graph,
+    /// returns one record per dependency with all the metrics joined
+    pub fn get_dep_records(
+        graph: &PackageGraph,
+        only_direct: bool,
+    ) -> Result<Vec<DependencyRecord>> {
+        let error_string = |e: anyhow::Error| format!("{:#}", e);
+
+        // Keyed by package, as the same `crate:version` can come from more than one source
+        let direct_deps: HashSet<&PackageId> = get_direct_dependencies(graph)
+            .iter()
+            .map(|pkg| pkg.id())
+            .collect();
+        let dep_kind_map = get_package_dep_kind_map(graph)?;
+        // Code metrics are computed for the whole graph at once
+        let code_reports: std::result::Result<
+            HashMap<(String, String, String), CodeReport>,
+            String,
+        > = code::CodeAnalyzer::new()
+            .analyze_code(graph, only_direct)
+            .map(|code_reports| {
+                code_reports
+                    .into_iter()
+                    .map(|report| {
+                        (
+                            (
+                                report.name.clone(),
+                                report.version.clone(),
+                                report.source.clone(),
+                            ),
+                            report,
+                        )
+                    })
+                    .collect()
+            })
+            .map_err(error_string);
+        let advisory_lookup = AdvisoryLookup::new().map_err(error_string);
+
+        let mut records: Vec<DependencyRecord> = Vec::new();
+        for dep in &get_all_dependencies(graph) {
+            let is_direct = direct_deps.contains(dep.id());
+            if only_direct && !is_direct {
+                continue;
+            }
+            let kind = dep_kind_map
+                .get(dep.id())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "fatal error in determining dependency kind for {}:{}",
+                        dep.name(),
+                        dep.version()
+                    )
+                })?
+                .clone();
+
+            let (cratesio_metrics, cratesio_error) = Self::split_result(
+                cratesio::CratesioAnalyzer::new()
+                    .and_then(|analyzer| analyzer.analyze_cratesio(dep))
+                    .map_err(error_string),
+            );
+            let (github_metrics, github_error) = Self::split_result(
+                github::GitHubAnalyzer::new()
+                    .and_then(|analyzer| analyzer.analyze_github(dep))
+                    .map_err(error_string),
+            );
+            let (code_report, code_error) =
+                Self::split_result(code_reports.as_ref().map_err(|e| e.clone()).and_then(
+                    |reports| {
+                        reports
+                            .get(&(
+                                dep.name().to_string(),
+                                dep.version().to_string(),
+                                dep.source().to_string(),
+                            ))
+                            .cloned()
+                            .ok_or_else(|| "no code metrics for the crate".to_string())
+                    },
+                ));
+            let (advisories, advisories_error) =
+                Self::split_result(advisory_lookup.as_ref().map_err(|e| e.clone()).and_then(
+                    |advisory_lookup| {
+                        advisory_lookup
+                            .get_crate_version_advisories(dep.name(), &dep.version().to_string())
+                            .map(|advisories| {
+                                advisories
+                                    .iter()
+                                    .map(|advisory| advisory.id().to_string())
+                                    .collect()
+                            })
+                            .map_err(error_string)
+                    },
+                ));
+
+            records.push(DependencyRecord {
+                name: dep.name().to_string(),
+                version: dep.version().clone(),
+                source: dep.source().to_string(),
+                is_direct,
+                kind,
+                cratesio_metrics,
+                cratesio_error,
+                github_metrics,
+                github_error,
+                code_report,
+                code_error,
+                advisories,
+                advisories_error,
+            });
+        }
+
+        Ok(records)
+    }
+
+    fn split_result<T>(result: std::result::Result<T, String>) -> (Option<T>, Option<String>) {
+        match result {
+            Ok(value) => (Some(value), None),
+            Err(e) => (None, Some(e)),
+        }
+    }
+
+    /// Given dependency records,
+    /// outputs a csv with a header row and the nested metrics flattened
+    pub fn get_dep_records_in_csv(records: &[DependencyRecord]) -> String {
+        let rows = iter::once(
+            DEPENDENCY_RECORD_CSV_HEADER
+                .iter()
+                .map(|column| column.to_string())
+                .collect::<Vec<String>>(),
+        )
+        .chain(records.iter().map(|record| record.get_csv_row()));
+
+        let mut csv = String::new();
+        for row in rows {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|field| DependencyRecord::get_csv_field(field))
+                .collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Given dependency records,
+    /// outputs newline delimited json with one record per line
+    pub fn get_dep_records_in_ndjson(records: &[DependencyRecord]) -> Result<String> {
+        let mut ndjson = String::new();
+        for record in records {
+            ndjson.push_str(&serde_json::to_string(record)?);
+            ndjson.push('\n');
+        }
+        Ok(ndjson)
+    }
+
+    /// Given a guppy graph, returns usage and activity metrics
+    pub fn get_dep_package_metrics(
+        graph: &PackageGraph,
+        only_direct: bool,
+    ) -> Result<Vec<PackageMetrics>> {
         let mut output: Vec<PackageMetrics> = Vec::new();
 
         let all_deps = get_all_dependencies(graph);
@@ -115,6 +450,7 @@ impl DependencyAnalyzer {
 
             output.push(PackageMetrics {
                 name: dep.name().to_string(),
+                source: dep.source().to_string(),
                 is_direct,
                 kind,
                 cratesio_metrics,
@@ -122,9 +458,7 @@ impl DependencyAnalyzer {
             });
         }
 
-        let json_output = serde_json::to_string(&output)?;
-
-        Ok(json_output)
+        Ok(output)
     }
 }
 
@@ -144,6 +478,152 @@ impl DependencyGraphAnalyzer {
         let json_output = serde_json::to_string(&reports)?;
         Ok(json_output)
     }
+
+    /// Given a cargo project path and a crate name,
+    /// returns the paths from the workspace members to each version of the crate
+    pub fn get_dependency_paths_from_path(
+        path: &Path,
+        crate_name: &str,
+    ) -> Result<Vec<DependencyPaths>> {
+        let graph = MetadataCommand::new().current_dir(path).build_graph()?;
+        Self::get_dependency_paths(&graph, crate_name)
+    }
+
+    /// Given a guppy graph and a crate name,
+    /// returns the paths from the workspace members to each version of the crate
+    pub fn get_dependency_paths(
+        graph: &PackageGraph,
+        crate_name: &str,
+    ) -> Result<Vec<DependencyPaths>> {
+        let mut packages: Vec<PackageMetadata> = graph
+            .packages()
+            .filter(|package| package.name() == crate_name)
+            .collect();
+        if packages.is_empty() {
+            return Err(anyhow!("{} is not in the dependency graph", crate_name));
+        }
+        packages.sort_by(|a, b| a.version().cmp(b.version()));
+        Ok(packages.iter().map(get_dependency_paths).collect())
+    }
+
+    /// Given the dependency paths of a crate,
+    /// outputs a markdown formatted report
+    pub fn get_dependency_paths_report(dependency_paths: &[DependencyPaths]) -> String {
+        let mut gh = GitHubCommentGenerator::new();
+        for paths in dependency_paths {
+            gh.add_header(&format!("{} {}", paths.name, paths.version), 3);
+            match &paths.shortest {
+                Some(shortest) => {
+                    gh.append_comment(&format!(
+                        "{} {}",
+                        GitHubCommentGenerator::get_text("Shortest path:", &Bold),
+                        GitHubCommentGenerator::get_text(&shortest.to_string(), &Code)
+                    ));
+                    gh.add_newline(2);
+                    gh.add_text("All paths:", &Bold);
+                    gh.add_newline(1);
+                    gh.append_comment(&UpdateAnalyzer::get_dependency_paths_string(paths));
+                }
+                None => gh.add_text("A workspace member", &Plain),
+            }
+            gh.add_newline(2);
+        }
+        gh.get_comment()
+    }
+}
+
+pub struct DependencyRiskAnalyzer;
+
+impl DependencyRiskAnalyzer {
+    /// Given a cargo project path,
+    /// ranks the dependencies by risk scored from their usage, activity, and code metrics
+    pub fn get_risk_scores_from_path(
+        path: &Path,
+        only_direct: bool,
+        risk_model: &RiskModel,
+    ) -> Result<Vec<RiskScore>> {
+        let graph = MetadataCommand::new().current_dir(path).build_graph()?;
+        Self::get_risk_scores(&graph, only_direct, risk_model)
+    }
+
+    /// Given a guppy graph,
+    /// ranks the dependencies by risk scored from their usage, activity, and code metrics
+    pub fn get_risk_scores(
+        graph: &PackageGraph,
+        only_direct: bool,
+        risk_model: &RiskModel,
+    ) -> Result<Vec<RiskScore>> {
+        let package_metrics = DependencyAnalyzer::get_dep_package_metrics(graph, only_direct)?;
+        let code_reports = code::CodeAnalyzer::new().analyze_code(graph, only_direct)?;
+        let advisory_lookup = AdvisoryLookup::new()?;
+
+        // Usage and activity metrics are per crate, not per version,
+        // while a crate of the same name can come from more than one source
+        let package_metrics: HashMap<(&str, &str), &PackageMetrics> = package_metrics
+            .iter()
+            .map(|metrics| ((metrics.name.as_str(), metrics.source.as_str()), metrics))
+            .collect();
+        let inputs: Vec<RiskInput> = code_reports
+            .iter()
+            .map(|code_report| {
+                let metrics =
+                    package_metrics.get(&(code_report.name.as_str(), code_report.source.as_str()));
+                RiskInput {
+                    name: code_report.name.clone(),
+                    version: code_report.version.clone(),
+                    cratesio_metrics: metrics.and_then(|m| m.cratesio_metrics.as_ref()),
+                    github_metrics: metrics.and_then(|m| m.github_metrics.as_ref()),
+                    code_report: Some(code_report),
+                    advisories: advisory_lookup
+                        .get_crate_version_advisories(&code_report.name, &code_report.version)
+                        .ok()
+                        .map(|advisories| advisories.len() as u64),
+                }
+            })
+            .collect();
+
+        Ok(risk_model.rank(&inputs))
+    }
+
+    /// Given ranked risk scores,
+    /// outputs a markdown formatted report
+    pub fn get_risk_report(risk_scores: &[RiskScore]) -> String {
+        let mut gh = GitHubCommentGenerator::new();
+        gh.add_header("Dependency risk ranking", 2);
+
+        let rows: Vec<Vec<String>> = risk_scores
+            .iter()
+            .enumerate()
+            .map(|(i, risk_score)| {
+                let contributions: Vec<String> = risk_score
+                    .contributions
+                    .iter()
+                    .filter(|c| c.contribution > 0.0)
+                    .map(|c| {
+                        let value = c
+                            .value
+                            .map_or_else(|| "unknown".to_string(), |value| value.to_string());
+                        format!("{} ({}): {:.1}", c.factor, value, c.contribution)
+                    })
+                    .collect();
+                vec![
+                    (i + 1).to_string(),
+                    format!("{} {}", risk_score.name, risk_score.version),
+                    format!("{:.1}", risk_score.score),
+                    contributions.join("<br>"),
+                ]
+            })
+            .collect();
+        let mut table = vec![vec![
+            "Rank".to_string(),
+            "Crate".to_string(),
+            "Score".to_string(),
+            "Contributions".to_string(),
+        ]];
+        table.extend(rows);
+        gh.add_html_table(&table);
+        gh.get_comment()
+    }
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -158,8 +638,27 @@ pub struct AdvisoryHighlight {
 pub enum AdvisoryStatus {
     Fixed,
     Introduced,
-    Unfixed, // or, persistent, advisory is in both the versions
-             // before and after of an update
+    Reintroduced, // advisory brought back by downgrading a dependency
+    Unfixed,      // or, persistent, advisory is in both the versions
+                  // before and after of an update
+}
+
+// How the version of a dependency changes between the reviewed states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DepChangeKind {
+    Update,
+    Downgrade,
+    SourceChange, // same version from another source, e.g., crates.io to git
+}
+
+impl DepChangeKind {
+    fn noun(&self) -> &'static str {
+        match self {
+            DepChangeKind::Update => "update",
+            DepChangeKind::Downgrade => "downgrade",
+            DepChangeKind::SourceChange => "source change",
+        }
+    }
 }
 
 pub struct UpdateAnalyzer;
@@ -175,6 +674,30 @@ impl UpdateAnalyzer {
         update_analyzer.analyze_updates(prior_graph, post_graph)
     }
 
+    /// Given two guppy graph, prior and post,
+    /// Analyzed the updated dependencies for the targets, features,
+    /// and workspace members in the review options
+    pub fn run_update_analyzer_with_options(
+        prior_graph: &PackageGraph,
+        post_graph: &PackageGraph,
+        review_options: &ReviewOptions,
+    ) -> Result<UpdateReviewReport> {
+        let update_analyzer = update::UpdateAnalyzer::new();
+        update_analyzer.analyze_updates_with_review_options(prior_graph, post_graph, review_options)
+    }
+
+    /// Given two Cargo.lock files, prior and post,
+    /// Analyzed the updated dependencies without building package graphs,
+    /// registries are resolved with the Cargo config in the workspace root
+    pub fn run_lockfile_update_analyzer(
+        prior_lockfile: &Lockfile,
+        post_lockfile: &Lockfile,
+        workspace_root: &Path,
+    ) -> Result<UpdateReviewReport> {
+        let update_analyzer = update::UpdateAnalyzer::new();
+        update_analyzer.analyze_lockfile_updates(prior_lockfile, post_lockfile, workspace_root)
+    }
+
     /// Given two guppy graph, prior and post,
     /// Analyzed the updated dependencies
     /// and outputs a markdown formatted report
@@ -183,7 +706,21 @@ impl UpdateAnalyzer {
         post_graph: &PackageGraph,
     ) -> Result<Option<String>> {
         let update_review_report = Self::run_update_analyzer(prior_graph, post_graph)?;
+        Self::get_summary_report_from_review(&update_review_report)
+    }
+
+    /// Given an update review report,
+    /// outputs a markdown formatted report
+    pub fn get_summary_report_from_review(
+        update_review_report: &UpdateReviewReport,
+    ) -> Result<Option<String>> {
         if update_review_report.dep_update_review_reports.is_empty()
+            && update_review_report.dep_downgrade_review_reports.is_empty()
+            && update_review_report
+                .dep_source_change_review_reports
+                .is_empty()
+            && update_review_report.dep_addition_review_reports.is_empty()
+            && update_review_report.dep_removal_reports.is_empty()
             && update_review_report.version_conflicts.is_empty()
         {
             return Ok(None);
@@ -197,199 +734,163 @@ impl UpdateAnalyzer {
         // Write down info on updated dependencies
         gh.add_header("Dependency update review", 2);
         for report in &update_review_report.dep_update_review_reports {
-            // Version update info
+            Self::add_dep_update_review(
+                &mut gh,
+                report,
+                &mut advisory_highlights,
+                DepChangeKind::Update,
+            )?;
+        }
+
+        // Write down info on downgraded dependencies
+        if !update_review_report.dep_downgrade_review_reports.is_empty() {
+            gh.add_header("Dependency downgrade review", 2);
+        }
+        for report in &update_review_report.dep_downgrade_review_reports {
+            Self::add_dep_update_review(
+                &mut gh,
+                report,
+                &mut advisory_highlights,
+                DepChangeKind::Downgrade,
+            )?;
+        }
+
+        // Write down info on dependencies moved to another source
+        if !update_review_report
+            .dep_source_change_review_reports
+            .is_empty()
+        {
+            gh.add_header("Dependency source change review", 2);
+        }
+        for report in &update_review_report.dep_source_change_review_reports {
+            Self::add_dep_update_review(
+                &mut gh,
+                report,
+                &mut advisory_highlights,
+                DepChangeKind::SourceChange,
+            )?;
+        }
+
+        // Write down info on added dependencies
+        if !update_review_report.dep_addition_review_reports.is_empty() {
+            gh.add_header("Dependency addition review", 2);
+        }
+        for report in &update_review_report.dep_addition_review_reports {
             gh.add_header(
-                &format!(
-                    "{} updated: {} --> {}",
-                    report.name, report.prior_version.version, report.updated_version.version
-                ),
+                &format!("{} added: {}", report.name, report.added_version.version),
                 3,
             );
 
             // Advisory
             let mut details: String = String::new();
+            let downloads = match report.added_version.downloads {
+                Some(downloads) => downloads.separated_string(),
+                None => String::from("unavailable"),
+            };
+            let configurations = report.configurations.as_ref().map(|c| c.join(", "));
             let mut checkmark_table: Vec<Vec<&str>> = vec![vec![
                 "No known advisories",
                 GitHubCommentGenerator::get_checkmark(
-                    report.updated_version.known_advisories.is_empty(),
+                    report.added_version.known_advisories.is_empty(),
                 ),
             ]];
 
-            // Keep track of advisory_highlights
-
-            // a closure to add to advisory highlights set
-            let mut add_to_advisory_highlights =
-                |a: &CrateVersionRustSecAdvisory, status: AdvisoryStatus| {
-                    advisory_highlights.insert(AdvisoryHighlight {
-                        status,
-                        crate_name: report.name.clone(),
-                        id: a.id.clone(),
-                        url: a.url.clone().map(|url| url.to_string()),
-                    })
-                };
-
-            // Add to advisory highlights for current crate
-            report
-                .updated_version
-                .known_advisories
-                .iter()
-                .for_each(|a| {
-                    let status = if report.prior_version.known_advisories.contains(a) {
-                        AdvisoryStatus::Unfixed
-                    } else {
-                        AdvisoryStatus::Introduced
-                    };
-                    add_to_advisory_highlights(a, status);
-                });
-            report
-                .prior_version
-                .known_advisories
-                .iter()
-                .filter(|a| !report.updated_version.known_advisories.contains(a))
-                .for_each(|a| {
-                    add_to_advisory_highlights(a, AdvisoryStatus::Fixed);
+            // Any advisory in an added crate is introduced by this change
+            report.added_version.known_advisories.iter().for_each(|a| {
+                advisory_highlights.insert(AdvisoryHighlight {
+                    status: AdvisoryStatus::Introduced,
+                    crate_name: report.name.clone(),
+                    id: a.id.clone(),
+                    url: a.url.clone().map(|url| url.to_string()),
                 });
-
-            // a closure for generating hyperlink of an advisory
-            let get_hyperlink = |a: &CrateVersionRustSecAdvisory| {
-                if let Some(url) = &a.url {
-                    GitHubCommentGenerator::get_hyperlink(&a.id, &url.to_string())
-                } else {
-                    a.id.clone()
-                }
-            };
-
-            if !report.updated_version.known_advisories.is_empty() {
+            });
+            if !report.added_version.known_advisories.is_empty() {
                 let ids: Vec<String> = report
-                    .updated_version
+                    .added_version
                     .known_advisories
                     .iter()
-                    .map(|a| get_hyperlink(a))
+                    .map(Self::get_advisory_hyperlink)
                     .collect();
-                gh.add_header(":bomb: The updated version contains known advisories", 3);
+                gh.add_header(":bomb: The added version contains known advisories", 3);
                 gh.add_bulleted_list(&ids, &Plain);
             }
 
-            let fixed_advisories: Vec<String> = report
-                .prior_version
-                .known_advisories
-                .iter()
-                .filter(|a| !report.updated_version.known_advisories.contains(a))
-                .map(|a| get_hyperlink(a))
-                .collect();
-            if !fixed_advisories.is_empty() {
-                gh.add_header(":tada: This update fixes known advisories", 3);
-                gh.add_bulleted_list(&fixed_advisories, &Plain);
+            // Build script
+            checkmark_table.push(vec![
+                "No build script",
+                GitHubCommentGenerator::get_checkmark(report.build_script_paths.is_empty()),
+            ]);
+            if !report.build_script_paths.is_empty() {
+                let paths: Vec<String> = report.build_script_paths.iter().cloned().collect();
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show build scripts",
+                    &GitHubCommentGenerator::get_bulleted_list(&paths, &Code),
+                ));
             }
 
-            // Diff summary
-            match &report.diff_stats {
+            // Unsafe code
+            match &report.unsafe_stats {
                 None => checkmark_table.push(vec![
-                    "Depdive failed to get the diff between versions from crates.io",
+                    "Depdive failed to scan the crates.io code for unsafe usage",
                     GitHubCommentGenerator::get_emoji(Warning),
                 ]),
                 Some(stats) => {
-                    // Diff overview
-                    details.push_str(&GitHubCommentGenerator::get_collapsible_section(
-                        "Click to show version diff summary",
-                        &GitHubCommentGenerator::get_html_table(&[
-                            vec![
-                                "total files changed".to_string(),
-                                stats.files_changed.len().separated_string(),
-                            ],
-                            vec![
-                                "total rust files changed".to_string(),
-                                stats.rust_files_changed.separated_string(),
-                            ],
-                            vec![
-                                "total loc change".to_string(),
-                                (stats.insertions + stats.deletions).separated_string(),
-                            ],
-                        ]),
-                    ));
-
-                    let changed_file_paths: Vec<String> =
-                        stats.files_changed.iter().cloned().collect();
-                    details.push_str(&GitHubCommentGenerator::get_collapsible_section(
-                        "Click to show changed files",
-                        &GitHubCommentGenerator::get_bulleted_list(&changed_file_paths, &Code),
-                    ));
-
                     checkmark_table.push(vec![
-                        "No change in the build script",
-                        GitHubCommentGenerator::get_checkmark(
-                            stats.modified_build_scripts.is_empty(),
-                        ),
+                        "No unsafe code",
+                        GitHubCommentGenerator::get_checkmark(stats.files_with_unsafe.is_empty()),
                     ]);
-                    if !stats.modified_build_scripts.is_empty() {
-                        let paths: Vec<String> =
-                            stats.modified_build_scripts.iter().cloned().collect();
+                    if !stats.files_with_unsafe.is_empty() {
+                        let paths: Vec<String> = stats.files_with_unsafe.iter().cloned().collect();
                         details.push_str(&GitHubCommentGenerator::get_collapsible_section(
-                            "Click to show modified build scripts",
-                            &GitHubCommentGenerator::get_bulleted_list(&paths, &Code),
-                        ));
-                    }
-
-                    checkmark_table.push(vec![
-                        "No change in any file with unsafe code",
-                        GitHubCommentGenerator::get_checkmark(stats.unsafe_file_changed.is_empty()),
-                    ]);
-                    if !stats.unsafe_file_changed.is_empty() {
-                        let paths: Vec<String> = stats
-                            .unsafe_file_changed
-                            .iter()
-                            .map(|stats| stats.file.clone())
-                            .collect();
-                        details.push_str(&GitHubCommentGenerator::get_collapsible_section(
-                            "Click to show changed files with unsafe code",
+                            "Click to show files with unsafe code",
                             &GitHubCommentGenerator::get_bulleted_list(&paths, &Code),
                         ));
                     }
                 }
             }
 
-            if let Some(crate_source_diff_report) = &report.updated_version.crate_source_diff_report
-            {
-                match crate_source_diff_report.is_different {
-                    None => {
-                        checkmark_table.push(vec![
-                            "Depdive failed to compare the crates.io code with its git source",
-                            GitHubCommentGenerator::get_emoji(Warning),
-                        ]);
-                    }
-                    Some(f) => {
-                        checkmark_table.push(vec![
-                            "The source and crates.io code are the same",
-                            GitHubCommentGenerator::get_checkmark(!f),
-                        ]);
-                        if f {
-                            let changed_files = crate_source_diff_report
-                                .file_diff_stats
-                                .as_ref()
-                                .ok_or_else(|| {
-                                anyhow!("Cannot locate file paths in git source diff report")
-                            })?;
-                            // Only added and modified files are of concern
-                            let paths: Vec<String> = changed_files
-                                .files_added
-                                .union(&changed_files.files_modified)
-                                .cloned()
-                                .collect();
-                            details.push_str(&GitHubCommentGenerator::get_collapsible_section(
-                                "Click to show the files that differ in crates.io from the git source",
-                                &GitHubCommentGenerator::get_bulleted_list(&paths, &Code),
-                            ));
-                        }
-                    }
+            // crates.io stats and source
+            match &report.added_version.crate_source_diff_report {
+                None => checkmark_table.push(vec![
+                    "The crate is not hosted on crates.io",
+                    GitHubCommentGenerator::get_emoji(Warning),
+                ]),
+                Some(crate_source_diff_report) => {
+                    checkmark_table.push(vec!["Downloads on crates.io", &downloads]);
+                    Self::add_crate_source_diff_check(
+                        crate_source_diff_report,
+                        &mut checkmark_table,
+                        &mut details,
+                    )?;
                 }
-            } else {
-                return Err(anyhow!("no crates source diff report for the new version"));
             }
+            if let Some(configurations) = &configurations {
+                checkmark_table.push(vec![
+                    "Target configurations including the crate",
+                    configurations,
+                ]);
+            }
+            Self::add_release_info_check(
+                &[&report.added_version],
+                &mut checkmark_table,
+                &mut details,
+            );
 
             gh.add_html_table(&checkmark_table);
             gh.add_collapsible_section("Cilck to show details", &details);
         }
 
+        // Write down removed dependencies
+        if !update_review_report.dep_removal_reports.is_empty() {
+            let removed: Vec<String> = update_review_report
+                .dep_removal_reports
+                .iter()
+                .map(|report| format!("{} {}", report.name, report.version))
+                .collect();
+            gh.add_header("Removed dependencies", 2);
+            gh.add_bulleted_list(&removed, &Code);
+        }
+
         if !update_review_report.version_conflicts.is_empty() {
             let mut conflicts: Vec<String> = Vec::new();
             for conflict in &update_review_report.version_conflicts {
@@ -402,6 +903,36 @@ impl UpdateAnalyzer {
                         "{} has version {} as a transitive dep but version {} as a direct dep",
                         name, transitive_dep_version, direct_dep_version
                     )),
+                    VersionConflict::SemverIncompatibleVersions { name, copies } => {
+                        conflicts.push(format!(
+                            "{} has semver incompatible versions: {}",
+                            name,
+                            Self::get_conflicting_copies_string(copies, false)
+                        ))
+                    }
+                    VersionConflict::DuplicateIntroduced {
+                        name,
+                        prior_copies,
+                        copies,
+                    } => conflicts.push(format!(
+                        "{} goes from {} to {} copies: {}",
+                        name,
+                        prior_copies,
+                        copies.len(),
+                        Self::get_conflicting_copies_string(copies, false)
+                    )),
+                    VersionConflict::LinksCollision { links, copies } => conflicts.push(format!(
+                        "native library `{}` is linked by multiple crates: {}",
+                        links,
+                        Self::get_conflicting_copies_string(copies, false)
+                    )),
+                    VersionConflict::RegistryAndGitSources { name, copies } => {
+                        conflicts.push(format!(
+                            "{} is pulled from both a registry and git: {}",
+                            name,
+                            Self::get_conflicting_copies_string(copies, true)
+                        ))
+                    }
                 }
             }
 
@@ -411,9 +942,826 @@ impl UpdateAnalyzer {
             );
         }
 
-        // Take advisory highlights to the top
+        // Take downgrades and advisory highlights to the top
+        let downgrade_banner =
+            Self::get_downgrade_banner(&update_review_report.dep_downgrade_review_reports);
         let advisory_banner = Self::get_advisory_banner(&advisory_highlights);
-        Ok(Some(format!("{}\n{}", advisory_banner, gh.get_comment())))
+        Ok(Some(format!(
+            "{}{}\n{}",
+            downgrade_banner,
+            advisory_banner,
+            gh.get_comment()
+        )))
+    }
+
+    // e.g., `target-spec 0.6.1` required by `foo`; `target-spec 0.7.0` required by `bar`
+    fn get_conflicting_copies_string(copies: &[ConflictingCopy], with_source: bool) -> String {
+        let copies: Vec<String> = copies
+            .iter()
+            .map(|copy| {
+                let mut text = format!("`{} {}`", copy.name, copy.version);
+                if with_source {
+                    text.push_str(&format!(" from `{}`", copy.source));
+                }
+                if !copy.workspace_members.is_empty() {
+                    let members: Vec<String> = copy
+                        .workspace_members
+                        .iter()
+                        .map(|member| format!("`{}`", member))
+                        .collect();
+                    text.push_str(&format!(" required by {}", members.join(", ")));
+                }
+                text
+            })
+            .collect();
+        copies.join("; ")
+    }
+
+    /// Adds the review of an updated, a downgraded,
+    /// or a moved to another source, dependency
+    fn add_dep_update_review(
+        gh: &mut GitHubCommentGenerator,
+        report: &DepUpdateReviewReport,
+        advisory_highlights: &mut HashSet<AdvisoryHighlight>,
+        kind: DepChangeKind,
+    ) -> Result<()> {
+        // Version update info
+        gh.add_header(
+            &format!(
+                "{} {}: {} --> {}",
+                report.name,
+                match kind {
+                    DepChangeKind::Update => "updated",
+                    DepChangeKind::Downgrade => "downgraded",
+                    DepChangeKind::SourceChange => "source changed",
+                },
+                Self::get_version_string(&report.prior_version),
+                Self::get_version_string(&report.updated_version)
+            ),
+            3,
+        );
+
+        // Advisory
+        let mut details: String = String::new();
+        let configurations = report.configurations.as_ref().map(|c| c.join(", "));
+        let shortest_path = report
+            .dependency_paths
+            .as_ref()
+            .and_then(|paths| paths.shortest.as_ref())
+            .map(|path| path.to_string());
+        let mut checkmark_table: Vec<Vec<&str>> = vec![vec![
+            "No known advisories",
+            GitHubCommentGenerator::get_checkmark(
+                report.updated_version.known_advisories.is_empty(),
+            ),
+        ]];
+
+        // Keep track of advisory_highlights
+
+        // a closure to add to advisory highlights set
+        let mut add_to_advisory_highlights =
+            |a: &CrateVersionRustSecAdvisory, status: AdvisoryStatus| {
+                advisory_highlights.insert(AdvisoryHighlight {
+                    status,
+                    crate_name: report.name.clone(),
+                    id: a.id.clone(),
+                    url: a.url.clone().map(|url| url.to_string()),
+                })
+            };
+
+        // Add to advisory highlights for current crate
+        report
+            .updated_version
+            .known_advisories
+            .iter()
+            .for_each(|a| {
+                let status = if report.prior_version.known_advisories.contains(a) {
+                    AdvisoryStatus::Unfixed
+                } else if kind == DepChangeKind::Downgrade {
+                    AdvisoryStatus::Reintroduced
+                } else {
+                    AdvisoryStatus::Introduced
+                };
+                add_to_advisory_highlights(a, status);
+            });
+        report
+            .prior_version
+            .known_advisories
+            .iter()
+            .filter(|a| !report.updated_version.known_advisories.contains(a))
+            .for_each(|a| {
+                add_to_advisory_highlights(a, AdvisoryStatus::Fixed);
+            });
+
+        if !report.updated_version.known_advisories.is_empty() {
+            let ids: Vec<String> = report
+                .updated_version
+                .known_advisories
+                .iter()
+                .map(Self::get_advisory_hyperlink)
+                .collect();
+            let version = match kind {
+                DepChangeKind::Update => "updated version",
+                DepChangeKind::Downgrade => "downgraded version",
+                DepChangeKind::SourceChange => "version from the new source",
+            };
+            gh.add_header(
+                &format!(":bomb: The {} contains known advisories", version),
+                3,
+            );
+            gh.add_bulleted_list(&ids, &Plain);
+        }
+
+        let fixed_advisories: Vec<String> = report
+            .prior_version
+            .known_advisories
+            .iter()
+            .filter(|a| !report.updated_version.known_advisories.contains(a))
+            .map(Self::get_advisory_hyperlink)
+            .collect();
+        if !fixed_advisories.is_empty() {
+            gh.add_header(
+                &format!(":tada: This {} fixes known advisories", kind.noun()),
+                3,
+            );
+            gh.add_bulleted_list(&fixed_advisories, &Plain);
+        }
+
+        // Crates pulled in or dropped in the dependency tree by this update
+        if let Some(churn) = &report.transitive_churn {
+            let change = kind.noun();
+            if !churn.added.is_empty() {
+                gh.add_header(
+                    &format!(
+                        ":package: This {} brings in {} new {}",
+                        change,
+                        churn.added.len(),
+                        Self::crate_text(churn.added.len())
+                    ),
+                    3,
+                );
+                let added: Vec<String> = churn
+                    .added
+                    .iter()
+                    .map(Self::get_churned_dependency_string)
+                    .collect();
+                gh.add_bulleted_list(&added, &Plain);
+            }
+            if !churn.removed.is_empty() {
+                let removed: Vec<String> = churn
+                    .removed
+                    .iter()
+                    .map(Self::get_churned_dependency_string)
+                    .collect();
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    &format!(
+                        "Click to show {} {} dropped by this {}",
+                        churn.removed.len(),
+                        Self::crate_text(churn.removed.len()),
+                        change
+                    ),
+                    &GitHubCommentGenerator::get_bulleted_list(&removed, &Plain),
+                ));
+            }
+        }
+
+        // Diff summary
+        match &report.diff_stats {
+            None => checkmark_table.push(vec![
+                "Depdive failed to get the diff between versions from crates.io",
+                GitHubCommentGenerator::get_emoji(Warning),
+            ]),
+            Some(stats) => {
+                // Diff overview
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show version diff summary",
+                    &GitHubCommentGenerator::get_html_table(&[
+                        vec![
+                            "total files changed".to_string(),
+                            stats.files_changed.len().separated_string(),
+                        ],
+                        vec![
+                            "total rust files changed".to_string(),
+                            stats.rust_files_changed.separated_string(),
+                        ],
+                        vec![
+                            "total loc change".to_string(),
+                            (stats.insertions + stats.deletions).separated_string(),
+                        ],
+                    ]),
+                ));
+
+                let changed_file_paths: Vec<String> = stats.files_changed.iter().cloned().collect();
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show changed files",
+                    &GitHubCommentGenerator::get_bulleted_list(&changed_file_paths, &Code),
+                ));
+
+                checkmark_table.push(vec![
+                    "No change in the build script",
+                    GitHubCommentGenerator::get_checkmark(stats.modified_build_scripts.is_empty()),
+                ]);
+                if !stats.modified_build_scripts.is_empty() {
+                    let paths: Vec<String> = stats.modified_build_scripts.iter().cloned().collect();
+                    details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                        "Click to show modified build scripts",
+                        &GitHubCommentGenerator::get_bulleted_list(&paths, &Code),
+                    ));
+                }
+
+                checkmark_table.push(vec![
+                    "No change in any file with unsafe code",
+                    GitHubCommentGenerator::get_checkmark(stats.unsafe_file_changed.is_empty()),
+                ]);
+                if !stats.unsafe_file_changed.is_empty() {
+                    let files: Vec<String> = stats
+                        .unsafe_file_changed
+                        .iter()
+                        .map(|stats| {
+                            let mut file = format!("`{}`", stats.file);
+                            if stats.unsafe_change_status
+                                == FileUnsafeCodeChangeStatus::UnsafeCodeNotAnalyzed
+                            {
+                                file.push_str("<br>could not analyze the changed unsafe items");
+                            }
+                            for change in &stats.unsafe_item_changes {
+                                file.push_str(&format!(
+                                    "<br>{}",
+                                    Self::get_unsafe_item_change_string(change)
+                                ));
+                            }
+                            file
+                        })
+                        .collect();
+                    details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                        "Click to show changed files with unsafe code",
+                        &GitHubCommentGenerator::get_bulleted_list(&files, &Plain),
+                    ));
+                }
+            }
+        }
+
+        match &report.updated_version.crate_source_diff_report {
+            Some(crate_source_diff_report) => Self::add_crate_source_diff_check(
+                crate_source_diff_report,
+                &mut checkmark_table,
+                &mut details,
+            )?,
+            None => checkmark_table.push(vec![
+                "The crate is not hosted on crates.io",
+                GitHubCommentGenerator::get_emoji(Warning),
+            ]),
+        }
+
+        // Binaries and native code added in the version diff
+        // or present only in crates.io
+        let native_artifacts: Vec<(&str, &NativeArtifacts)> = vec![
+            (
+                "added in the version diff",
+                report
+                    .diff_stats
+                    .as_ref()
+                    .map(|stats| &stats.native_artifacts),
+            ),
+            (
+                "in crates.io but not in the git source",
+                report
+                    .updated_version
+                    .crate_source_diff_report
+                    .as_ref()
+                    .and_then(|report| report.native_artifacts.as_ref()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(origin, artifacts)| artifacts.map(|artifacts| (origin, artifacts)))
+        .collect();
+        if !native_artifacts.is_empty() {
+            let items: Vec<String> = native_artifacts
+                .iter()
+                .flat_map(|(origin, artifacts)| {
+                    Self::get_native_artifact_strings(origin, artifacts)
+                })
+                .collect();
+            checkmark_table.push(vec![
+                "No binary file or vendored native code added",
+                GitHubCommentGenerator::get_checkmark(items.is_empty()),
+            ]);
+            if !items.is_empty() {
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show added binary files and vendored native code",
+                    &GitHubCommentGenerator::get_bulleted_list(&items, &Plain),
+                ));
+            }
+        }
+
+        // Behaviour of the code that runs at build time in the new version
+        if let Some(build_scan_report) = &report.build_scan_report {
+            checkmark_table.push(vec![
+                "No process, network, file write outside OUT_DIR, env read, or include_bytes! at build time",
+                GitHubCommentGenerator::get_checkmark(build_scan_report.findings.is_empty()),
+            ]);
+            if !build_scan_report.findings.is_empty() {
+                let findings: Vec<String> = build_scan_report
+                    .findings
+                    .iter()
+                    .map(Self::get_build_scan_finding_string)
+                    .collect();
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show the build time behaviour of the new version",
+                    &GitHubCommentGenerator::get_bulleted_list(&findings, &Plain),
+                ));
+            }
+        }
+
+        // Breaking changes in the public API under a semver compatible version
+        if let Some(semver_check_report) = &report.semver_check_report {
+            let violations = semver_check_report.get_violations();
+            checkmark_table.push(vec![
+                "No breaking change in the public API for a semver compatible version",
+                GitHubCommentGenerator::get_checkmark(violations.is_empty()),
+            ]);
+            if !violations.is_empty() {
+                let changes: Vec<String> = violations
+                    .iter()
+                    .map(|change| Self::get_api_change_string(change))
+                    .collect();
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show breaking changes in the public API",
+                    &GitHubCommentGenerator::get_bulleted_list(&changes, &Plain),
+                ));
+            }
+        }
+
+        // Targets with the crate in their dependency graph
+        if let Some(configurations) = &configurations {
+            checkmark_table.push(vec![
+                "Target configurations including the crate",
+                configurations,
+            ]);
+        }
+
+        // Which workspace members pull in the crate
+        if let Some(shortest_path) = &shortest_path {
+            checkmark_table.push(vec!["Shortest path from the workspace", shortest_path]);
+        }
+        if let Some(dependency_paths) = &report.dependency_paths {
+            if dependency_paths.all.len() > 1 {
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show all dependency paths",
+                    &Self::get_dependency_paths_string(dependency_paths),
+                ));
+            }
+        }
+
+        // Release age, yanked status, and newer versions
+        Self::add_release_info_check(
+            &[&report.prior_version, &report.updated_version],
+            &mut checkmark_table,
+            &mut details,
+        );
+
+        // Publishers, owners, and repository of the crate
+        match &report.ownership_report {
+            Some(ownership_report) => {
+                checkmark_table.push(vec![
+                    "No change in the publisher, owners, or repository",
+                    GitHubCommentGenerator::get_checkmark(!ownership_report.has_changes()),
+                ]);
+                details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                    "Click to show publishers and owners",
+                    &GitHubCommentGenerator::get_bulleted_list(
+                        &Self::get_ownership_strings(report, ownership_report),
+                        &Plain,
+                    ),
+                ));
+            }
+            // Versions not from a registry have no ownership info to begin with
+            None if report.updated_version.crate_source_diff_report.is_some() => checkmark_table
+                .push(vec![
+                    "Publishers and owners are unavailable from the registry",
+                    GitHubCommentGenerator::get_emoji(Warning),
+                ]),
+            None => {}
+        }
+
+        gh.add_html_table(&checkmark_table);
+        gh.add_collapsible_section("Cilck to show details", &details);
+        Ok(())
+    }
+
+    fn get_dependency_paths_string(dependency_paths: &DependencyPaths) -> String {
+        let mut paths: Vec<String> = dependency_paths
+            .all
+            .iter()
+            .map(|path| path.to_string())
+            .collect();
+        if dependency_paths.truncated {
+            paths.push(format!(
+                "more paths are left out after the first {}",
+                MAX_DEPENDENCY_PATHS
+            ));
+        }
+        GitHubCommentGenerator::get_bulleted_list(&paths, &Code)
+    }
+
+    /// Adds checks on the yanked status and newer versions of the last given version,
+    /// and the release info of all given versions in details
+    fn add_release_info_check(
+        versions: &[&VersionInfo],
+        checkmark_table: &mut Vec<Vec<&str>>,
+        details: &mut String,
+    ) {
+        let version = match versions.last() {
+            Some(version) => version,
+            None => return,
+        };
+        let release_info = match (&version.release_info, &version.crate_source_diff_report) {
+            (Some(release_info), _) => release_info,
+            (None, Some(_)) => {
+                checkmark_table.push(vec![
+                    "Release info is unavailable from the registry",
+                    GitHubCommentGenerator::get_emoji(Warning),
+                ]);
+                return;
+            }
+            // Versions not from a registry have no release info
+            (None, None) => return,
+        };
+        checkmark_table.push(vec![
+            "The version is not yanked",
+            GitHubCommentGenerator::get_checkmark(!release_info.yanked),
+        ]);
+        checkmark_table.push(vec![
+            "No newer version is available",
+            if release_info.newer_versions == 0 {
+                GitHubCommentGenerator::get_checkmark(true)
+            } else {
+                GitHubCommentGenerator::get_emoji(Warning)
+            },
+        ]);
+
+        let items: Vec<String> = versions
+            .iter()
+            .filter_map(|v| {
+                v.release_info
+                    .as_ref()
+                    .map(|release_info| Self::get_release_info_string(&v.version, release_info))
+            })
+            .collect();
+        details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+            "Click to show release info",
+            &GitHubCommentGenerator::get_bulleted_list(&items, &Plain),
+        ));
+    }
+
+    // e.g., `1.7.1` released on 2021-06-30, 3 day(s) ago, yanked, 2 newer version(s) available
+    fn get_release_info_string(version: &Version, release_info: &ReleaseInfo) -> String {
+        let mut s = format!(
+            "`{}` released on {}, {} day(s) ago",
+            version,
+            release_info.created_at.format("%Y-%m-%d"),
+            release_info.age_days
+        );
+        if release_info.yanked {
+            s.push_str(", :warning: yanked");
+        }
+        s.push_str(&format!(
+            ", {} newer version(s) available",
+            release_info.newer_versions
+        ));
+        s
+    }
+
+    fn get_ownership_strings(
+        report: &DepUpdateReviewReport,
+        ownership_report: &OwnershipReport,
+    ) -> Vec<String> {
+        let get_login = |login: &Option<String>| match login {
+            Some(login) => format!("`{}`", login),
+            None => "unknown".to_string(),
+        };
+        let get_logins = |logins: &[String]| {
+            logins
+                .iter()
+                .map(|login| format!("`{}`", login))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        let mut items: Vec<String> = Vec::new();
+        if ownership_report.is_publisher_changed() {
+            items.push(format!(
+                ":warning: publisher changed from {} in {} to {} in {}",
+                get_login(&ownership_report.prior_publisher),
+                report.prior_version.version,
+                get_login(&ownership_report.updated_publisher),
+                report.updated_version.version
+            ));
+        } else {
+            items.push(format!(
+                "{} published by {}",
+                report.updated_version.version,
+                get_login(&ownership_report.updated_publisher)
+            ));
+        }
+        if !ownership_report.new_owners.is_empty() {
+            items.push(format!(
+                ":warning: owners publishing for the first time since {}: {}",
+                report.prior_version.version,
+                get_logins(&ownership_report.new_owners)
+            ));
+        }
+        if ownership_report.is_repository_changed() {
+            items.push(format!(
+                ":warning: repository changed from {} to {}",
+                get_login(&ownership_report.prior_repository),
+                get_login(&ownership_report.updated_repository)
+            ));
+        }
+        items.push(format!(
+            "current owners: {}",
+            get_logins(&ownership_report.owners)
+        ));
+        items
+    }
+
+    // e.g., removed fn `de::from_str`
+    fn get_api_change_string(change: &ApiChange) -> String {
+        let change_type = match change.change_type {
+            ApiChangeType::Added => "added",
+            ApiChangeType::Removed => "removed",
+            ApiChangeType::Modified => "changed",
+        };
+        let kind = match change.kind {
+            ApiItemKind::Module => "module",
+            ApiItemKind::Function => "fn",
+            ApiItemKind::Struct => "struct",
+            ApiItemKind::Field => "field",
+            ApiItemKind::Enum => "enum",
+            ApiItemKind::Variant => "variant",
+            ApiItemKind::Trait => "trait",
+            ApiItemKind::TraitItem => "trait item",
+            ApiItemKind::TraitImpl => "trait impl",
+            ApiItemKind::Const => "const",
+            ApiItemKind::Static => "static",
+            ApiItemKind::TypeAlias => "type alias",
+            ApiItemKind::Reexport => "re-export",
+            ApiItemKind::Macro => "macro",
+        };
+        format!("{} {} `{}`", change_type, kind, change.path)
+    }
+
+    // e.g., `bar 0.2.0` via `foo 1.3.0`, `baz 0.1.0`
+    fn get_churned_dependency_string(dep: &ChurnedDependency) -> String {
+        let dependents: Vec<String> = dep
+            .dependents
+            .iter()
+            .map(|dependent| format!("`{}`", dependent))
+            .collect();
+        format!(
+            "`{} {}` via {}",
+            dep.name,
+            dep.version,
+            dependents.join(", ")
+        )
+    }
+
+    fn crate_text(n: usize) -> &'static str {
+        if n == 1 {
+            "crate"
+        } else {
+            "crates"
+        }
+    }
+
+    // e.g., `build.rs:12`: process command `Command::new` in the build script
+    fn get_build_scan_finding_string(finding: &BuildScanFinding) -> String {
+        let behaviour = match finding.behaviour {
+            BuildBehaviour::ProcessCommand => "process command",
+            BuildBehaviour::Network => "network access",
+            BuildBehaviour::FsWriteOutsideOutDir => "file write outside OUT_DIR",
+            BuildBehaviour::EnvRead => "env var read",
+            BuildBehaviour::IncludeBytes => "included bytes",
+        };
+        let target = match finding.target {
+            BuildTimeTargetKind::BuildScript => "the build script",
+            BuildTimeTargetKind::ProcMacro => "the proc-macro",
+            BuildTimeTargetKind::BuildDependency => "the build dependency",
+        };
+        format!(
+            "`{}:{}`: {} `{}` in {}",
+            finding.file, finding.line, behaviour, finding.detail, target
+        )
+    }
+
+    // e.g., `lib/libfoo.so`: Elf binary, 1,024 bytes, added in the version diff
+    fn get_native_artifact_strings(
+        origin: &str,
+        native_artifacts: &NativeArtifacts,
+    ) -> Vec<String> {
+        let binary_files = native_artifacts.binary_files.iter().map(|file| {
+            format!(
+                "`{}`: {:?} binary, {} bytes, {}",
+                file.path,
+                file.format,
+                file.size.separated_string(),
+                origin
+            )
+        });
+        let vendored_native_dirs = native_artifacts.vendored_native_dirs.iter().map(|dir| {
+            format!(
+                "`{}`: {} native source files, {} bytes, {}",
+                dir.path,
+                dir.files.separated_string(),
+                dir.size.separated_string(),
+                origin
+            )
+        });
+        binary_files.chain(vendored_native_dirs).collect()
+    }
+
+    // Versions sourced from git are suffixed with the short commit hash
+    fn get_version_string(version: &VersionInfo) -> String {
+        match &version.git_source {
+            Some(git_source) => format!(
+                "{} (git {})",
+                version.version,
+                &git_source.commit[..git_source.commit.len().min(7)]
+            ),
+            None => version.version.to_string(),
+        }
+    }
+
+    // e.g., modified unsafe fn `deref` (lines 5-7)
+    fn get_unsafe_item_change_string(change: &UnsafeItemChange) -> String {
+        let change_type = match change.change_type {
+            UnsafeItemChangeType::Added => "added",
+            UnsafeItemChangeType::Removed => "removed",
+            UnsafeItemChangeType::Modified => "modified",
+        };
+        let kind = match change.kind {
+            UnsafeItemKind::Block => "unsafe block",
+            UnsafeItemKind::Function => "unsafe fn",
+            UnsafeItemKind::Method => "unsafe method",
+            UnsafeItemKind::Impl => "unsafe impl",
+            UnsafeItemKind::Trait => "unsafe trait",
+        };
+        let mut s = format!("{} {}", change_type, kind);
+        if let Some(name) = &change.name {
+            match change.kind {
+                UnsafeItemKind::Block => s.push_str(&format!(" in `{}`", name)),
+                _ => s.push_str(&format!(" `{}`", name)),
+            }
+        }
+        if let Some((start, end)) = change.new_lines.or(change.old_lines) {
+            s.push_str(&format!(" (lines {}-{})", start, end));
+        }
+        s
+    }
+
+    fn get_advisory_hyperlink(a: &CrateVersionRustSecAdvisory) -> String {
+        if let Some(url) = &a.url {
+            GitHubCommentGenerator::get_hyperlink(&a.id, &url.to_string())
+        } else {
+            a.id.clone()
+        }
+    }
+
+    /// Adds a check, and file paths in details,
+    /// on whether the crates.io code differs from the git source
+    fn add_crate_source_diff_check(
+        crate_source_diff_report: &CrateSourceDiffReport,
+        checkmark_table: &mut Vec<Vec<&str>>,
+        details: &mut String,
+    ) -> Result<()> {
+        match crate_source_diff_report.is_different {
+            None => {
+                checkmark_table.push(vec![
+                    "Depdive failed to compare the crates.io code with its git source",
+                    GitHubCommentGenerator::get_emoji(Warning),
+                ]);
+            }
+            Some(f) => {
+                // Differences only in docs, tests, and the like are not flagged
+                checkmark_table.push(vec![
+                    "No code-relevant difference between the source and crates.io code",
+                    match crate_source_diff_report.get_severity() {
+                        Some(DiffSeverity::High) => GitHubCommentGenerator::get_emoji(RedCross),
+                        Some(DiffSeverity::Medium) => GitHubCommentGenerator::get_emoji(Warning),
+                        _ => GitHubCommentGenerator::get_emoji(WhiteCheckMark),
+                    },
+                ]);
+                if f {
+                    let changed_files = crate_source_diff_report
+                        .file_diff_stats
+                        .as_ref()
+                        .ok_or_else(|| {
+                            anyhow!("Cannot locate file paths in git source diff report")
+                        })?;
+                    // Only added and modified files are of concern
+                    if !changed_files.classified_files.is_empty() {
+                        let files: Vec<String> = changed_files
+                            .classified_files
+                            .iter()
+                            .map(|file| {
+                                format!(
+                                    "`{}`: {} ({} severity)",
+                                    file.path,
+                                    Self::get_source_file_kind_string(file.kind),
+                                    Self::get_diff_severity_string(file.severity)
+                                )
+                            })
+                            .collect();
+                        details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                            "Click to show the files that differ in crates.io from the git source",
+                            &GitHubCommentGenerator::get_bulleted_list(&files, &Plain),
+                        ));
+                    }
+
+                    if let Some(manifest_diff) = &crate_source_diff_report.manifest_diff {
+                        if !manifest_diff.is_empty() {
+                            let changes: Vec<String> = manifest_diff
+                                .changes
+                                .iter()
+                                .map(Self::get_manifest_change_string)
+                                .collect();
+                            details.push_str(&GitHubCommentGenerator::get_collapsible_section(
+                                "Click to show the manifest entries that differ in crates.io from the git source",
+                                &GitHubCommentGenerator::get_bulleted_list(&changes, &Plain),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn get_source_file_kind_string(kind: SourceFileKind) -> &'static str {
+        match kind {
+            SourceFileKind::BuildScript => "build script",
+            SourceFileKind::ProcMacroEntry => "proc-macro entry",
+            SourceFileKind::RustSource => "rust source",
+            SourceFileKind::BinaryOrVendored => "binary or vendored",
+            SourceFileKind::Generated => "generated",
+            SourceFileKind::Docs => "docs",
+            SourceFileKind::Test => "test",
+            SourceFileKind::Other => "other",
+        }
+    }
+
+    fn get_diff_severity_string(severity: DiffSeverity) -> &'static str {
+        match severity {
+            DiffSeverity::High => "high",
+            DiffSeverity::Medium => "medium",
+            DiffSeverity::Low => "low",
+        }
+    }
+
+    // e.g., `build-dependencies.evil`: none in git, `{ version = "0.1" }` in crates.io
+    fn get_manifest_change_string(change: &ManifestChange) -> String {
+        let get_value = |value: &Option<String>| match value {
+            Some(value) => format!("`{}`", value),
+            None => "none".to_string(),
+        };
+        format!(
+            "`{}.{}`: {} in git, {} in crates.io",
+            change.section,
+            change.key,
+            get_value(&change.git),
+            get_value(&change.cratesio)
+        )
+    }
+
+    fn get_downgrade_banner(downgrade_reports: &[DepUpdateReviewReport]) -> String {
+        if downgrade_reports.is_empty() {
+            return String::new();
+        }
+
+        let names: Vec<String> = downgrade_reports
+            .iter()
+            .map(|report| {
+                format!(
+                    "{} {} --> {}",
+                    report.name, report.prior_version.version, report.updated_version.version
+                )
+            })
+            .collect();
+        let mut downgrade_banner = GitHubCommentGenerator::get_header_text(
+            &format!(
+                ":warning: This change downgrades {} {}\n",
+                downgrade_reports.len(),
+                if downgrade_reports.len() == 1 {
+                    "dependency"
+                } else {
+                    "dependencies"
+                }
+            ),
+            1,
+        );
+        downgrade_banner.push_str(&GitHubCommentGenerator::get_bulleted_list(&names, &Code));
+        downgrade_banner.push('\n');
+        downgrade_banner
     }
 
     fn get_advisory_banner(advisory_highlights: &HashSet<AdvisoryHighlight>) -> String {
@@ -434,6 +1782,21 @@ impl UpdateAnalyzer {
             ));
         }
 
+        let reintroduced = advisory_highlights
+            .iter()
+            .filter(|a| a.status == AdvisoryStatus::Reintroduced)
+            .count();
+        if reintroduced > 0 {
+            advisory_banner.push_str(&GitHubCommentGenerator::get_header_text(
+                &format!(
+                    ":bomb: This downgrade re-introduces {} known {}\n",
+                    reintroduced,
+                    advisory_text(reintroduced)
+                ),
+                1,
+            ));
+        }
+
         let unfixed = advisory_highlights
             .iter()
             .filter(|a| a.status == AdvisoryStatus::Unfixed)
@@ -475,6 +1838,18 @@ impl UpdateAnalyzer {
         advisory_banner
     }
 
+    /// Given two guppy graph, prior and post,
+    /// Analyzed the updated dependencies
+    /// and outputs the full report in json
+    pub fn get_json_report(
+        prior_graph: &PackageGraph,
+        post_graph: &PackageGraph,
+    ) -> Result<String> {
+        let update_review_report = Self::run_update_analyzer(prior_graph, post_graph)?;
+        let json_output = serde_json::to_string(&update_review_report)?;
+        Ok(json_output)
+    }
+
     /// Get update review report in markdown format
     /// for a given repo and prior and post commit
     pub fn run_update_analyzer_from_repo_commits(
@@ -482,36 +1857,161 @@ impl UpdateAnalyzer {
         commit_a: &str,
         commit_b: &str,
     ) -> Result<Option<String>> {
-        let repo = Repository::open(&path)?;
-        let starter_commit = repo.head()?.peel_to_commit()?;
+        // Keep the exported trees during the review
+        // for the registries in their Cargo config
+        let (prior_graph, post_graph, _trees) =
+            Self::get_package_graphs_from_repo_commits(path, commit_a, commit_b)?;
+        UpdateAnalyzer::get_summary_report(&prior_graph, &post_graph)
+    }
 
-        let mut checkout_builder = CheckoutBuilder::new();
-        checkout_builder.force();
+    /// Get update review report in json format
+    /// for a given repo and prior and post commit
+    pub fn run_update_analyzer_from_repo_commits_in_json(
+        path: &Path,
+        commit_a: &str,
+        commit_b: &str,
+    ) -> Result<String> {
+        let (prior_graph, post_graph, _trees) =
+            Self::get_package_graphs_from_repo_commits(path, commit_a, commit_b)?;
+        UpdateAnalyzer::get_json_report(&prior_graph, &post_graph)
+    }
 
-        // Get prior_graph
-        repo.checkout_tree(
-            &repo.find_object(Oid::from_str(commit_a)?, None)?,
-            Some(&mut checkout_builder),
-        )?;
-        let prior_graph = MetadataCommand::new().current_dir(path).build_graph()?;
+    /// Get update review report in markdown format
+    /// for two paths, presumably checked out at two commits for a given repo
+    pub fn run_update_analyzer_from_paths(path_a: &Path, path_b: &Path) -> Result<Option<String>> {
+        let (prior_graph, post_graph) = Self::get_package_graphs_from_paths(path_a, path_b)?;
+        UpdateAnalyzer::get_summary_report(&prior_graph, &post_graph)
+    }
 
-        // Get post_graph
-        repo.checkout_tree(
-            &repo.find_object(Oid::from_str(commit_b)?, None)?,
-            Some(&mut checkout_builder),
+    /// Get update review report in json format
+    /// for two paths, presumably checked out at two commits for a given repo
+    pub fn run_update_analyzer_from_paths_in_json(path_a: &Path, path_b: &Path) -> Result<String> {
+        let (prior_graph, post_graph) = Self::get_package_graphs_from_paths(path_a, path_b)?;
+        UpdateAnalyzer::get_json_report(&prior_graph, &post_graph)
+    }
+
+    /// Get update review report in markdown format
+    /// for two Cargo.lock files of a workspace, without running cargo metadata
+    pub fn run_update_analyzer_from_lockfiles(
+        lockfile_a: &Path,
+        lockfile_b: &Path,
+    ) -> Result<Option<String>> {
+        let update_review_report = Self::run_lockfile_update_analyzer(
+            &Lockfile::from_file(lockfile_a)?,
+            &Lockfile::from_file(lockfile_b)?,
+            &Self::get_lockfile_workspace_root(lockfile_b)?,
         )?;
-        let post_graph = MetadataCommand::new().current_dir(path).build_graph()?;
+        Self::get_summary_report_from_review(&update_review_report)
+    }
 
-        repo.checkout_tree(starter_commit.as_object(), Some(&mut checkout_builder))?;
-        UpdateAnalyzer::get_summary_report(&prior_graph, &post_graph)
+    /// Get update review report in json format
+    /// for two Cargo.lock files of a workspace, without running cargo metadata
+    pub fn run_update_analyzer_from_lockfiles_in_json(
+        lockfile_a: &Path,
+        lockfile_b: &Path,
+    ) -> Result<String> {
+        let update_review_report = Self::run_lockfile_update_analyzer(
+            &Lockfile::from_file(lockfile_a)?,
+            &Lockfile::from_file(lockfile_b)?,
+            &Self::get_lockfile_workspace_root(lockfile_b)?,
+        )?;
+        Ok(serde_json::to_string(&update_review_report)?)
     }
 
     /// Get update review report in markdown format
+    /// for a unified diff of a Cargo.lock, e.g., `git diff -- Cargo.lock`,
+    /// in the given workspace root
+    pub fn run_update_analyzer_from_lockfile_diff(
+        diff: &str,
+        workspace_root: &Path,
+    ) -> Result<Option<String>> {
+        let (prior_lockfile, post_lockfile) = Lockfile::from_unified_diff(diff)?;
+        let update_review_report =
+            Self::run_lockfile_update_analyzer(&prior_lockfile, &post_lockfile, workspace_root)?;
+        Self::get_summary_report_from_review(&update_review_report)
+    }
+
+    /// Get update review report in json format
+    /// for a unified diff of a Cargo.lock, e.g., `git diff -- Cargo.lock`,
+    /// in the given workspace root
+    pub fn run_update_analyzer_from_lockfile_diff_in_json(
+        diff: &str,
+        workspace_root: &Path,
+    ) -> Result<String> {
+        let (prior_lockfile, post_lockfile) = Lockfile::from_unified_diff(diff)?;
+        let update_review_report =
+            Self::run_lockfile_update_analyzer(&prior_lockfile, &post_lockfile, workspace_root)?;
+        Ok(serde_json::to_string(&update_review_report)?)
+    }
+
+    /// The directory of a Cargo.lock file is its workspace root
+    pub fn get_lockfile_workspace_root(lockfile: &Path) -> Result<PathBuf> {
+        let lockfile = lockfile.canonicalize()?;
+        lockfile
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .ok_or_else(|| anyhow!("invalid Cargo.lock path {}", lockfile.display()))
+    }
+
+    /// Get the prior and post guppy graph
     /// for two paths, presumably checked out at two commits for a given repo
-    pub fn run_update_analyzer_from_paths(path_a: &Path, path_b: &Path) -> Result<Option<String>> {
+    pub fn get_package_graphs_from_paths(
+        path_a: &Path,
+        path_b: &Path,
+    ) -> Result<(PackageGraph, PackageGraph)> {
         let prior_graph = MetadataCommand::new().current_dir(path_a).build_graph()?;
         let post_graph = MetadataCommand::new().current_dir(path_b).build_graph()?;
-        UpdateAnalyzer::get_summary_report(&prior_graph, &post_graph)
+        Ok((prior_graph, post_graph))
+    }
+
+    /// Get the prior and post guppy graph
+    /// for a given repo and prior and post revision,
+    /// e.g., a commit sha, branch, tag, or HEAD~3.
+    /// The working tree of the repo is left untouched.
+    /// The graphs are rooted in the returned exported trees,
+    /// keep those alive while the graphs are in use,
+    /// e.g., for the registries in their Cargo config
+    pub fn get_package_graphs_from_repo_commits(
+        path: &Path,
+        commit_a: &str,
+        commit_b: &str,
+    ) -> Result<(PackageGraph, PackageGraph, (TempDir, TempDir))> {
+        let (tree_a, tree_b) = Self::export_commit_trees(path, commit_a, commit_b)?;
+        let (prior_graph, post_graph) =
+            Self::get_package_graphs_from_paths(tree_a.path(), tree_b.path())?;
+        Ok((prior_graph, post_graph, (tree_a, tree_b)))
+    }
+
+    /// Exports the trees of a prior and post revision of a repo
+    /// to temporary directories, removed on drop
+    pub fn export_commit_trees(
+        path: &Path,
+        commit_a: &str,
+        commit_b: &str,
+    ) -> Result<(TempDir, TempDir)> {
+        let repo = Repository::open(&path)?;
+        Ok((
+            Self::export_commit_tree(&repo, commit_a)?,
+            Self::export_commit_tree(&repo, commit_b)?,
+        ))
+    }
+
+    fn export_commit_tree(repo: &Repository, revspec: &str) -> Result<TempDir> {
+        let tree = repo
+            .revparse_single(revspec)
+            .and_then(|object| object.peel_to_tree())
+            .map_err(|e| anyhow!("cannot resolve {} to a commit: {}", revspec, e))?;
+        let dir = tempfile::tempdir()?;
+
+        // Write the files to the directory
+        // without updating the index or the working tree of the repo
+        let mut checkout_builder = CheckoutBuilder::new();
+        checkout_builder
+            .target_dir(dir.path())
+            .update_index(false)
+            .force();
+        repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))?;
+        Ok(dir)
     }
 }
 
@@ -519,6 +2019,7 @@ impl UpdateAnalyzer {
 mod test {
     use super::*;
     use crate::diff::DiffAnalyzer;
+    use git2::{Oid, Signature};
     use once_cell::sync::Lazy;
     use serial_test::serial;
     use std::sync::Once;
@@ -619,6 +2120,102 @@ mod test {
         );
     }
 
+    #[test]
+    #[serial]
+    fn test_lib_update_review_report_in_json() {
+        setup_git_repos();
+
+        let name = "diem";
+        let repository = "https://github.com/diem/diem";
+        let repo = DIFF_ANALYZER.get_git_repo(name, repository).unwrap();
+        let path = repo
+            .path()
+            .parent()
+            .ok_or_else(|| anyhow!("repository path not found for {}", repository))
+            .unwrap();
+        let json = UpdateAnalyzer::run_update_analyzer_from_repo_commits_in_json(
+            path,
+            "20da44ad0918e6f260e9f150a60f28ec3b8665b2",
+            "2b2e529d96b6fbd9b5d111ecdd21acb61e95a28f",
+        )
+        .unwrap();
+        let report: UpdateReviewReport = serde_json::from_str(&json).unwrap();
+        assert!(!report.dep_update_review_reports.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_lib_package_graphs_from_repo_revisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+
+        let commit_version = |version: &str| {
+            std::fs::write(
+                &manifest_path,
+                format!("[package]\nname = \"app\"\nversion = \"{}\"\n", version),
+            )
+            .unwrap();
+            let mut index = repo.index().unwrap();
+            index
+                .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+                .unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = Signature::now("user", "email@domain.com").unwrap();
+            let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                version,
+                &tree,
+                &parents,
+            )
+            .unwrap()
+        };
+        commit_version("0.1.0");
+        let commit = commit_version("0.2.0");
+        repo.tag_lightweight("v0.2.0", &repo.find_object(commit, None).unwrap(), false)
+            .unwrap();
+
+        // An uncommitted change in the working tree
+        let dirty_manifest = "[package]\nname = \"app\"\nversion = \"0.3.0\"\n";
+        std::fs::write(&manifest_path, dirty_manifest).unwrap();
+
+        let (prior_graph, post_graph, _trees) =
+            UpdateAnalyzer::get_package_graphs_from_repo_commits(dir.path(), "HEAD~1", "v0.2.0")
+                .unwrap();
+        // The graphs are rooted in the exported trees kept alive with them
+        assert!(prior_graph.workspace().root().exists());
+        assert!(post_graph.workspace().root().exists());
+        let get_version = |graph: &PackageGraph| {
+            graph
+                .workspace()
+                .member_by_path("")
+                .unwrap()
+                .version()
+                .to_string()
+        };
+        assert_eq!(get_version(&prior_graph), "0.1.0");
+        assert_eq!(get_version(&post_graph), "0.2.0");
+
+        // The working tree and the index are untouched
+        assert_eq!(
+            std::fs::read_to_string(&manifest_path).unwrap(),
+            dirty_manifest
+        );
+        assert!(repo
+            .status_file(Path::new("Cargo.toml"))
+            .unwrap()
+            .contains(git2::Status::WT_MODIFIED));
+
+        assert!(UpdateAnalyzer::export_commit_trees(dir.path(), "HEAD", "no-such-branch").is_err());
+    }
+
     #[test]
     #[serial]
     fn test_lib_for_no_updates() {
@@ -639,4 +2236,68 @@ mod test {
         .unwrap()
         .is_none());
     }
+
+    #[test]
+    fn test_lib_dep_records_output() {
+        let records = vec![
+            DependencyRecord {
+                name: "tokio".to_string(),
+                version: Version::parse("1.7.1").unwrap(),
+                source: "crates.io".to_string(),
+                is_direct: true,
+                kind: DependencyKind::Normal,
+                cratesio_metrics: Some(CratesioReport {
+                    name: "tokio".to_string(),
+                    is_hosted: true,
+                    downloads: 1000,
+                    dependents: 10,
+                }),
+                cratesio_error: None,
+                github_metrics: None,
+                github_error: Some("rate limited, \"retry\" later".to_string()),
+                code_report: None,
+                code_error: Some("cargo geiger failed".to_string()),
+                advisories: Some(vec![
+                    "RUSTSEC-2021-0072".to_string(),
+                    "RUSTSEC-2021-0124".to_string(),
+                ]),
+                advisories_error: None,
+            },
+            DependencyRecord {
+                name: "cc".to_string(),
+                version: Version::parse("1.0.68").unwrap(),
+                source: "crates.io".to_string(),
+                is_direct: false,
+                kind: DependencyKind::Build,
+                cratesio_metrics: None,
+                cratesio_error: Some("not found".to_string()),
+                github_metrics: None,
+                github_error: None,
+                code_report: None,
+                code_error: None,
+                advisories: None,
+                advisories_error: Some("no database".to_string()),
+            },
+        ];
+
+        let csv = DependencyAnalyzer::get_dep_records_in_csv(&records);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], DEPENDENCY_RECORD_CSV_HEADER.join(","));
+        assert!(lines[1].starts_with("tokio,1.7.1,crates.io,true,Normal,1000,10,,"));
+        assert!(lines[1].contains(",\"rate limited, \"\"retry\"\" later\","));
+        assert!(lines[1].ends_with(",cargo geiger failed,RUSTSEC-2021-0072 RUSTSEC-2021-0124,"));
+        assert!(lines[2].starts_with("cc,1.0.68,crates.io,false,Build,,,not found,"));
+        assert!(lines[2].ends_with(",,no database"));
+
+        let ndjson = DependencyAnalyzer::get_dep_records_in_ndjson(&records).unwrap();
+        let values: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0]["cratesio_metrics"]["downloads"], 1000);
+        assert_eq!(values[1]["cratesio_error"], "not found");
+        assert!(values[1]["cratesio_metrics"].is_null());
+    }
 }
@@ -0,0 +1,455 @@
+//! This module reads the packages resolved in a Cargo.lock,
+//! or in the old and new sides of a unified diff of a Cargo.lock,
+//! so that dependency changes can be reviewed without running cargo metadata,
+//! i.e., without a resolvable workspace, network access, or a toolchain.
+//!
+//! Cargo.lock is read line by line rather than as a toml document
+//! as a diff hunk carries only fragments of the file
+
+use anyhow::{anyhow, Result};
+use semver::Version;
+use std::collections::{BTreeMap, BTreeSet};
+use std::{fs, path::Path};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: Version,
+    pub source: Option<String>, // None for workspace members and path dependencies
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Lockfile {
+    pub packages: BTreeSet<LockedPackage>,
+}
+
+/// A change of a locked package between two lockfiles,
+/// old is None for an addition and new is None for a removal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackageChange {
+    pub name: String,
+    pub old: Option<LockedPackage>,
+    pub new: Option<LockedPackage>,
+}
+
+impl Lockfile {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(Self {
+            packages: parse_packages(content.lines())?,
+        })
+    }
+
+    /// Reads the prior and post lockfile from a unified diff,
+    /// e.g., the output of `git diff -- Cargo.lock`.
+    /// Each side only contains the packages that appear in the hunks,
+    /// which is enough to find the changed ones
+    pub fn from_unified_diff(diff: &str) -> Result<(Self, Self)> {
+        let mut prior = Self::default();
+        let mut post = Self::default();
+
+        for hunk in get_lockfile_hunks(diff)? {
+            check_package_names(&hunk.old_lines)?;
+            check_package_names(&hunk.new_lines)?;
+            prior.packages.extend(parse_packages(
+                hunk.old_lines.iter().map(|(line, _)| *line),
+            )?);
+            post.packages.extend(parse_packages(
+                hunk.new_lines.iter().map(|(line, _)| *line),
+            )?);
+        }
+
+        Ok((prior, post))
+    }
+
+    /// Returns the packages added, removed, or changed in the other lockfile,
+    /// sorted by name.
+    /// Packages without a source, i.e., workspace members and path dependencies, are skipped
+    pub fn get_changes(&self, other: &Lockfile) -> Vec<LockedPackageChange> {
+        let removed = Self::get_packages_by_name(self.packages.difference(&other.packages));
+        let added = Self::get_packages_by_name(other.packages.difference(&self.packages));
+
+        let names: BTreeSet<&str> = removed.keys().chain(added.keys()).copied().collect();
+        let mut changes: Vec<LockedPackageChange> = Vec::new();
+        for name in names {
+            let removed = removed.get(name).cloned().unwrap_or_default();
+            let added = added.get(name).cloned().unwrap_or_default();
+
+            // Pair the versions of a crate in order, e.g., 0.3 -> 0.4 and 1.0 -> 1.2,
+            // and the rest are additions or removals
+            for i in 0..removed.len().max(added.len()) {
+                changes.push(LockedPackageChange {
+                    name: name.to_string(),
+                    old: removed.get(i).map(|&package| package.clone()),
+                    new: added.get(i).map(|&package| package.clone()),
+                });
+            }
+        }
+        changes
+    }
+
+    fn get_packages_by_name<'a>(
+        packages: impl Iterator<Item = &'a LockedPackage>,
+    ) -> BTreeMap<&'a str, Vec<&'a LockedPackage>> {
+        let mut packages_by_name: BTreeMap<&str, Vec<&LockedPackage>> = BTreeMap::new();
+        // packages are sorted by name and version
+        for package in packages.filter(|package| package.source.is_some()) {
+            packages_by_name
+                .entry(package.name.as_str())
+                .or_default()
+                .push(package);
+        }
+        packages_by_name
+    }
+}
+
+// Lines of each side with whether the line is changed
+struct LockfileHunk<'a> {
+    old_lines: Vec<(&'a str, bool)>,
+    new_lines: Vec<(&'a str, bool)>,
+}
+
+// Collects the hunks of the files named Cargo.lock in a unified diff
+fn get_lockfile_hunks(diff: &str) -> Result<Vec<LockfileHunk<'_>>> {
+    let mut hunks: Vec<LockfileHunk> = Vec::new();
+    let mut is_lockfile = false;
+    let mut lines = diff.lines();
+
+    while let Some(line) = lines.next() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            is_lockfile = path.trim_end().ends_with("Cargo.lock");
+            continue;
+        }
+        let (mut old_count, mut new_count) = match line.strip_prefix("@@ ") {
+            Some(header) => parse_hunk_header(header)?,
+            None => continue,
+        };
+
+        let mut hunk = LockfileHunk {
+            old_lines: Vec::new(),
+            new_lines: Vec::new(),
+        };
+        while old_count > 0 || new_count > 0 {
+            let line = lines
+                .next()
+                .ok_or_else(|| anyhow!("unexpected end of diff in a hunk"))?;
+            // a blank context line may lose its leading space, e.g., in an editor
+            let marker = line.chars().next().unwrap_or(' ');
+            let content = line.get(1..).unwrap_or_default();
+            match marker {
+                ' ' => {
+                    hunk.old_lines.push((content, false));
+                    hunk.new_lines.push((content, false));
+                    old_count = old_count.saturating_sub(1);
+                    new_count = new_count.saturating_sub(1);
+                }
+                '-' => {
+                    hunk.old_lines.push((content, true));
+                    old_count = old_count.saturating_sub(1);
+                }
+                '+' => {
+                    hunk.new_lines.push((content, true));
+                    new_count = new_count.saturating_sub(1);
+                }
+                '\\' => {} // \ No newline at end of file
+                _ => return Err(anyhow!("invalid line in a diff hunk: {}", line)),
+            }
+        }
+        if is_lockfile {
+            hunks.push(hunk);
+        }
+    }
+
+    Ok(hunks)
+}
+
+// e.g., -12,7 +12,7 @@ name = "foo"
+fn parse_hunk_header(header: &str) -> Result<(usize, usize)> {
+    let mut ranges = header.split_whitespace();
+    let mut get_count = |prefix: char| -> Result<usize> {
+        let range = ranges
+            .next()
+            .and_then(|range| range.strip_prefix(prefix))
+            .ok_or_else(|| anyhow!("invalid hunk header: @@ {}", header))?;
+        // the line count is 1 when omitted
+        match range.split_once(',') {
+            Some((_, count)) => Ok(count.parse()?),
+            None => Ok(1),
+        }
+    };
+    Ok((get_count('-')?, get_count('+')?))
+}
+
+// A changed version or source of a package cut off before its name,
+// e.g., in a diff without context lines (-U0), cannot be told apart
+// from an unchanged package and would be silently skipped
+fn check_package_names(lines: &[(&str, bool)]) -> Result<()> {
+    let mut has_name = false;
+    for (line, is_changed) in lines {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            has_name = false;
+        } else if let Some((key, value)) = line.split_once(" = ") {
+            // the lockfile format version is not quoted, e.g., version = 3
+            match key {
+                "name" => has_name = true,
+                "version" | "source" if *is_changed && !has_name && value.starts_with('"') => {
+                    return Err(anyhow!(
+                        "no package name in the context of a changed line in Cargo.lock: {}, \
+                        generate the diff with context lines, e.g., git diff -U3",
+                        line
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+// A fragment may start in the middle of a [[package]] table,
+// the fields read before the first header belong to that package
+fn parse_packages<'a>(lines: impl Iterator<Item = &'a str>) -> Result<BTreeSet<LockedPackage>> {
+    let mut packages: BTreeSet<LockedPackage> = BTreeSet::new();
+    let mut in_package = true;
+    let mut fields: BTreeMap<&str, &str> = BTreeMap::new();
+
+    for line in lines.map(|line| line.trim()) {
+        if line.starts_with('[') && !line.starts_with("[[package]]") && line.ends_with(']') {
+            // another table, e.g., [metadata] in old lockfiles
+            add_package(&mut packages, &mut fields)?;
+            in_package = false;
+        } else if line == "[[package]]" {
+            add_package(&mut packages, &mut fields)?;
+            in_package = true;
+        } else if in_package {
+            if let Some((key, value)) = line.split_once(" = ") {
+                if matches!(key, "name" | "version" | "source") {
+                    fields.insert(key, value.trim_matches('"'));
+                }
+            }
+        }
+    }
+    add_package(&mut packages, &mut fields)?;
+
+    Ok(packages)
+}
+
+// A package cut off by the end of a hunk without a name or version is skipped
+fn add_package<'a>(
+    packages: &mut BTreeSet<LockedPackage>,
+    fields: &mut BTreeMap<&'a str, &'a str>,
+) -> Result<()> {
+    if let (Some(name), Some(version)) = (fields.get("name"), fields.get("version")) {
+        packages.insert(LockedPackage {
+            name: name.to_string(),
+            version: Version::parse(version)?,
+            source: fields.get("source").map(|source| source.to_string()),
+        });
+    }
+    fields.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    const PRIOR_LOCKFILE: &str = r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "libc",
+ "rand 0.7.3",
+]
+
+[[package]]
+name = "libc"
+version = "0.2.97"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "12b8adadd720df158f4d70dfe7ccc6adb0472d7c55ca83445f6a5ab3e36f8fb6"
+
+[[package]]
+name = "rand"
+version = "0.7.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "6a6b1679d49b24bbfe0c803429aa1874472f50d9b363131f0e89fc356b544d03"
+
+[[package]]
+name = "tokio"
+version = "1.7.0"
+source = "git+https://github.com/tokio-rs/tokio?branch=master#1d8ea1b"
+"#;
+
+    fn get_test_package(name: &str, version: &str, source: Option<&str>) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            version: Version::parse(version).unwrap(),
+            source: source.map(|source| source.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_lockfile_parse() {
+        let lockfile = Lockfile::parse(PRIOR_LOCKFILE).unwrap();
+        assert_eq!(lockfile.packages.len(), 4);
+        assert!(lockfile
+            .packages
+            .contains(&get_test_package("app", "0.1.0", None)));
+        assert!(lockfile.packages.contains(&get_test_package(
+            "tokio",
+            "1.7.0",
+            Some("git+https://github.com/tokio-rs/tokio?branch=master#1d8ea1b")
+        )));
+
+        // the checksums of old lockfiles are not packages
+        let lockfile = Lockfile::parse(&format!(
+            "{}\n[metadata]\n\"checksum libc 0.2.97 ({})\" = \"12b8\"\n",
+            PRIOR_LOCKFILE, CRATES_IO
+        ))
+        .unwrap();
+        assert_eq!(lockfile.packages.len(), 4);
+
+        assert!(Lockfile::parse("[[package]]\nname = \"a\"\nversion = \"one\"").is_err());
+    }
+
+    #[test]
+    fn test_lockfile_changes() {
+        let prior = Lockfile::parse(PRIOR_LOCKFILE).unwrap();
+        let post = Lockfile::parse(
+            &PRIOR_LOCKFILE
+                .replace("0.2.97", "0.2.98")
+                .replace(
+                    "name = \"tokio\"\nversion = \"1.7.0\"",
+                    "name = \"rand\"\nversion = \"0.8.4\"",
+                )
+                .replace(
+                    "git+https://github.com/tokio-rs/tokio?branch=master#1d8ea1b",
+                    CRATES_IO,
+                )
+                .replace(
+                    "name = \"app\"\nversion = \"0.1.0\"",
+                    "name = \"app\"\nversion = \"0.2.0\"",
+                ),
+        )
+        .unwrap();
+
+        // the workspace member app is skipped
+        assert_eq!(
+            prior.get_changes(&post),
+            vec![
+                LockedPackageChange {
+                    name: "libc".to_string(),
+                    old: Some(get_test_package("libc", "0.2.97", Some(CRATES_IO))),
+                    new: Some(get_test_package("libc", "0.2.98", Some(CRATES_IO))),
+                },
+                LockedPackageChange {
+                    name: "rand".to_string(),
+                    old: None,
+                    new: Some(get_test_package("rand", "0.8.4", Some(CRATES_IO))),
+                },
+                LockedPackageChange {
+                    name: "tokio".to_string(),
+                    old: Some(get_test_package(
+                        "tokio",
+                        "1.7.0",
+                        Some("git+https://github.com/tokio-rs/tokio?branch=master#1d8ea1b")
+                    )),
+                    new: None,
+                },
+            ]
+        );
+        assert!(prior.get_changes(&prior).is_empty());
+    }
+
+    #[test]
+    fn test_lockfile_from_unified_diff() {
+        let diff = format!(
+            r#"diff --git a/src/main.rs b/src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1 +1 @@
+-name = "ignored"
++version = "1.0.0"
+diff --git a/Cargo.lock b/Cargo.lock
+index 3c5a1b2..8d0f9e4 100644
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -12,9 +12,9 @@ dependencies = [
+
+ [[package]]
+ name = "libc"
+-version = "0.2.97"
++version = "0.2.98"
+ source = "{crates_io}"
+-checksum = "12b8adadd720df158f4d70dfe7ccc6adb0472d7c55ca83445f6a5ab3e36f8fb6"
++checksum = "320cfe77175da3a483efed4bc0adc1968ca050b098ce4f2f1c13a56626128790"
+
+ [[package]]
+ name = "rand"
+@@ -25,3 +25,9 @@ checksum = "6a6b1679d49b24bbfe0c803429aa1874472f50d9b363131f0e89fc356b544d03"
+ name = "tokio"
+ version = "1.7.0"
+ source = "git+https://github.com/tokio-rs/tokio?branch=master#1d8ea1b"
++
++[[package]]
++name = "zeroize"
++version = "1.4.1"
++source = "{crates_io}"
++checksum = "377db0846015f7ae377174787dd452e1c5f5a9050bc6f954911d01f116daa0cd"
+\ No newline at end of file
+"#,
+            crates_io = CRATES_IO
+        );
+
+        let (prior, post) = Lockfile::from_unified_diff(&diff).unwrap();
+        // rand is cut off before its version on both sides
+        assert_eq!(
+            prior
+                .packages
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["libc", "tokio"]
+        );
+        assert_eq!(
+            prior.get_changes(&post),
+            vec![
+                LockedPackageChange {
+                    name: "libc".to_string(),
+                    old: Some(get_test_package("libc", "0.2.97", Some(CRATES_IO))),
+                    new: Some(get_test_package("libc", "0.2.98", Some(CRATES_IO))),
+                },
+                LockedPackageChange {
+                    name: "zeroize".to_string(),
+                    old: None,
+                    new: Some(get_test_package("zeroize", "1.4.1", Some(CRATES_IO))),
+                },
+            ]
+        );
+
+        // a version bump without the package name as context
+        assert!(Lockfile::from_unified_diff(
+            "+++ b/Cargo.lock\n@@ -14 +14 @@\n-version = \"0.2.97\"\n+version = \"0.2.98\"\n"
+        )
+        .is_err());
+        // but a lockfile format bump is not a package change
+        let (prior, post) = Lockfile::from_unified_diff(
+            "+++ b/Cargo.lock\n@@ -3 +3 @@\n-version = 3\n+version = 4\n",
+        )
+        .unwrap();
+        assert!(prior.get_changes(&post).is_empty());
+
+        assert!(Lockfile::from_unified_diff("+++ b/Cargo.lock\n@@ -1,2 +1,2 @@\n a\n").is_err());
+        assert!(Lockfile::from_unified_diff("+++ b/Cargo.lock\n@@ -1,x +1 @@\n a\n").is_err());
+    }
+}
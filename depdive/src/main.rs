@@ -1,11 +1,38 @@
-use anyhow::Result;
-use depdive::{DependencyAnalyzer, DependencyGraphAnalyzer, UpdateAnalyzer};
-use std::path::Path;
+use anyhow::{anyhow, Result};
+use depdive::{
+    advisory::ADVISORY_DB_ENV,
+    cache::{DepdiveCache, EvictionPolicy, CACHE_DIR_ENV},
+    lockfile::Lockfile,
+    policy::Policy,
+    registry::CARGO_CONFIG_ENV,
+    risk::RiskModel,
+    update::{ReviewOptions, UpdateReviewReport, WORKERS_ENV},
+    DependencyAnalyzer, DependencyGraphAnalyzer, DependencyRiskAnalyzer, UpdateAnalyzer,
+};
+use guppy::graph::PackageGraph;
+use std::{io::Read, path::Path, str::FromStr};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(about = "Rust dependency analysis")]
 struct Args {
+    #[structopt(long)]
+    /// Directory to cache crates.io tarballs and git repos across runs,
+    /// overrides DEPDIVE_CACHE_DIR
+    cache_dir: Option<String>,
+    #[structopt(long)]
+    /// Path to a local rustsec advisory database, e.g., a checkout of
+    /// https://github.com/rustsec/advisory-db, overrides DEPDIVE_ADVISORY_DB
+    advisory_db: Option<String>,
+    #[structopt(long)]
+    /// Max number of dependencies reviewed concurrently,
+    /// overrides DEPDIVE_WORKERS
+    workers: Option<usize>,
+    #[structopt(long)]
+    /// Cargo config file with alternative registries and source replacement,
+    /// taking precedence over the workspace and CARGO_HOME config,
+    /// overrides DEPDIVE_CARGO_CONFIG
+    cargo_config: Option<String>,
     #[structopt(subcommand)]
     cmd: Command,
 }
@@ -27,6 +54,38 @@ enum Command {
         #[structopt(subcommand)]
         cmd: DepReviewCommand,
     },
+
+    #[structopt(name = "why")]
+    /// Show the dependency paths from the workspace members
+    /// to every version of a crate
+    Why {
+        /// Name of the crate
+        crate_name: String,
+        #[structopt(long, default_value = ".")]
+        /// Path to the cargo project
+        path: String,
+        #[structopt(long, default_value = "markdown")]
+        /// Output format: markdown or json
+        format: OutputFormat,
+    },
+
+    #[structopt(name = "cache")]
+    /// Manage the cache of crates.io tarballs and git repos
+    Cache {
+        #[structopt(subcommand)]
+        cmd: CacheCommand,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum CacheCommand {
+    #[structopt(name = "clean")]
+    /// Evict entries per DEPDIVE_CACHE_MAX_AGE_DAYS and DEPDIVE_CACHE_MAX_SIZE_MB
+    Clean {
+        #[structopt(long)]
+        /// Remove all entries
+        all: bool,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -47,6 +106,69 @@ enum DepReviewCommand {
         /// if only direct deps should be analyzed
         only_direct: Option<bool>,
     },
+    #[structopt(name = "full")]
+    /// One record per dependency with all the metrics joined,
+    /// and the error of each metric that could not be collected
+    Full {
+        /// Path to repo
+        path: String,
+        #[structopt(long)]
+        /// if only direct deps should be analyzed
+        only_direct: Option<bool>,
+        #[structopt(long, default_value = "json")]
+        /// Output format: json, ndjson, or csv
+        format: RecordFormat,
+    },
+    #[structopt(name = "risk")]
+    /// Rank the dependencies by a risk score
+    /// combining usage, activity, and code metrics
+    Risk {
+        /// Path to repo
+        path: String,
+        #[structopt(long)]
+        /// if only direct deps should be analyzed
+        only_direct: Option<bool>,
+        #[structopt(long)]
+        /// Path to a risk model file with the factor weights and targets
+        model: Option<String>,
+        #[structopt(long, default_value = "markdown")]
+        /// Output format: markdown or json
+        format: OutputFormat,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+struct ReviewOptionsArgs {
+    #[structopt(long = "target", number_of_values = 1)]
+    /// Target triple to resolve the dependencies for, can be repeated,
+    /// any platform if not set
+    targets: Vec<String>,
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    /// Comma separated features to enable on top of the default features,
+    /// all features are enabled if neither this nor --no-default-features is set
+    features: Vec<String>,
+    #[structopt(long)]
+    /// Do not enable the default features
+    no_default_features: bool,
+    #[structopt(long)]
+    /// Exclude dev dependencies of the workspace
+    exclude_dev: bool,
+    #[structopt(long = "package", short = "p", number_of_values = 1)]
+    /// Workspace member to review the dependencies of, can be repeated,
+    /// all members if not set
+    members: Vec<String>,
+}
+
+impl From<ReviewOptionsArgs> for ReviewOptions {
+    fn from(args: ReviewOptionsArgs) -> Self {
+        ReviewOptions {
+            targets: args.targets,
+            features: args.features,
+            no_default_features: args.no_default_features,
+            exclude_dev: args.exclude_dev,
+            members: args.members,
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -57,38 +179,220 @@ enum UpdateReviewCommand {
         prior: String,
         /// Path to repository new state post update
         post: String,
+        #[structopt(long, default_value = "markdown")]
+        /// Output format: markdown or json
+        format: OutputFormat,
+        #[structopt(long)]
+        /// Path to a policy file, e.g., depdive.toml,
+        /// exits with an error if any deny rule is violated
+        policy: Option<String>,
+        #[structopt(flatten)]
+        review_options: ReviewOptionsArgs,
+    },
+
+    #[structopt(name = "lockfiles")]
+    /// Review the changes between two Cargo.lock files
+    /// without resolving the workspace with cargo metadata
+    Lockfiles {
+        /// Path to Cargo.lock prior to update
+        prior: String,
+        /// Path to Cargo.lock post update
+        post: String,
+        #[structopt(long, default_value = "markdown")]
+        /// Output format: markdown or json
+        format: OutputFormat,
+        #[structopt(long)]
+        /// Path to a policy file, e.g., depdive.toml,
+        /// exits with an error if any deny rule is violated
+        policy: Option<String>,
+    },
+
+    #[structopt(name = "lockfile-diff")]
+    /// Review the changes in a unified diff of a Cargo.lock,
+    /// e.g., the output of git diff -- Cargo.lock
+    LockfileDiff {
+        /// Path to the diff file, - to read from stdin
+        diff: String,
+        #[structopt(long, default_value = ".")]
+        /// Workspace root to read the Cargo config for registries from
+        workspace: String,
+        #[structopt(long, default_value = "markdown")]
+        /// Output format: markdown or json
+        format: OutputFormat,
+        #[structopt(long)]
+        /// Path to a policy file, e.g., depdive.toml,
+        /// exits with an error if any deny rule is violated
+        policy: Option<String>,
     },
 
     #[structopt(name = "commits")]
     Commits {
         /// Path to the git repository
         path: String,
-        /// Commit sha prior to update
+        /// Revision prior to update, e.g., a commit sha, branch, tag, or HEAD~3
         prior: String,
-        /// Commit sha post update
+        /// Revision post update
         post: String,
+        #[structopt(long, default_value = "markdown")]
+        /// Output format: markdown or json
+        format: OutputFormat,
+        #[structopt(long)]
+        /// Path to a policy file, e.g., depdive.toml,
+        /// exits with an error if any deny rule is violated
+        policy: Option<String>,
+        #[structopt(flatten)]
+        review_options: ReviewOptionsArgs,
     },
 }
 
-fn update_analyzer_from_paths(prior: &str, post: &str) -> Result<()> {
-    let report = UpdateAnalyzer::run_update_analyzer_from_paths(Path::new(prior), Path::new(post))?
-        .unwrap_or_default();
-    println!("{}", report);
-    Ok(())
+#[derive(Debug)]
+enum OutputFormat {
+    Markdown,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "markdown" => Ok(OutputFormat::Markdown),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(anyhow!(
+                "unknown output format {}, expected markdown or json",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum RecordFormat {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl FromStr for RecordFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(RecordFormat::Json),
+            "ndjson" => Ok(RecordFormat::Ndjson),
+            "csv" => Ok(RecordFormat::Csv),
+            _ => Err(anyhow!(
+                "unknown output format {}, expected json, ndjson, or csv",
+                s
+            )),
+        }
+    }
+}
+
+fn update_analyzer_from_paths(
+    prior: &str,
+    post: &str,
+    format: OutputFormat,
+    policy: Option<String>,
+    review_options: &ReviewOptions,
+) -> Result<()> {
+    let (prior_graph, post_graph) =
+        UpdateAnalyzer::get_package_graphs_from_paths(Path::new(prior), Path::new(post))?;
+    review_updates(&prior_graph, &post_graph, format, policy, review_options)
+}
+
+fn update_analyzer_from_lockfiles(
+    prior: &str,
+    post: &str,
+    format: OutputFormat,
+    policy: Option<String>,
+) -> Result<()> {
+    let update_review_report = UpdateAnalyzer::run_lockfile_update_analyzer(
+        &Lockfile::from_file(Path::new(prior))?,
+        &Lockfile::from_file(Path::new(post))?,
+        &UpdateAnalyzer::get_lockfile_workspace_root(Path::new(post))?,
+    )?;
+    output_review(&update_review_report, format, policy)
+}
+
+fn update_analyzer_from_lockfile_diff(
+    diff: &str,
+    workspace: &str,
+    format: OutputFormat,
+    policy: Option<String>,
+) -> Result<()> {
+    let diff = if diff == "-" {
+        let mut diff = String::new();
+        std::io::stdin().read_to_string(&mut diff)?;
+        diff
+    } else {
+        std::fs::read_to_string(diff)?
+    };
+    let (prior_lockfile, post_lockfile) = Lockfile::from_unified_diff(&diff)?;
+    let update_review_report = UpdateAnalyzer::run_lockfile_update_analyzer(
+        &prior_lockfile,
+        &post_lockfile,
+        Path::new(workspace),
+    )?;
+    output_review(&update_review_report, format, policy)
 }
 
 fn update_analyzer_from_repo_commits(
     path: &str,
     prior_commit: &str,
     post_commit: &str,
+    format: OutputFormat,
+    policy: Option<String>,
+    review_options: &ReviewOptions,
 ) -> Result<()> {
-    let report = UpdateAnalyzer::run_update_analyzer_from_repo_commits(
+    // Keep the exported trees during the review
+    // for the registries in their Cargo config
+    let (prior_graph, post_graph, _trees) = UpdateAnalyzer::get_package_graphs_from_repo_commits(
         Path::new(path),
         prior_commit,
         post_commit,
-    )?
-    .unwrap_or_default();
+    )?;
+    review_updates(&prior_graph, &post_graph, format, policy, review_options)
+}
+
+fn review_updates(
+    prior_graph: &PackageGraph,
+    post_graph: &PackageGraph,
+    format: OutputFormat,
+    policy: Option<String>,
+    review_options: &ReviewOptions,
+) -> Result<()> {
+    let update_review_report =
+        UpdateAnalyzer::run_update_analyzer_with_options(prior_graph, post_graph, review_options)?;
+    output_review(&update_review_report, format, policy)
+}
+
+fn output_review(
+    update_review_report: &UpdateReviewReport,
+    format: OutputFormat,
+    policy: Option<String>,
+) -> Result<()> {
+    let report = match format {
+        OutputFormat::Markdown => {
+            UpdateAnalyzer::get_summary_report_from_review(update_review_report)?
+                .unwrap_or_default()
+        }
+        OutputFormat::Json => serde_json::to_string(update_review_report)?,
+    };
     println!("{}", report);
+
+    if let Some(policy) = policy {
+        let policy_report = Policy::from_file(Path::new(&policy))?.evaluate(update_review_report);
+        for violation in &policy_report.violations {
+            eprintln!("{}", violation);
+        }
+        if policy_report.has_denials() {
+            return Err(anyhow!(
+                "the dependency changes violate the policy in {}",
+                policy
+            ));
+        }
+    }
     Ok(())
 }
 
@@ -110,16 +414,137 @@ fn get_code_metrics_for_deps_in_json(path: &str, only_direct: Option<bool>) -> R
     Ok(())
 }
 
+fn get_dep_records(path: &str, only_direct: Option<bool>, format: RecordFormat) -> Result<()> {
+    let records = DependencyAnalyzer::get_dep_records_from_path(
+        Path::new(path),
+        only_direct.unwrap_or(false),
+    )?;
+    let report = match format {
+        RecordFormat::Json => format!("{}\n", serde_json::to_string(&records)?),
+        RecordFormat::Ndjson => DependencyAnalyzer::get_dep_records_in_ndjson(&records)?,
+        RecordFormat::Csv => DependencyAnalyzer::get_dep_records_in_csv(&records),
+    };
+    // ndjson and csv end with a line break
+    print!("{}", report);
+    Ok(())
+}
+
+fn get_risk_scores_for_deps(
+    path: &str,
+    only_direct: Option<bool>,
+    model: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let risk_model = match model {
+        Some(model) => RiskModel::from_file(Path::new(&model))?,
+        None => RiskModel::default(),
+    };
+    let risk_scores = DependencyRiskAnalyzer::get_risk_scores_from_path(
+        Path::new(path),
+        only_direct.unwrap_or(false),
+        &risk_model,
+    )?;
+    let report = match format {
+        OutputFormat::Markdown => DependencyRiskAnalyzer::get_risk_report(&risk_scores),
+        OutputFormat::Json => serde_json::to_string(&risk_scores)?,
+    };
+    println!("{}", report);
+    Ok(())
+}
+
+fn get_dependency_paths(crate_name: &str, path: &str, format: OutputFormat) -> Result<()> {
+    let dependency_paths =
+        DependencyGraphAnalyzer::get_dependency_paths_from_path(Path::new(path), crate_name)?;
+    let report = match format {
+        OutputFormat::Markdown => {
+            DependencyGraphAnalyzer::get_dependency_paths_report(&dependency_paths)
+        }
+        OutputFormat::Json => serde_json::to_string(&dependency_paths)?,
+    };
+    println!("{}", report);
+    Ok(())
+}
+
+fn clean_cache(all: bool) -> Result<()> {
+    let cache = DepdiveCache::from_env()?.ok_or_else(|| {
+        anyhow!(
+            "no cache directory set, use --cache-dir or {}",
+            CACHE_DIR_ENV
+        )
+    })?;
+    let evicted = if all {
+        cache.clean()?
+    } else {
+        cache.evict(&EvictionPolicy::from_env()?)?
+    };
+    for path in &evicted {
+        println!("removed {}", path.display());
+    }
+    Ok(())
+}
+
+// Keep the cache within the eviction policy after a run
+fn evict_cache() -> Result<()> {
+    if let Some(cache) = DepdiveCache::from_env()? {
+        cache.evict(&EvictionPolicy::from_env()?)?;
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let args = Args::from_iter(std::env::args());
+    // Analyzers read these options from the environment
+    if let Some(cache_dir) = args.cache_dir {
+        std::env::set_var(CACHE_DIR_ENV, cache_dir);
+    }
+    if let Some(advisory_db) = args.advisory_db {
+        std::env::set_var(ADVISORY_DB_ENV, advisory_db);
+    }
+    if let Some(workers) = args.workers {
+        std::env::set_var(WORKERS_ENV, workers.to_string());
+    }
+    if let Some(cargo_config) = args.cargo_config {
+        std::env::set_var(CARGO_CONFIG_ENV, cargo_config);
+    }
 
     match args.cmd {
         Command::UpdateReview { cmd } => match cmd {
-            UpdateReviewCommand::Paths { prior, post } => update_analyzer_from_paths(&prior, &post),
-            UpdateReviewCommand::Commits { path, prior, post } => {
-                update_analyzer_from_repo_commits(&path, &prior, &post)
-            }
-        },
+            UpdateReviewCommand::Paths {
+                prior,
+                post,
+                format,
+                policy,
+                review_options,
+            } => update_analyzer_from_paths(&prior, &post, format, policy, &review_options.into()),
+            UpdateReviewCommand::Commits {
+                path,
+                prior,
+                post,
+                format,
+                policy,
+                review_options,
+            } => update_analyzer_from_repo_commits(
+                &path,
+                &prior,
+                &post,
+                format,
+                policy,
+                &review_options.into(),
+            ),
+            UpdateReviewCommand::Lockfiles {
+                prior,
+                post,
+                format,
+                policy,
+            } => update_analyzer_from_lockfiles(&prior, &post, format, policy),
+            UpdateReviewCommand::LockfileDiff {
+                diff,
+                workspace,
+                format,
+                policy,
+            } => update_analyzer_from_lockfile_diff(&diff, &workspace, format, policy),
+        }
+        .and(evict_cache()),
         Command::DepReview { cmd } => match cmd {
             DepReviewCommand::PackageMetrics { path, only_direct } => {
                 get_package_metrics_for_deps_in_json(&path, only_direct)
@@ -127,6 +552,25 @@ fn main() -> Result<()> {
             DepReviewCommand::CodeMetrics { path, only_direct } => {
                 get_code_metrics_for_deps_in_json(&path, only_direct)
             }
+            DepReviewCommand::Full {
+                path,
+                only_direct,
+                format,
+            } => get_dep_records(&path, only_direct, format),
+            DepReviewCommand::Risk {
+                path,
+                only_direct,
+                model,
+                format,
+            } => get_risk_scores_for_deps(&path, only_direct, model, format),
+        },
+        Command::Why {
+            crate_name,
+            path,
+            format,
+        } => get_dependency_paths(&crate_name, &path, format),
+        Command::Cache { cmd } => match cmd {
+            CacheCommand::Clean { all } => clean_cache(all),
         },
     }
 }
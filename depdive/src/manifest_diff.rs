@@ -0,0 +1,312 @@
+//! This module compares the Cargo.toml.orig of a crate published on crates.io
+//! with the Cargo.toml in its git source
+//! for the differences that matter for a build:
+//! dependencies, features, build script, links, and proc-macro.
+//! The manifests are compared semantically to ignore whitespace and ordering
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use toml::Value;
+
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ManifestChangeKind {
+    Dependency,
+    Feature,
+    Build,
+    Links,
+    ProcMacro,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestChange {
+    pub kind: ManifestChangeKind,
+    // e.g., dependencies, target.'cfg(unix)'.build-dependencies, features, package, lib
+    pub section: String,
+    pub key: String,              // dependency, feature, or field name
+    pub git: Option<String>,      // None when absent in the git source
+    pub cratesio: Option<String>, // None when absent in crates.io
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ManifestDiff {
+    pub changes: Vec<ManifestChange>,
+}
+
+impl ManifestDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+// (kind, section, key) -> normalized value
+type ManifestEntries = BTreeMap<(ManifestChangeKind, String, String), String>;
+
+/// Returns the semantic differences between the git source Cargo.toml
+/// and crates.io Cargo.toml.orig
+pub fn get_manifest_diff(git_toml: &str, cratesio_toml: &str) -> Result<ManifestDiff> {
+    let git = get_manifest_entries(&toml::from_str(git_toml)?)?;
+    let cratesio = get_manifest_entries(&toml::from_str(cratesio_toml)?)?;
+
+    let keys: BTreeSet<&(ManifestChangeKind, String, String)> =
+        git.keys().chain(cratesio.keys()).collect();
+    let changes = keys
+        .into_iter()
+        .filter(|key| git.get(key) != cratesio.get(key))
+        .map(|key| {
+            let (kind, section, name) = key;
+            ManifestChange {
+                kind: *kind,
+                section: section.clone(),
+                key: name.clone(),
+                git: git.get(key).cloned(),
+                cratesio: cratesio.get(key).cloned(),
+            }
+        })
+        .collect();
+
+    Ok(ManifestDiff { changes })
+}
+
+fn get_manifest_entries(toml: &Value) -> Result<ManifestEntries> {
+    let mut entries = ManifestEntries::new();
+
+    add_dependency_entries(toml, "", &mut entries)?;
+    if let Some(targets) = toml.get("target").and_then(|t| t.as_table()) {
+        for (target, table) in targets {
+            add_dependency_entries(table, &format!("target.'{}'.", target), &mut entries)?;
+        }
+    }
+
+    if let Some(features) = toml.get("features").and_then(|f| f.as_table()) {
+        for (feature, value) in features {
+            entries.insert(
+                (
+                    ManifestChangeKind::Feature,
+                    "features".to_string(),
+                    feature.clone(),
+                ),
+                get_normalized_value(value),
+            );
+        }
+    }
+
+    let fields = [
+        (ManifestChangeKind::Build, "package", "build"),
+        (ManifestChangeKind::Links, "package", "links"),
+        (ManifestChangeKind::ProcMacro, "lib", "proc-macro"),
+    ];
+    for (kind, section, field) in fields.iter() {
+        if let Some(value) = get_field(toml.get(section), field) {
+            entries.insert(
+                (*kind, section.to_string(), field.to_string()),
+                get_normalized_value(value),
+            );
+        }
+    }
+
+    Ok(entries)
+}
+
+fn add_dependency_entries(toml: &Value, prefix: &str, entries: &mut ManifestEntries) -> Result<()> {
+    for table_name in DEPENDENCY_TABLES.iter() {
+        let table = match get_field(Some(toml), table_name) {
+            Some(table) => table
+                .as_table()
+                .ok_or_else(|| anyhow!("{}{} is not a table", prefix, table_name))?,
+            None => continue,
+        };
+        for (name, spec) in table {
+            entries.insert(
+                (
+                    ManifestChangeKind::Dependency,
+                    format!("{}{}", prefix, table_name),
+                    name.clone(),
+                ),
+                get_normalized_dependency(spec),
+            );
+        }
+    }
+    Ok(())
+}
+
+// Cargo accepts both dash and underscore in keys, e.g., proc-macro and proc_macro
+fn get_field<'a>(table: Option<&'a Value>, field: &str) -> Option<&'a Value> {
+    let table = table?;
+    table
+        .get(field)
+        .or_else(|| table.get(field.replace('-', "_")))
+}
+
+// A version string and a table with only the version are the same dependency,
+// and default values are dropped
+fn get_normalized_dependency(spec: &Value) -> String {
+    let mut fields: BTreeMap<String, String> = BTreeMap::new();
+    match spec {
+        Value::Table(table) => {
+            for (key, value) in table {
+                let key = key.replace('_', "-");
+                let is_default = matches!(
+                    (key.as_str(), value),
+                    ("default-features", Value::Boolean(true))
+                        | ("optional", Value::Boolean(false))
+                );
+                if !is_default {
+                    fields.insert(key, get_normalized_value(value));
+                }
+            }
+        }
+        value => {
+            fields.insert("version".to_string(), get_normalized_value(value));
+        }
+    }
+
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{} = {}", key, value))
+        .collect();
+    format!("{{ {} }}", fields.join(", "))
+}
+
+// Arrays, e.g., features, are compared as sets
+fn get_normalized_value(value: &Value) -> String {
+    match value {
+        Value::Array(array) => {
+            let items: BTreeSet<String> = array.iter().map(get_normalized_value).collect();
+            format!(
+                "[{}]",
+                items.into_iter().collect::<Vec<String>>().join(", ")
+            )
+        }
+        Value::Table(table) => {
+            let fields: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, get_normalized_value(value)))
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GIT_TOML: &str = r#"
+        [package]
+        name = "foo"
+        version = "0.1.0"
+
+        [dependencies]
+        serde = { version = "1.0", features = ["derive", "rc"] }
+        libc = "0.2"
+
+        [target.'cfg(unix)'.dependencies]
+        nix = "0.20"
+
+        [features]
+        default = ["std"]
+        std = []
+    "#;
+
+    #[test]
+    fn test_manifest_diff_ignores_noise() {
+        let cratesio_toml = r#"
+            [features]
+            std    = []
+            default = [ "std" ]
+
+            [dependencies]
+            libc = { version = "0.2", default-features = true }
+            serde = { features = ["rc", "derive"], version = "1.0", optional = false }
+
+            [target."cfg(unix)".dependencies]
+            nix = { version = "0.20" }
+
+            [package]
+            version = "0.1.0"
+            name = "foo"
+        "#;
+        let diff = get_manifest_diff(GIT_TOML, cratesio_toml).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_manifest_diff_changes() {
+        let cratesio_toml = r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            build = "build.rs"
+            links = "foo"
+
+            [lib]
+            proc_macro = true
+
+            [dependencies]
+            serde = { version = "1.0", features = ["derive"] }
+            libc = "0.2"
+
+            [target.'cfg(unix)'.dependencies]
+            nix = "0.20"
+
+            [build-dependencies]
+            evil = "0.1"
+
+            [features]
+            default = ["std", "evil"]
+            std = []
+        "#;
+        let diff = get_manifest_diff(GIT_TOML, cratesio_toml).unwrap();
+        assert_eq!(
+            diff.changes,
+            vec![
+                ManifestChange {
+                    kind: ManifestChangeKind::Dependency,
+                    section: "build-dependencies".to_string(),
+                    key: "evil".to_string(),
+                    git: None,
+                    cratesio: Some("{ version = \"0.1\" }".to_string()),
+                },
+                ManifestChange {
+                    kind: ManifestChangeKind::Dependency,
+                    section: "dependencies".to_string(),
+                    key: "serde".to_string(),
+                    git: Some("{ features = [\"derive\", \"rc\"], version = \"1.0\" }".to_string()),
+                    cratesio: Some("{ features = [\"derive\"], version = \"1.0\" }".to_string()),
+                },
+                ManifestChange {
+                    kind: ManifestChangeKind::Feature,
+                    section: "features".to_string(),
+                    key: "default".to_string(),
+                    git: Some("[\"std\"]".to_string()),
+                    cratesio: Some("[\"evil\", \"std\"]".to_string()),
+                },
+                ManifestChange {
+                    kind: ManifestChangeKind::Build,
+                    section: "package".to_string(),
+                    key: "build".to_string(),
+                    git: None,
+                    cratesio: Some("\"build.rs\"".to_string()),
+                },
+                ManifestChange {
+                    kind: ManifestChangeKind::Links,
+                    section: "package".to_string(),
+                    key: "links".to_string(),
+                    git: None,
+                    cratesio: Some("\"foo\"".to_string()),
+                },
+                ManifestChange {
+                    kind: ManifestChangeKind::ProcMacro,
+                    section: "lib".to_string(),
+                    key: "proc-macro".to_string(),
+                    git: None,
+                    cratesio: Some("true".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,652 @@
+//! This module abstracts a policy engine
+//! to evaluate update review reports against configurable rules,
+//! e.g., to fail a CI job on a risky dependency update.
+//!
+//! A policy is read from a toml file, e.g., depdive.toml:
+//! ```toml
+//! [advisories]
+//! introduced = "deny"
+//!
+//! [build_script]
+//! changed = "deny"
+//! allowlist = ["libgit2-sys"]
+//!
+//! [crate_source]
+//! differs_from_git = "warn"
+//!
+//! [unsafe_code]
+//! increase = "deny"
+//! max_increase = 0
+//!
+//! [downloads]
+//! below_minimum = "deny"
+//! min_downloads = 10000
+//!
+//! [release_age]
+//! too_new = "warn"
+//! min_days = 7
+//! ```
+//! A rule not present in the file is not enforced.
+
+use anyhow::{anyhow, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, fs, path::Path};
+
+use crate::code::UnsafeDetails;
+use crate::diff::CrateSourceDiffReport;
+use crate::file_kind::DiffSeverity;
+use crate::update::{DepUpdateReviewReport, UnsafeDelta, UpdateReviewReport, VersionInfo};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    #[default]
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub advisories: AdvisoryPolicy,
+    pub build_script: BuildScriptPolicy,
+    pub crate_source: CrateSourcePolicy,
+    pub unsafe_code: UnsafeCodePolicy,
+    pub downloads: DownloadsPolicy,
+    pub release_age: ReleaseAgePolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdvisoryPolicy {
+    // advisories present after the change but not before
+    pub introduced: RuleLevel,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildScriptPolicy {
+    // modified build scripts in an update, or a build script in an added crate
+    pub changed: RuleLevel,
+    pub allowlist: HashSet<String>, // crate names exempted from the rule
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrateSourcePolicy {
+    pub differs_from_git: RuleLevel,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnsafeCodePolicy {
+    // geiger unsafe counters added across files,
+    // removals elsewhere do not offset them,
+    // for an added crate, all of its unsafe counters
+    pub increase: RuleLevel,
+    pub max_increase: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadsPolicy {
+    pub below_minimum: RuleLevel,
+    pub min_downloads: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReleaseAgePolicy {
+    // the version was published fewer than min_days ago at review time
+    pub too_new: RuleLevel,
+    pub min_days: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PolicyRule {
+    IntroducedAdvisory,
+    BuildScriptChanged,
+    CrateSourceDiffers,
+    UnsafeIncrease,
+    LowDownloads,
+    TooNew,
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PolicyRule::IntroducedAdvisory => "advisories.introduced",
+            PolicyRule::BuildScriptChanged => "build_script.changed",
+            PolicyRule::CrateSourceDiffers => "crate_source.differs_from_git",
+            PolicyRule::UnsafeIncrease => "unsafe_code.increase",
+            PolicyRule::LowDownloads => "downloads.below_minimum",
+            PolicyRule::TooNew => "release_age.too_new",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub rule: PolicyRule,
+    pub level: RuleLevel,
+    pub name: String,
+    pub version: Version,
+    pub message: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            RuleLevel::Deny => "deny",
+            RuleLevel::Warn => "warn",
+            RuleLevel::Allow => "allow",
+        };
+        write!(
+            f,
+            "{}: [{}] {} {}: {}",
+            level, self.rule, self.name, self.version, self.message
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PolicyReport {
+    pub violations: Vec<PolicyViolation>,
+}
+
+impl PolicyReport {
+    pub fn has_denials(&self) -> bool {
+        self.violations.iter().any(|v| v.level == RuleLevel::Deny)
+    }
+}
+
+impl Policy {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read policy file {}: {}", path.display(), e))?;
+        Self::from_toml_str(&content)
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Evaluate the policy against an update review report
+    /// and return the violations sorted by crate, version, and rule
+    pub fn evaluate(&self, report: &UpdateReviewReport) -> PolicyReport {
+        let mut violations: Vec<PolicyViolation> = Vec::new();
+
+        for dep in report
+            .dep_update_review_reports
+            .iter()
+            .chain(report.dep_downgrade_review_reports.iter())
+            .chain(report.dep_source_change_review_reports.iter())
+        {
+            self.evaluate_dep_update(dep, &mut violations);
+        }
+
+        for dep in &report.dep_addition_review_reports {
+            let version = &dep.added_version;
+
+            let advisories: Vec<&str> = version
+                .known_advisories
+                .iter()
+                .map(|a| a.id.as_str())
+                .collect();
+            self.check_introduced_advisories(version, &advisories, &mut violations);
+
+            if !dep.build_script_paths.is_empty() {
+                let mut paths: Vec<&str> =
+                    dep.build_script_paths.iter().map(|p| p.as_str()).collect();
+                paths.sort_unstable();
+                self.check_build_scripts(version, "adds build script(s)", &paths, &mut violations);
+            }
+
+            if let Some(unsafe_stats) = &dep.unsafe_stats {
+                let added = Self::get_unsafe_total(&unsafe_stats.unsafe_details);
+                self.check_unsafe_increase(version, (added, 0), &mut violations);
+            }
+
+            // Crates not hosted on crates.io have no download count
+            if let Some(crate_source_diff_report) = &version.crate_source_diff_report {
+                self.check_crate_source(version, crate_source_diff_report, &mut violations);
+                self.check_downloads(version, &mut violations);
+            }
+            self.check_release_age(version, &mut violations);
+        }
+
+        violations
+            .sort_by(|a, b| (&a.name, &a.version, a.rule).cmp(&(&b.name, &b.version, b.rule)));
+        PolicyReport { violations }
+    }
+
+    fn evaluate_dep_update(
+        &self,
+        dep: &DepUpdateReviewReport,
+        violations: &mut Vec<PolicyViolation>,
+    ) {
+        let version = &dep.updated_version;
+
+        let advisories: Vec<&str> = version
+            .known_advisories
+            .iter()
+            .filter(|a| !dep.prior_version.known_advisories.contains(a))
+            .map(|a| a.id.as_str())
+            .collect();
+        self.check_introduced_advisories(version, &advisories, violations);
+
+        if let Some(diff_stats) = &dep.diff_stats {
+            if !diff_stats.modified_build_scripts.is_empty() {
+                let mut paths: Vec<&str> = diff_stats
+                    .modified_build_scripts
+                    .iter()
+                    .map(|p| p.as_str())
+                    .collect();
+                paths.sort_unstable();
+                self.check_build_scripts(version, "modifies build script(s)", &paths, violations);
+            }
+
+            let (mut added, mut removed) = (0, 0);
+            for stats in &diff_stats.unsafe_file_changed {
+                let (file_added, file_removed) = Self::get_unsafe_changes(&stats.unsafe_delta);
+                added += file_added;
+                removed += file_removed;
+            }
+            self.check_unsafe_increase(version, (added, removed), violations);
+        }
+
+        // Crates not hosted on crates.io have no download count
+        if let Some(crate_source_diff_report) = &version.crate_source_diff_report {
+            self.check_crate_source(version, crate_source_diff_report, violations);
+            self.check_downloads(version, violations);
+        }
+        self.check_release_age(version, violations);
+    }
+
+    fn check_introduced_advisories(
+        &self,
+        version: &VersionInfo,
+        advisories: &[&str],
+        violations: &mut Vec<PolicyViolation>,
+    ) {
+        if advisories.is_empty() {
+            return;
+        }
+        Self::add_violation(
+            violations,
+            PolicyRule::IntroducedAdvisory,
+            self.advisories.introduced,
+            version,
+            format!("introduces known advisories {}", advisories.join(", ")),
+        );
+    }
+
+    fn check_build_scripts(
+        &self,
+        version: &VersionInfo,
+        change: &str,
+        paths: &[&str],
+        violations: &mut Vec<PolicyViolation>,
+    ) {
+        if self.build_script.allowlist.contains(&version.name) {
+            return;
+        }
+        Self::add_violation(
+            violations,
+            PolicyRule::BuildScriptChanged,
+            self.build_script.changed,
+            version,
+            format!("{} {}", change, paths.join(", ")),
+        );
+    }
+
+    fn check_crate_source(
+        &self,
+        version: &VersionInfo,
+        crate_source_diff_report: &CrateSourceDiffReport,
+        violations: &mut Vec<PolicyViolation>,
+    ) {
+        // Differences only in docs, tests, and the like are noise
+        if crate_source_diff_report.get_severity() != Some(DiffSeverity::High) {
+            return;
+        }
+        Self::add_violation(
+            violations,
+            PolicyRule::CrateSourceDiffers,
+            self.crate_source.differs_from_git,
+            version,
+            "crates.io code differs from the git source in code-relevant files".to_string(),
+        );
+    }
+
+    fn check_unsafe_increase(
+        &self,
+        version: &VersionInfo,
+        (added, removed): (u64, u64),
+        violations: &mut Vec<PolicyViolation>,
+    ) {
+        if added <= self.unsafe_code.max_increase {
+            return;
+        }
+        Self::add_violation(
+            violations,
+            PolicyRule::UnsafeIncrease,
+            self.unsafe_code.increase,
+            version,
+            format!(
+                "adds {} unsafe counter(s), more than the allowed {}, and removes {}",
+                added, self.unsafe_code.max_increase, removed
+            ),
+        );
+    }
+
+    fn check_downloads(&self, version: &VersionInfo, violations: &mut Vec<PolicyViolation>) {
+        // Registries without a web API have no download stats
+        let downloads = match version.downloads {
+            Some(downloads) => downloads,
+            None => return,
+        };
+        if downloads >= self.downloads.min_downloads {
+            return;
+        }
+        Self::add_violation(
+            violations,
+            PolicyRule::LowDownloads,
+            self.downloads.below_minimum,
+            version,
+            format!(
+                "{} downloads, fewer than the required {}",
+                downloads, self.downloads.min_downloads
+            ),
+        );
+    }
+
+    fn check_release_age(&self, version: &VersionInfo, violations: &mut Vec<PolicyViolation>) {
+        // Versions not from a registry have no release info
+        let release_info = match &version.release_info {
+            Some(release_info) => release_info,
+            None => return,
+        };
+        if release_info.age_days >= self.release_age.min_days {
+            return;
+        }
+        Self::add_violation(
+            violations,
+            PolicyRule::TooNew,
+            self.release_age.too_new,
+            version,
+            format!(
+                "released {} day(s) ago, fewer than the required {}",
+                release_info.age_days, self.release_age.min_days
+            ),
+        );
+    }
+
+    fn add_violation(
+        violations: &mut Vec<PolicyViolation>,
+        rule: PolicyRule,
+        level: RuleLevel,
+        version: &VersionInfo,
+        message: String,
+    ) {
+        if level == RuleLevel::Allow {
+            return;
+        }
+        violations.push(PolicyViolation {
+            rule,
+            level,
+            name: version.name.clone(),
+            version: version.version.clone(),
+            message,
+        });
+    }
+
+    // Counters added and removed in a file, kept apart
+    // so that removed unsafe code does not hide added one
+    fn get_unsafe_changes(delta: &UnsafeDelta) -> (u64, u64) {
+        let counters = [
+            delta.functions,
+            delta.expressions,
+            delta.impls,
+            delta.traits,
+            delta.methods,
+        ];
+        let added = counters.iter().filter(|&&c| c > 0).map(|&c| c as u64).sum();
+        let removed = counters
+            .iter()
+            .filter(|&&c| c < 0)
+            .map(|c| c.unsigned_abs())
+            .sum();
+        (added, removed)
+    }
+
+    fn get_unsafe_total(unsafe_details: &UnsafeDetails) -> u64 {
+        unsafe_details.functions
+            + unsafe_details.expressions
+            + unsafe_details.impls
+            + unsafe_details.traits
+            + unsafe_details.methods
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cratesio::ReleaseInfo;
+    use crate::diff::{ClassifiedFile, FileDiffStats};
+    use crate::file_kind::{NativeArtifacts, SourceFileKind};
+    use crate::update::{
+        CrateUnsafeStats, CrateVersionRustSecAdvisory, DepAdditionReviewReport,
+        FileUnsafeChangeStats, FileUnsafeCodeChangeStatus, VersionDiffStats,
+    };
+    use git2::Delta;
+    use std::path::PathBuf;
+
+    fn get_test_version_info(name: &str, version: &str, downloads: u64) -> VersionInfo {
+        VersionInfo {
+            name: name.to_string(),
+            version: Version::parse(version).unwrap(),
+            downloads: Some(downloads),
+            crate_source_diff_report: Some(CrateSourceDiffReport {
+                name: name.to_string(),
+                version: version.to_string(),
+                is_different: Some(false),
+                ..Default::default()
+            }),
+            known_advisories: Vec::new(),
+            git_source: None,
+            release_info: None,
+        }
+    }
+
+    fn get_test_advisory(id: &str) -> CrateVersionRustSecAdvisory {
+        CrateVersionRustSecAdvisory {
+            id: id.to_string(),
+            title: "test advisory".to_string(),
+            url: None,
+        }
+    }
+
+    fn get_test_update_review_report() -> UpdateReviewReport {
+        // tokio introduces an advisory, modifies its build script,
+        // adds unsafe code, and was released a day ago
+        let prior_version = get_test_version_info("tokio", "1.7.0", 1_000_000);
+        let mut updated_version = get_test_version_info("tokio", "1.7.1", 500);
+        updated_version.known_advisories = vec![get_test_advisory("RUSTSEC-2021-0072")];
+        updated_version.crate_source_diff_report = Some(CrateSourceDiffReport {
+            is_different: Some(true),
+            ..Default::default()
+        });
+        updated_version.release_info = Some(ReleaseInfo {
+            created_at: chrono::Utc::now() - chrono::Duration::days(1),
+            age_days: 1,
+            yanked: false,
+            newer_versions: 0,
+        });
+        let diff_stats = VersionDiffStats {
+            files_changed: HashSet::new(),
+            rust_files_changed: 2,
+            insertions: 10,
+            deletions: 2,
+            modified_build_scripts: vec!["build.rs".to_string()].into_iter().collect(),
+            unsafe_file_changed: vec![
+                FileUnsafeChangeStats {
+                    file: "src/lib.rs".to_string(),
+                    change_type: Delta::Modified,
+                    unsafe_change_status: FileUnsafeCodeChangeStatus::UnsafeCounterModified,
+                    unsafe_delta: UnsafeDelta {
+                        expressions: 3,
+                        functions: -1,
+                        ..Default::default()
+                    },
+                    unsafe_status: None,
+                    unsafe_item_changes: Vec::new(),
+                },
+                // removed unsafe code does not offset the added one
+                FileUnsafeChangeStats {
+                    file: "src/io.rs".to_string(),
+                    change_type: Delta::Modified,
+                    unsafe_change_status: FileUnsafeCodeChangeStatus::UnsafeCounterModified,
+                    unsafe_delta: UnsafeDelta {
+                        expressions: -10,
+                        ..Default::default()
+                    },
+                    unsafe_status: None,
+                    unsafe_item_changes: Vec::new(),
+                },
+            ],
+            native_artifacts: NativeArtifacts::default(),
+        };
+
+        // libgit2-sys gets added with a build script and unsafe code
+        let added_version = get_test_version_info("libgit2-sys", "0.12.21+1.1.0", 5_000_000);
+        let unsafe_stats = CrateUnsafeStats {
+            rust_files: 2,
+            files_with_unsafe: vec!["src/lib.rs".to_string()].into_iter().collect(),
+            unsafe_details: UnsafeDetails {
+                functions: 5,
+                ..Default::default()
+            },
+        };
+
+        UpdateReviewReport {
+            dep_update_review_reports: vec![DepUpdateReviewReport {
+                name: "tokio".to_string(),
+                prior_version,
+                updated_version,
+                diff_stats: Some(diff_stats),
+                build_scan_report: None,
+                transitive_churn: None,
+                semver_check_report: None,
+                ownership_report: None,
+                configurations: None,
+                dependency_paths: None,
+            }],
+            dep_downgrade_review_reports: Vec::new(),
+            dep_source_change_review_reports: Vec::new(),
+            dep_addition_review_reports: vec![DepAdditionReviewReport {
+                name: "libgit2-sys".to_string(),
+                added_version,
+                build_script_paths: vec!["build.rs".to_string()].into_iter().collect(),
+                unsafe_stats: Some(unsafe_stats),
+                configurations: None,
+            }],
+            dep_removal_reports: Vec::new(),
+            version_conflicts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_policy_from_file() {
+        let policy =
+            Policy::from_file(&PathBuf::from("resources/test/policy/depdive.toml")).unwrap();
+        assert_eq!(policy.advisories.introduced, RuleLevel::Deny);
+        assert_eq!(policy.build_script.changed, RuleLevel::Deny);
+        assert!(policy.build_script.allowlist.contains("libgit2-sys"));
+        assert_eq!(policy.crate_source.differs_from_git, RuleLevel::Warn);
+        assert_eq!(policy.unsafe_code.increase, RuleLevel::Deny);
+        assert_eq!(policy.unsafe_code.max_increase, 0);
+        assert_eq!(policy.downloads.below_minimum, RuleLevel::Deny);
+        assert_eq!(policy.downloads.min_downloads, 10000);
+        assert_eq!(policy.release_age.too_new, RuleLevel::Warn);
+        assert_eq!(policy.release_age.min_days, 7);
+
+        assert!(Policy::from_toml_str("[advisories]\nintroduced = \"block\"").is_err());
+        assert!(Policy::from_toml_str("[advisory]\nintroduced = \"deny\"").is_err());
+    }
+
+    #[test]
+    fn test_policy_evaluate() {
+        let policy =
+            Policy::from_file(&PathBuf::from("resources/test/policy/depdive.toml")).unwrap();
+        let policy_report = policy.evaluate(&get_test_update_review_report());
+        assert!(policy_report.has_denials());
+
+        let violations: Vec<(&str, PolicyRule, RuleLevel)> = policy_report
+            .violations
+            .iter()
+            .map(|v| (v.name.as_str(), v.rule, v.level))
+            .collect();
+        // libgit2-sys build script is allowlisted
+        assert_eq!(
+            violations,
+            vec![
+                ("libgit2-sys", PolicyRule::UnsafeIncrease, RuleLevel::Deny),
+                ("tokio", PolicyRule::IntroducedAdvisory, RuleLevel::Deny),
+                ("tokio", PolicyRule::BuildScriptChanged, RuleLevel::Deny),
+                ("tokio", PolicyRule::CrateSourceDiffers, RuleLevel::Warn),
+                ("tokio", PolicyRule::UnsafeIncrease, RuleLevel::Deny),
+                ("tokio", PolicyRule::LowDownloads, RuleLevel::Deny),
+                ("tokio", PolicyRule::TooNew, RuleLevel::Warn),
+            ]
+        );
+        assert_eq!(
+            policy_report.violations[1].to_string(),
+            "deny: [advisories.introduced] tokio 1.7.1: introduces known advisories RUSTSEC-2021-0072"
+        );
+        assert_eq!(
+            policy_report.violations[4].message,
+            "adds 3 unsafe counter(s), more than the allowed 0, and removes 11"
+        );
+    }
+
+    #[test]
+    fn test_policy_default_allows_all() {
+        let policy = Policy::from_toml_str("").unwrap();
+        let policy_report = policy.evaluate(&get_test_update_review_report());
+        assert!(policy_report.violations.is_empty());
+        assert!(!policy_report.has_denials());
+
+        // Only warnings do not fail the policy
+        let policy = Policy::from_toml_str("[crate_source]\ndiffers_from_git = \"warn\"").unwrap();
+        let policy_report = policy.evaluate(&get_test_update_review_report());
+        assert_eq!(policy_report.violations.len(), 1);
+        assert!(!policy_report.has_denials());
+    }
+
+    #[test]
+    fn test_policy_crate_source_severity() {
+        let policy = Policy::from_toml_str("[crate_source]\ndiffers_from_git = \"deny\"").unwrap();
+        let mut update_review_report = get_test_update_review_report();
+        let crate_source_diff_report = update_review_report.dep_update_review_reports[0]
+            .updated_version
+            .crate_source_diff_report
+            .as_mut()
+            .unwrap();
+
+        // A README stub in crates.io is not a violation
+        crate_source_diff_report.file_diff_stats = Some(FileDiffStats {
+            classified_files: vec![ClassifiedFile {
+                path: "README.md".to_string(),
+                kind: SourceFileKind::Docs,
+                severity: DiffSeverity::Low,
+            }],
+            ..Default::default()
+        });
+        let policy_report = policy.evaluate(&update_review_report);
+        assert!(policy_report.violations.is_empty());
+    }
+}
@@ -0,0 +1,519 @@
+//! This module resolves where crates are downloaded from
+//! and where their stats are looked up
+//! for crates.io, alternative registries,
+//! and mirrors set up through Cargo source replacement, e.g.,
+//!
+//! ```toml
+//! [source.crates-io]
+//! replace-with = "mirror"
+//!
+//! [source.mirror]
+//! registry = "sparse+https://mirror.example.com/index/"
+//! ```
+//!
+//! Cargo config files are read from `DEPDIVE_CARGO_CONFIG`,
+//! `.cargo/config.toml` in the workspace and its ancestors,
+//! and `$CARGO_HOME/config.toml`, in order of precedence.
+
+use anyhow::{anyhow, Result};
+use git2::Repository;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tempfile::tempdir;
+
+pub const CARGO_CONFIG_ENV: &str = "DEPDIVE_CARGO_CONFIG";
+
+pub const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
+const CRATES_IO_SPARSE_INDEX: &str = "sparse+https://index.crates.io/";
+const CRATES_IO_DL: &str = "https://crates.io/api/v1/crates";
+const CRATES_IO_API: &str = "https://crates.io";
+const CRATES_IO_SOURCE: &str = "crates-io";
+
+// Guards against a cycle in source replacement
+const MAX_REPLACEMENTS: usize = 16;
+
+/// A registry to download crates from
+/// per the registry's config.json
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registry {
+    pub index: String,
+    pub dl: String, // download url, optionally a template with {crate}, {version} etc.
+    pub api: Option<String>, // None if the registry offers no web API
+}
+
+#[derive(Deserialize)]
+struct RegistryConfig {
+    dl: String,
+    api: Option<String>,
+}
+
+impl Registry {
+    pub fn crates_io() -> Self {
+        Self {
+            index: CRATES_IO_INDEX.to_string(),
+            dl: CRATES_IO_DL.to_string(),
+            api: Some(CRATES_IO_API.to_string()),
+        }
+    }
+
+    pub fn is_crates_io(&self) -> bool {
+        is_crates_io_index(&self.index)
+    }
+
+    /// Resolves the download url for a crate version,
+    /// see https://doc.rust-lang.org/cargo/reference/registries.html#index-format
+    pub fn get_download_url(&self, name: &str, version: &str) -> Result<String> {
+        let markers = [
+            "{crate}",
+            "{version}",
+            "{prefix}",
+            "{lowerprefix}",
+            "{sha256-checksum}",
+        ];
+        if !markers.iter().any(|marker| self.dl.contains(marker)) {
+            return Ok(format!(
+                "{}/{}/{}/download",
+                self.dl.trim_end_matches('/'),
+                name,
+                version
+            ));
+        }
+        if self.dl.contains("{sha256-checksum}") {
+            return Err(anyhow!(
+                "checksum in download url is not supported for {}",
+                self.index
+            ));
+        }
+
+        let prefix = get_index_prefix(name);
+        Ok(self
+            .dl
+            .replace("{crate}", name)
+            .replace("{version}", version)
+            .replace("{prefix}", &prefix)
+            .replace("{lowerprefix}", &prefix.to_lowercase()))
+    }
+
+    /// Whether the registry serves a crates.io compatible web API
+    pub fn has_api(&self) -> bool {
+        self.api.is_some()
+    }
+
+    /// Base url for the crates.io compatible web API
+    pub fn get_api_url(&self) -> Result<&str> {
+        self.api
+            .as_deref()
+            .map(|api| api.trim_end_matches('/'))
+            .ok_or_else(|| anyhow!("registry {} has no web API", self.index))
+    }
+}
+
+fn is_crates_io_index(index: &str) -> bool {
+    let index = normalize_index(index);
+    index == normalize_index(CRATES_IO_INDEX) || index == normalize_index(CRATES_IO_SPARSE_INDEX)
+}
+
+// The same index may be referred to with or without a trailing slash or .git
+fn normalize_index(index: &str) -> &str {
+    index.trim_end_matches('/').trim_end_matches(".git")
+}
+
+// Directory of a crate in the registry index, e.g., "se/rd" for serde
+fn get_index_prefix(name: &str) -> String {
+    match name.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", &name[..1]),
+        _ => format!("{}/{}", &name[..2], &name[2..4]),
+    }
+}
+
+/// Registries and source replacements read from Cargo config files
+#[derive(Debug, Default, Clone)]
+pub struct CargoConfig {
+    sources: HashMap<String, String>,      // source name to index url
+    registries: HashMap<String, String>,   // registry name to index url, per [registries]
+    replace_with: HashMap<String, String>, // source name to the replacing source name
+}
+
+impl CargoConfig {
+    /// Reads the Cargo config files that apply to a workspace
+    pub fn discover(workspace_root: &Path) -> Result<Self> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        if let Some(path) = env::var_os(CARGO_CONFIG_ENV).filter(|path| !path.is_empty()) {
+            paths.push(PathBuf::from(path));
+        }
+        for dir in workspace_root.ancestors() {
+            paths.push(dir.join(".cargo").join("config.toml"));
+            paths.push(dir.join(".cargo").join("config"));
+        }
+        let cargo_home = env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")));
+        if let Some(cargo_home) = cargo_home {
+            paths.push(cargo_home.join("config.toml"));
+            paths.push(cargo_home.join("config"));
+        }
+
+        let paths: Vec<PathBuf> = paths.into_iter().filter(|path| path.is_file()).collect();
+        Self::from_paths(&paths)
+    }
+
+    /// Earlier paths take precedence over the later ones
+    pub fn from_paths(paths: &[PathBuf]) -> Result<Self> {
+        let mut config = Self::default();
+        for path in paths {
+            config.merge_toml_str(&read_to_string(path)?)?;
+        }
+        Ok(config)
+    }
+
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        let mut config = Self::default();
+        config.merge_toml_str(toml)?;
+        Ok(config)
+    }
+
+    // Keys already set take precedence
+    fn merge_toml_str(&mut self, toml: &str) -> Result<()> {
+        let value: toml::Value = toml::from_str(toml)?;
+
+        if let Some(registries) = value.get("registries").and_then(|r| r.as_table()) {
+            for (name, registry) in registries {
+                if let Some(index) = registry.get("index").and_then(|i| i.as_str()) {
+                    self.registries
+                        .entry(name.clone())
+                        .or_insert_with(|| index.to_string());
+                    self.sources
+                        .entry(name.clone())
+                        .or_insert_with(|| index.to_string());
+                }
+            }
+        }
+
+        if let Some(sources) = value.get("source").and_then(|s| s.as_table()) {
+            for (name, source) in sources {
+                if let Some(index) = source.get("registry").and_then(|r| r.as_str()) {
+                    self.sources
+                        .entry(name.clone())
+                        .or_insert_with(|| index.to_string());
+                }
+                if let Some(replacement) = source.get("replace-with").and_then(|r| r.as_str()) {
+                    self.replace_with
+                        .entry(name.clone())
+                        .or_insert_with(|| replacement.to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Name of a registry in [registries] for the given index,
+    /// as used for `registry = "<name>"` in a Cargo.toml dependency
+    pub fn get_registry_name(&self, index: &str) -> Option<&str> {
+        self.registries
+            .iter()
+            .find(|(_, registry_index)| normalize_index(registry_index) == normalize_index(index))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Applies source replacement to a registry index,
+    /// returns the index itself if it is not replaced
+    pub fn get_replaced_index(&self, index: &str) -> Result<String> {
+        let mut source = match self.get_source_name(index) {
+            Some(source) => source,
+            None => return Ok(index.to_string()),
+        };
+
+        for _ in 0..MAX_REPLACEMENTS {
+            let replacement = match self.replace_with.get(source) {
+                Some(replacement) => replacement,
+                None => break,
+            };
+            if !self.sources.contains_key(replacement) && replacement != CRATES_IO_SOURCE {
+                // e.g., a directory or local-registry source
+                // that cannot be downloaded from, use the original registry
+                return Ok(index.to_string());
+            }
+            source = replacement;
+        }
+        if self.replace_with.contains_key(source) {
+            return Err(anyhow!("cycle in source replacement for {}", index));
+        }
+
+        match self.sources.get(source) {
+            Some(index) => Ok(index.clone()),
+            None => Ok(CRATES_IO_INDEX.to_string()),
+        }
+    }
+
+    fn get_source_name(&self, index: &str) -> Option<&str> {
+        if is_crates_io_index(index) {
+            return Some(CRATES_IO_SOURCE);
+        }
+        self.sources
+            .iter()
+            .find(|(_, source_index)| normalize_index(source_index) == normalize_index(index))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Resolves registry indexes, as present in Cargo.lock,
+/// to the registry to download crates from
+pub struct RegistryResolver {
+    config: CargoConfig,
+    client: Client,
+    // resolved registries keyed by the index in Cargo.lock
+    registries: Mutex<HashMap<String, Registry>>,
+}
+
+impl RegistryResolver {
+    pub fn new(config: CargoConfig) -> Result<Self> {
+        Ok(Self {
+            config,
+            client: Client::builder().user_agent("diem/whackadep").build()?,
+            registries: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn config(&self) -> &CargoConfig {
+        &self.config
+    }
+
+    pub fn resolve(&self, index: &str) -> Result<Registry> {
+        if let Some(registry) = self.registries.lock().unwrap().get(index) {
+            return Ok(registry.clone());
+        }
+
+        let replaced_index = self.config.get_replaced_index(index)?;
+        let registry = if is_crates_io_index(&replaced_index) {
+            Registry::crates_io()
+        } else {
+            let config = self.get_registry_config(&replaced_index)?;
+            Registry {
+                index: replaced_index,
+                dl: config.dl,
+                // A crates.io mirror may only serve downloads,
+                // crates.io still has the stats then
+                api: config
+                    .api
+                    .or_else(|| is_crates_io_index(index).then(|| CRATES_IO_API.to_string())),
+            }
+        };
+
+        self.registries
+            .lock()
+            .unwrap()
+            .insert(index.to_string(), registry.clone());
+        Ok(registry)
+    }
+
+    fn get_registry_config(&self, index: &str) -> Result<RegistryConfig> {
+        let config = match index.strip_prefix("sparse+") {
+            Some(url) => self
+                .client
+                .get(format!("{}/config.json", url.trim_end_matches('/')))
+                .send()?
+                .error_for_status()?
+                .text()?,
+            None => {
+                // config.json is at the root of a git index
+                let dir = tempdir()?;
+                let index = index.strip_prefix("registry+").unwrap_or(index);
+                Repository::clone(index, dir.path())?;
+                read_to_string(dir.path().join("config.json"))?
+            }
+        };
+        serde_json::from_str(&config)
+            .map_err(|error| anyhow!("invalid config.json for registry {}: {}", index, error))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves the given paths over http on a local port,
+    /// a stand-in for a registry, returns the base url
+    pub(crate) fn serve_http(routes: HashMap<String, Vec<u8>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+                let (status, body) = match routes.get(path) {
+                    Some(body) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", Vec::new()),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_registry_download_url() {
+        let registry = Registry::crates_io();
+        assert_eq!(
+            registry.get_download_url("libc", "0.2.93").unwrap(),
+            "https://crates.io/api/v1/crates/libc/0.2.93/download"
+        );
+
+        let registry = Registry {
+            index: "sparse+https://registry.example.com/index/".to_string(),
+            dl: "https://registry.example.com/dl/{prefix}/{lowerprefix}/{crate}-{version}.crate"
+                .to_string(),
+            api: None,
+        };
+        assert!(!registry.is_crates_io());
+        assert_eq!(
+            registry.get_download_url("Serde", "1.0.0").unwrap(),
+            "https://registry.example.com/dl/Se/rd/se/rd/Serde-1.0.0.crate"
+        );
+        assert_eq!(
+            registry.get_download_url("syn", "1.0.0").unwrap(),
+            "https://registry.example.com/dl/3/s/3/s/syn-1.0.0.crate"
+        );
+        assert!(!registry.has_api());
+        assert!(registry.get_api_url().is_err());
+    }
+
+    #[test]
+    fn test_registry_source_replacement() {
+        let config = CargoConfig::from_toml_str(
+            r#"
+            [registries.internal]
+            index = "https://git.example.com/index.git"
+
+            [source.crates-io]
+            replace-with = "mirror"
+
+            [source.mirror]
+            registry = "sparse+https://mirror.example.com/index/"
+
+            [source.vendored]
+            directory = "vendor"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.get_replaced_index(CRATES_IO_INDEX).unwrap(),
+            "sparse+https://mirror.example.com/index/"
+        );
+        assert_eq!(
+            config
+                .get_replaced_index("https://git.example.com/index")
+                .unwrap(),
+            "https://git.example.com/index.git"
+        );
+        assert_eq!(
+            config.get_registry_name("https://git.example.com/index.git/"),
+            Some("internal")
+        );
+        assert_eq!(config.get_registry_name(CRATES_IO_INDEX), None);
+
+        // A source that cannot be downloaded from is not followed
+        let config = CargoConfig::from_toml_str(
+            r#"
+            [source.crates-io]
+            replace-with = "vendored"
+
+            [source.vendored]
+            directory = "vendor"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.get_replaced_index(CRATES_IO_INDEX).unwrap(),
+            CRATES_IO_INDEX
+        );
+
+        let config = CargoConfig::from_toml_str(
+            r#"
+            [source.a]
+            registry = "https://a.example.com/index"
+            replace-with = "b"
+
+            [source.b]
+            registry = "https://b.example.com/index"
+            replace-with = "a"
+            "#,
+        )
+        .unwrap();
+        assert!(config
+            .get_replaced_index("https://a.example.com/index")
+            .is_err());
+    }
+
+    #[test]
+    fn test_registry_resolve_sparse_mirror() {
+        let mut routes: HashMap<String, Vec<u8>> = HashMap::new();
+        routes.insert(
+            "/index/config.json".to_string(),
+            br#"{"dl": "https://mirror.example.com/dl"}"#.to_vec(),
+        );
+        let url = serve_http(routes);
+
+        let config = CargoConfig::from_toml_str(&format!(
+            r#"
+            [source.crates-io]
+            replace-with = "mirror"
+
+            [source.mirror]
+            registry = "sparse+{}/index/"
+            "#,
+            url
+        ))
+        .unwrap();
+        let resolver = RegistryResolver::new(config).unwrap();
+        let registry = resolver.resolve(CRATES_IO_INDEX).unwrap();
+        assert_eq!(registry.index, format!("sparse+{}/index/", url));
+        assert_eq!(registry.dl, "https://mirror.example.com/dl");
+        // crates.io stats are used for a mirror without web API
+        assert!(registry.has_api());
+        assert_eq!(registry.get_api_url().unwrap(), CRATES_IO_API);
+
+        // Unreplaced registries are resolved as they are
+        assert_eq!(
+            resolver
+                .resolve(&format!("sparse+{}/index/", url))
+                .unwrap()
+                .api,
+            None
+        );
+        assert!(resolver
+            .resolve(&format!("sparse+{}/missing/", url))
+            .is_err());
+    }
+}
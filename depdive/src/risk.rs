@@ -0,0 +1,494 @@
+//! This module abstracts a scoring model
+//! to rank dependencies by their risk from the dependency monitoring metrics.
+//!
+//! Each factor scores a risk from 0 to 1 that is scaled by its weight,
+//! and the score of a crate is the sum of the weighted factors.
+//! The default model scores from 0 to 100 and can be tuned with a toml file:
+//! ```toml
+//! # share of the weight counted for a factor whose metric is unavailable
+//! unknown = 0.5
+//!
+//! [weights]
+//! downloads = 10
+//! dependents = 5
+//! inactivity = 10
+//! committers = 5
+//! unsafe_code = 15
+//! build_script = 5
+//! exclusive_deps = 10
+//! advisories = 40
+//!
+//! [targets]
+//! downloads = 1000000 # no risk from this many downloads on
+//! dependents = 100    # no risk from this many dependents on
+//! inactivity = 365    # full risk from this many days since the last commit on
+//! committers = 3      # no risk from this many recent committers on
+//! unsafe_code = 1000  # full risk from this many used unsafe items on
+//! exclusive_deps = 20 # full risk from this many exclusive dependencies on
+//! advisories = 1      # full risk from this many advisories on
+//! ```
+//! A weight not present in the file keeps its default.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt, fs, path::Path};
+
+use crate::code::CodeReport;
+use crate::cratesio::CratesioReport;
+use crate::github::GitHubReport;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskModel {
+    pub unknown: f64,
+    pub weights: RiskWeights,
+    pub targets: RiskTargets,
+}
+
+impl Default for RiskModel {
+    fn default() -> Self {
+        Self {
+            unknown: 0.5,
+            weights: RiskWeights::default(),
+            targets: RiskTargets::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskWeights {
+    pub downloads: f64,
+    pub dependents: f64,
+    pub inactivity: f64,
+    pub committers: f64,
+    pub unsafe_code: f64,
+    pub build_script: f64,
+    pub exclusive_deps: f64,
+    pub advisories: f64,
+}
+
+impl Default for RiskWeights {
+    fn default() -> Self {
+        Self {
+            downloads: 10.0,
+            dependents: 5.0,
+            inactivity: 10.0,
+            committers: 5.0,
+            unsafe_code: 15.0,
+            build_script: 5.0,
+            exclusive_deps: 10.0,
+            advisories: 40.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskTargets {
+    pub downloads: f64,
+    pub dependents: f64,
+    pub inactivity: f64, // days since the last commit on the default branch
+    pub committers: f64, // committers in the recent activity window
+    pub unsafe_code: f64,
+    pub exclusive_deps: f64,
+    pub advisories: f64,
+}
+
+impl Default for RiskTargets {
+    fn default() -> Self {
+        Self {
+            downloads: 1_000_000.0,
+            dependents: 100.0,
+            inactivity: 365.0,
+            committers: 3.0,
+            unsafe_code: 1000.0,
+            exclusive_deps: 20.0,
+            advisories: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RiskFactor {
+    Downloads,
+    Dependents,
+    Inactivity,
+    Committers,
+    UnsafeCode,
+    BuildScript,
+    ExclusiveDeps,
+    Advisories,
+}
+
+impl fmt::Display for RiskFactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RiskFactor::Downloads => "downloads",
+            RiskFactor::Dependents => "dependents",
+            RiskFactor::Inactivity => "inactivity",
+            RiskFactor::Committers => "committers",
+            RiskFactor::UnsafeCode => "unsafe_code",
+            RiskFactor::BuildScript => "build_script",
+            RiskFactor::ExclusiveDeps => "exclusive_deps",
+            RiskFactor::Advisories => "advisories",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The metrics of a crate version the risk is scored from,
+/// None for the metrics that could not be collected
+#[derive(Default, Clone)]
+pub struct RiskInput<'a> {
+    pub name: String,
+    pub version: String,
+    pub cratesio_metrics: Option<&'a CratesioReport>,
+    pub github_metrics: Option<&'a GitHubReport>,
+    pub code_report: Option<&'a CodeReport>,
+    pub advisories: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RiskContribution {
+    pub factor: RiskFactor,
+    pub value: Option<f64>, // the metric, None when unavailable
+    pub risk: f64,          // from 0 to 1
+    pub contribution: f64,  // the risk scaled by the weight of the factor
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RiskScore {
+    pub name: String,
+    pub version: String,
+    pub score: f64,
+    // ordered by contribution, highest first
+    pub contributions: Vec<RiskContribution>,
+}
+
+impl RiskModel {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read risk model file {}: {}", path.display(), e))?;
+        Self::from_toml_str(&content)
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self> {
+        let model: Self = toml::from_str(content)?;
+        if !(0.0..=1.0).contains(&model.unknown) {
+            return Err(anyhow!(
+                "unknown must be between 0 and 1, found {}",
+                model.unknown
+            ));
+        }
+        // A negative weight or target would invert the ranking of its factor
+        let (weights, targets) = (&model.weights, &model.targets);
+        let values = [
+            ("weights.downloads", weights.downloads),
+            ("weights.dependents", weights.dependents),
+            ("weights.inactivity", weights.inactivity),
+            ("weights.committers", weights.committers),
+            ("weights.unsafe_code", weights.unsafe_code),
+            ("weights.build_script", weights.build_script),
+            ("weights.exclusive_deps", weights.exclusive_deps),
+            ("weights.advisories", weights.advisories),
+            ("targets.downloads", targets.downloads),
+            ("targets.dependents", targets.dependents),
+            ("targets.inactivity", targets.inactivity),
+            ("targets.committers", targets.committers),
+            ("targets.unsafe_code", targets.unsafe_code),
+            ("targets.exclusive_deps", targets.exclusive_deps),
+            ("targets.advisories", targets.advisories),
+        ];
+        if let Some((name, value)) = values.iter().find(|(_, value)| *value < 0.0) {
+            return Err(anyhow!("{} must not be negative, found {}", name, value));
+        }
+        Ok(model)
+    }
+
+    /// Scores the crates and returns them ranked by score, highest first
+    pub fn rank(&self, inputs: &[RiskInput]) -> Vec<RiskScore> {
+        let mut scores: Vec<RiskScore> = inputs.iter().map(|input| self.score(input)).collect();
+        scores.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| (&a.name, &a.version).cmp(&(&b.name, &b.version)))
+        });
+        scores
+    }
+
+    pub fn score(&self, input: &RiskInput) -> RiskScore {
+        let (weights, targets) = (&self.weights, &self.targets);
+        let cratesio = input
+            .cratesio_metrics
+            .filter(|cratesio_metrics| cratesio_metrics.is_hosted);
+        let github = input
+            .github_metrics
+            .filter(|github_metrics| github_metrics.is_github_repo);
+        let activity = github.map(|github_metrics| &github_metrics.activity_metrics);
+        let unsafe_used = input
+            .code_report
+            .and_then(|code_report| code_report.unsafe_report.as_ref())
+            .map(|unsafe_report| {
+                if unsafe_report.forbids_unsafe {
+                    return 0;
+                }
+                let used = &unsafe_report.used_unsafe_count;
+                used.functions + used.expressions + used.impls + used.traits + used.methods
+            });
+
+        let mut contributions = vec![
+            self.get_contribution(
+                RiskFactor::Downloads,
+                weights.downloads,
+                cratesio.map(|c| c.downloads as f64),
+                |downloads| 1.0 - Self::log_scale(downloads, targets.downloads),
+            ),
+            self.get_contribution(
+                RiskFactor::Dependents,
+                weights.dependents,
+                cratesio.map(|c| c.dependents as f64),
+                |dependents| 1.0 - Self::log_scale(dependents, targets.dependents),
+            ),
+            self.get_contribution(
+                RiskFactor::Inactivity,
+                weights.inactivity,
+                activity.map(|a| a.days_since_last_commit as f64),
+                |days| Self::linear_scale(days, targets.inactivity),
+            ),
+            self.get_contribution(
+                RiskFactor::Committers,
+                weights.committers,
+                activity.map(|a| a.recent_activity.committers as f64),
+                |committers| 1.0 - Self::linear_scale(committers, targets.committers),
+            ),
+            self.get_contribution(
+                RiskFactor::UnsafeCode,
+                weights.unsafe_code,
+                unsafe_used.map(|used| used as f64),
+                |used| Self::log_scale(used, targets.unsafe_code),
+            ),
+            self.get_contribution(
+                RiskFactor::BuildScript,
+                weights.build_script,
+                input.code_report.map(|code_report| {
+                    if code_report.has_build_script {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }),
+                |has_build_script| has_build_script,
+            ),
+            self.get_contribution(
+                RiskFactor::ExclusiveDeps,
+                weights.exclusive_deps,
+                input
+                    .code_report
+                    .and_then(|code_report| code_report.exclusive_dep_report.as_ref())
+                    .map(|dep_report| dep_report.total_deps as f64),
+                |deps| Self::linear_scale(deps, targets.exclusive_deps),
+            ),
+            self.get_contribution(
+                RiskFactor::Advisories,
+                weights.advisories,
+                input.advisories.map(|advisories| advisories as f64),
+                |advisories| Self::linear_scale(advisories, targets.advisories),
+            ),
+        ];
+        contributions.sort_by(|a, b| {
+            b.contribution
+                .partial_cmp(&a.contribution)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.factor.cmp(&b.factor))
+        });
+
+        RiskScore {
+            name: input.name.clone(),
+            version: input.version.clone(),
+            score: contributions.iter().map(|c| c.contribution).sum(),
+            contributions,
+        }
+    }
+
+    fn get_contribution(
+        &self,
+        factor: RiskFactor,
+        weight: f64,
+        value: Option<f64>,
+        get_risk: impl Fn(f64) -> f64,
+    ) -> RiskContribution {
+        let risk = value.map_or(self.unknown, get_risk);
+        RiskContribution {
+            factor,
+            value,
+            risk,
+            contribution: risk * weight,
+        }
+    }
+
+    // 0 at no value, growing linearly to 1 at the target
+    fn linear_scale(value: f64, target: f64) -> f64 {
+        if target <= 0.0 {
+            return if value > 0.0 { 1.0 } else { 0.0 };
+        }
+        (value / target).min(1.0)
+    }
+
+    // 0 at no value, growing logarithmically to 1 at the target,
+    // for metrics spanning orders of magnitude
+    fn log_scale(value: f64, target: f64) -> f64 {
+        if target <= 0.0 {
+            return if value > 0.0 { 1.0 } else { 0.0 };
+        }
+        ((1.0 + value).ln() / (1.0 + target).ln()).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::code::{DepReport, UnsafeDetails, UnsafeReport};
+    use crate::guppy_wrapper::DependencyKind;
+
+    fn get_test_cratesio_report(downloads: u64, dependents: u64) -> CratesioReport {
+        CratesioReport {
+            name: "test".to_string(),
+            is_hosted: true,
+            downloads,
+            dependents,
+        }
+    }
+
+    fn get_test_code_report(unsafe_expressions: u64, exclusive_deps: u64) -> CodeReport {
+        CodeReport {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            source: "crates.io".to_string(),
+            is_direct: true,
+            kind: DependencyKind::Normal,
+            has_build_script: true,
+            loc_report: None,
+            unsafe_report: Some(UnsafeReport {
+                forbids_unsafe: false,
+                used_unsafe_count: UnsafeDetails {
+                    expressions: unsafe_expressions,
+                    ..Default::default()
+                },
+                unused_unsafe_count: UnsafeDetails::default(),
+            }),
+            dep_report: None,
+            exclusive_dep_report: Some(DepReport {
+                total_deps: exclusive_deps,
+                ..Default::default()
+            }),
+            dependency_paths: None,
+        }
+    }
+
+    fn get_contribution(score: &RiskScore, factor: RiskFactor) -> &RiskContribution {
+        score
+            .contributions
+            .iter()
+            .find(|c| c.factor == factor)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_risk_score() {
+        let model = RiskModel::default();
+        let popular = get_test_cratesio_report(1_000_000, 100);
+        let obscure = get_test_cratesio_report(99, 0);
+        let safe_code = get_test_code_report(0, 0);
+        let unsafe_code = get_test_code_report(1000, 40);
+
+        let inputs = vec![
+            RiskInput {
+                name: "popular".to_string(),
+                version: "1.0.0".to_string(),
+                cratesio_metrics: Some(&popular),
+                code_report: Some(&safe_code),
+                advisories: Some(0),
+                ..Default::default()
+            },
+            RiskInput {
+                name: "obscure".to_string(),
+                version: "0.1.0".to_string(),
+                cratesio_metrics: Some(&obscure),
+                code_report: Some(&unsafe_code),
+                advisories: Some(2),
+                ..Default::default()
+            },
+            RiskInput {
+                name: "unknown".to_string(),
+                version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        ];
+        let scores = model.rank(&inputs);
+        let names: Vec<&str> = scores.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["obscure", "unknown", "popular"]);
+
+        // Without github metrics, the activity factors count as unknown
+        let popular = &scores[2];
+        assert_eq!(get_contribution(popular, RiskFactor::Downloads).risk, 0.0);
+        assert_eq!(get_contribution(popular, RiskFactor::Dependents).risk, 0.0);
+        assert_eq!(get_contribution(popular, RiskFactor::UnsafeCode).risk, 0.0);
+        assert_eq!(get_contribution(popular, RiskFactor::BuildScript).risk, 1.0);
+        assert_eq!(
+            get_contribution(popular, RiskFactor::Inactivity).value,
+            None
+        );
+        assert_eq!(get_contribution(popular, RiskFactor::Inactivity).risk, 0.5);
+        assert_eq!(popular.score, 5.0 + 5.0 + 2.5);
+
+        let obscure = &scores[0];
+        assert_eq!(obscure.contributions[0].factor, RiskFactor::Advisories);
+        assert_eq!(obscure.contributions[0].contribution, 40.0);
+        assert_eq!(
+            get_contribution(obscure, RiskFactor::ExclusiveDeps).risk,
+            1.0
+        );
+        let downloads = get_contribution(obscure, RiskFactor::Downloads);
+        assert_eq!(downloads.value, Some(99.0));
+        assert!(downloads.risk > 0.5 && downloads.risk < 1.0);
+        let total: f64 = obscure.contributions.iter().map(|c| c.contribution).sum();
+        assert_eq!(obscure.score, total);
+
+        // Everything unknown scores half of the total weight
+        assert_eq!(scores[1].score, 50.0);
+    }
+
+    #[test]
+    fn test_risk_model_from_toml() {
+        let model = RiskModel::from_toml_str(
+            r#"
+            unknown = 0.0
+
+            [weights]
+            advisories = 100
+
+            [targets]
+            downloads = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(model.weights.advisories, 100.0);
+        assert_eq!(model.weights.downloads, 10.0);
+        assert_eq!(model.targets.downloads, 10.0);
+        assert_eq!(model.targets.dependents, 100.0);
+
+        let score = model.score(&RiskInput {
+            name: "unknown".to_string(),
+            version: "0.1.0".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(score.score, 0.0);
+
+        assert!(RiskModel::from_toml_str("unknown = 2.0").is_err());
+        assert!(RiskModel::from_toml_str("[weights]\nadvisories = -40.0").is_err());
+        assert!(RiskModel::from_toml_str("[targets]\ndownloads = -1.0").is_err());
+        assert!(RiskModel::from_toml_str("[weights]\nstars = 1").is_err());
+    }
+}
@@ -0,0 +1,985 @@
+//! This module compares the public API of two versions of a crate
+//! to flag breaking changes shipped under a semver compatible version bump.
+//! The API is read from the library source with syn:
+//! pub items reachable through pub modules or re-exports, their signatures, and trait impls
+
+use crate::crate_layout::{get_module_dir, get_module_file, get_path_attr, join_path};
+use quote::ToTokens;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Bound;
+
+// Re-exports of re-exports are resolved up to this depth
+const MAX_REEXPORT_DEPTH: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiItemKind {
+    Module,
+    Function,
+    Struct,
+    Field,
+    Enum,
+    Variant,
+    Trait,
+    TraitItem,
+    TraitImpl,
+    Const,
+    Static,
+    TypeAlias,
+    Reexport,
+    Macro,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiItem {
+    pub kind: ApiItemKind,
+    pub signature: String,
+    // e.g., a variant of an exhaustive enum, or a trait method without a default
+    pub breaking_if_added: bool,
+}
+
+/// Public items of a crate keyed by their path, e.g., `de::Error::custom`,
+/// trait impls are keyed by `impl Trait for Type`
+pub type PublicApi = BTreeMap<String, ApiItem>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiChangeType {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiChange {
+    pub path: String,
+    pub kind: ApiItemKind,
+    pub change_type: ApiChangeType,
+    pub breaking: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SemverCheckReport {
+    pub breaking_change_allowed: bool, // false when the version bump is semver compatible
+    pub changes: Vec<ApiChange>,
+}
+
+impl SemverCheckReport {
+    /// Breaking changes shipped under a semver compatible version bump
+    pub fn get_violations(&self) -> Vec<&ApiChange> {
+        if self.breaking_change_allowed {
+            return Vec::new();
+        }
+        self.changes
+            .iter()
+            .filter(|change| change.breaking)
+            .collect()
+    }
+}
+
+/// Versions are semver compatible with the same left-most non-zero component
+pub fn get_semver_compatibility(version: &Version) -> (u64, u64, u64) {
+    match (version.major, version.minor) {
+        (0, 0) => (0, 0, version.patch),
+        (0, minor) => (0, minor, 0),
+        (major, _) => (major, 0, 0),
+    }
+}
+
+/// Reads the public API of a library and the modules it declares,
+/// read_file returns the content of a file relative to the crate directory
+pub fn get_public_api(lib_path: &str, read_file: impl Fn(&str) -> Option<String>) -> PublicApi {
+    let mut reader = ApiReader {
+        api: PublicApi::new(),
+        modules: HashSet::new(),
+        private_modules: HashSet::new(),
+        reexports: Vec::new(),
+        inherent_impls: Vec::new(),
+        visited: HashSet::new(),
+        read_file,
+    };
+    let lib_path = join_path("", lib_path);
+    reader.modules.insert(String::new());
+    reader.add_file(&lib_path, &get_module_dir(&lib_path, true), "");
+    reader.resolve_inherent_impls();
+    reader.resolve_reexports();
+
+    // Trait impls apply wherever they are declared
+    let private_modules = &reader.private_modules;
+    reader
+        .api
+        .into_iter()
+        .filter(|(path, item)| {
+            item.kind == ApiItemKind::TraitImpl || is_visible(path, "", private_modules)
+        })
+        .collect()
+}
+
+/// Compares the public API of two versions of a crate
+pub fn get_semver_check_report(
+    (old_version, old_api): (&Version, &PublicApi),
+    (new_version, new_api): (&Version, &PublicApi),
+) -> SemverCheckReport {
+    let mut changes: Vec<ApiChange> = Vec::new();
+    for (path, old_item) in old_api {
+        match new_api.get(path) {
+            None => changes.push(ApiChange {
+                path: path.clone(),
+                kind: old_item.kind,
+                change_type: ApiChangeType::Removed,
+                breaking: true,
+            }),
+            Some(new_item) if new_item != old_item => changes.push(ApiChange {
+                path: path.clone(),
+                kind: new_item.kind,
+                change_type: ApiChangeType::Modified,
+                breaking: new_item.kind != old_item.kind
+                    || new_item.signature != old_item.signature,
+            }),
+            _ => (),
+        }
+    }
+    for (path, new_item) in new_api {
+        if !old_api.contains_key(path) {
+            changes.push(ApiChange {
+                path: path.clone(),
+                kind: new_item.kind,
+                change_type: ApiChangeType::Added,
+                breaking: new_item.breaking_if_added,
+            });
+        }
+    }
+    changes.sort_by(|a, b| (!a.breaking, &a.path).cmp(&(!b.breaking, &b.path)));
+
+    SemverCheckReport {
+        breaking_change_allowed: get_semver_compatibility(old_version)
+            != get_semver_compatibility(new_version),
+        changes,
+    }
+}
+
+struct ApiReader<F: Fn(&str) -> Option<String>> {
+    api: PublicApi, // pub items of all modules, private ones included
+    modules: HashSet<String>,
+    private_modules: HashSet<String>,
+    reexports: Vec<Reexport>,
+    inherent_impls: Vec<InherentImpl>,
+    visited: HashSet<String>,
+    read_file: F,
+}
+
+// A pub use in a module, e.g., `pub use imp::a as b` in the crate root
+struct Reexport {
+    module: String,
+    name: Option<String>, // None for a glob
+    source: String,       // e.g., imp::a
+}
+
+// The pub items of an inherent impl in a module,
+// resolved once all modules are read as the type may be declared in another
+struct InherentImpl {
+    module: String,
+    self_type: String,                         // e.g., super::Parser
+    items: Vec<(String, ApiItemKind, String)>, // name, kind, and signature
+}
+
+impl<F: Fn(&str) -> Option<String>> ApiReader<F> {
+    fn add_file(&mut self, file: &str, module_dir: &str, prefix: &str) {
+        if !self.visited.insert(file.to_string()) {
+            return;
+        }
+        let syntax = match (self.read_file)(file).and_then(|source| syn::parse_file(&source).ok()) {
+            Some(syntax) => syntax,
+            None => return,
+        };
+        self.add_items(&syntax.items, (file, module_dir), prefix);
+    }
+
+    fn add_items(&mut self, items: &[syn::Item], (file, module_dir): (&str, &str), prefix: &str) {
+        for item in items {
+            match item {
+                // private modules are read as well,
+                // as their items can be re-exported and their trait impls apply
+                syn::Item::Mod(i) => {
+                    let path = get_item_path(prefix, &i.ident);
+                    if !is_public(&i.vis, &i.attrs) {
+                        self.private_modules.insert(path.clone());
+                    }
+                    self.modules.insert(path.clone());
+                    self.add_item(&path, ApiItemKind::Module, String::new(), false);
+                    match &i.content {
+                        Some((_, items)) => self.add_items(
+                            items,
+                            (file, &join_path(module_dir, &i.ident.to_string())),
+                            &path,
+                        ),
+                        None => {
+                            let module_file = get_module_file(
+                                (file, module_dir),
+                                &i.ident.to_string(),
+                                get_path_attr(&i.attrs).as_deref(),
+                                &self.read_file,
+                            );
+                            let module_dir = get_module_dir(&module_file, false);
+                            self.add_file(&module_file, &module_dir, &path);
+                        }
+                    }
+                }
+                syn::Item::Fn(i) if is_public(&i.vis, &i.attrs) => self.add_item(
+                    &get_item_path(prefix, &i.sig.ident),
+                    ApiItemKind::Function,
+                    get_fn_signature(&i.sig),
+                    false,
+                ),
+                syn::Item::Struct(i) if is_public(&i.vis, &i.attrs) => self.add_struct(prefix, i),
+                syn::Item::Union(i) if is_public(&i.vis, &i.attrs) => {
+                    let path = get_item_path(prefix, &i.ident);
+                    self.add_item(
+                        &path,
+                        ApiItemKind::Struct,
+                        format!("union {}", get_generics_signature(&i.generics)),
+                        false,
+                    );
+                    for field in i.fields.named.iter() {
+                        if let (Some(ident), true) = (&field.ident, is_public(&field.vis, &[])) {
+                            self.add_item(
+                                &get_item_path(&path, ident),
+                                ApiItemKind::Field,
+                                get_tokens(&field.ty),
+                                false,
+                            );
+                        }
+                    }
+                }
+                syn::Item::Enum(i) if is_public(&i.vis, &i.attrs) => {
+                    let path = get_item_path(prefix, &i.ident);
+                    let exhaustive = !is_non_exhaustive(&i.attrs);
+                    self.add_item(
+                        &path,
+                        ApiItemKind::Enum,
+                        format!(
+                            "{}{}",
+                            get_generics_signature(&i.generics),
+                            if exhaustive { "" } else { " #[non_exhaustive]" }
+                        ),
+                        false,
+                    );
+                    for variant in &i.variants {
+                        self.add_item(
+                            &get_item_path(&path, &variant.ident),
+                            ApiItemKind::Variant,
+                            get_tokens(&variant.fields),
+                            exhaustive,
+                        );
+                    }
+                }
+                syn::Item::Trait(i) if is_public(&i.vis, &i.attrs) => self.add_trait(prefix, i),
+                syn::Item::Impl(i) => self.add_impl(prefix, i),
+                syn::Item::Const(i) if is_public(&i.vis, &i.attrs) => self.add_item(
+                    &get_item_path(prefix, &i.ident),
+                    ApiItemKind::Const,
+                    get_tokens(&i.ty),
+                    false,
+                ),
+                syn::Item::Static(i) if is_public(&i.vis, &i.attrs) => self.add_item(
+                    &get_item_path(prefix, &i.ident),
+                    ApiItemKind::Static,
+                    format!("{}{}", get_tokens(&i.mutability), get_tokens(&i.ty)),
+                    false,
+                ),
+                syn::Item::Type(i) if is_public(&i.vis, &i.attrs) => self.add_item(
+                    &get_item_path(prefix, &i.ident),
+                    ApiItemKind::TypeAlias,
+                    format!(
+                        "{} = {}",
+                        get_generics_signature(&i.generics),
+                        get_tokens(&i.ty)
+                    ),
+                    false,
+                ),
+                syn::Item::Use(i) if is_public(&i.vis, &i.attrs) => {
+                    self.add_use_tree(prefix, "", &i.tree)
+                }
+                syn::Item::Macro(i)
+                    if i.attrs
+                        .iter()
+                        .any(|attr| attr.path.is_ident("macro_export")) =>
+                {
+                    // exported macros live at the crate root
+                    if let Some(ident) = &i.ident {
+                        self.add_item(
+                            &format!("{}!", ident),
+                            ApiItemKind::Macro,
+                            String::new(),
+                            false,
+                        );
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn add_struct(&mut self, prefix: &str, i: &syn::ItemStruct) {
+        let path = get_item_path(prefix, &i.ident);
+        // a struct with public fields only can be built with a struct expression,
+        // then adding a field, or a private one, breaks the users
+        let exhaustive =
+            !is_non_exhaustive(&i.attrs) && i.fields.iter().all(|field| is_public(&field.vis, &[]));
+        let shape = match &i.fields {
+            syn::Fields::Named(_) => "{}",
+            syn::Fields::Unnamed(_) => "()",
+            syn::Fields::Unit => ";",
+        };
+        self.add_item(
+            &path,
+            ApiItemKind::Struct,
+            format!(
+                "{} {}{}",
+                get_generics_signature(&i.generics),
+                shape,
+                if exhaustive { "" } else { " .." }
+            ),
+            false,
+        );
+        for (index, field) in i.fields.iter().enumerate() {
+            if !is_public(&field.vis, &[]) {
+                continue;
+            }
+            let name = match &field.ident {
+                Some(ident) => ident.to_string(),
+                None => index.to_string(),
+            };
+            self.add_item(
+                &format!("{}::{}", path, name),
+                ApiItemKind::Field,
+                get_tokens(&field.ty),
+                exhaustive,
+            );
+        }
+    }
+
+    fn add_trait(&mut self, prefix: &str, i: &syn::ItemTrait) {
+        let path = get_item_path(prefix, &i.ident);
+        self.add_item(
+            &path,
+            ApiItemKind::Trait,
+            format!(
+                "{}trait{}: {}",
+                get_tokens(&i.unsafety),
+                get_generics_signature(&i.generics),
+                get_tokens(&i.supertraits)
+            ),
+            false,
+        );
+        for item in &i.items {
+            // adding an item without a default breaks the implementors
+            let (ident, signature, has_default) = match item {
+                syn::TraitItem::Method(m) => {
+                    (&m.sig.ident, get_fn_signature(&m.sig), m.default.is_some())
+                }
+                syn::TraitItem::Type(t) => (&t.ident, get_tokens(&t.bounds), t.default.is_some()),
+                syn::TraitItem::Const(c) => (&c.ident, get_tokens(&c.ty), c.default.is_some()),
+                _ => continue,
+            };
+            self.add_item(
+                &get_item_path(&path, ident),
+                ApiItemKind::TraitItem,
+                signature,
+                !has_default,
+            );
+        }
+    }
+
+    fn add_impl(&mut self, prefix: &str, i: &syn::ItemImpl) {
+        if let Some((negative, trait_path, _)) = &i.trait_ {
+            let path = format!(
+                "impl {}{} for {}",
+                if negative.is_some() { "!" } else { "" },
+                get_tokens(trait_path),
+                get_tokens(&i.self_ty)
+            );
+            self.add_item(
+                &path,
+                ApiItemKind::TraitImpl,
+                get_generics_signature(&i.generics),
+                false,
+            );
+            return;
+        }
+
+        let self_type = match &*i.self_ty {
+            syn::Type::Path(type_path) => type_path
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect::<Vec<String>>()
+                .join("::"),
+            _ => return,
+        };
+        let items = i
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::ImplItem::Method(m) if is_public(&m.vis, &m.attrs) => Some((
+                    m.sig.ident.to_string(),
+                    ApiItemKind::Function,
+                    get_fn_signature(&m.sig),
+                )),
+                syn::ImplItem::Const(c) if is_public(&c.vis, &c.attrs) => {
+                    Some((c.ident.to_string(), ApiItemKind::Const, get_tokens(&c.ty)))
+                }
+                _ => None,
+            })
+            .collect();
+        self.inherent_impls.push(InherentImpl {
+            module: prefix.to_string(),
+            self_type,
+            items,
+        });
+    }
+
+    // Inherent items are keyed by the path of their type,
+    // and are only part of the API if the type is,
+    // e.g., the pub fn of a private struct is not
+    fn resolve_inherent_impls(&mut self) {
+        for inherent_impl in std::mem::take(&mut self.inherent_impls) {
+            let type_path =
+                match get_candidate_paths(&inherent_impl.module, &inherent_impl.self_type)
+                    .into_iter()
+                    .find(|candidate| {
+                        self.api.get(candidate).is_some_and(|item| {
+                            matches!(
+                                item.kind,
+                                ApiItemKind::Struct | ApiItemKind::Enum | ApiItemKind::TypeAlias
+                            )
+                        })
+                    }) {
+                    Some(type_path) => type_path,
+                    None => continue,
+                };
+            for (name, kind, signature) in inherent_impl.items {
+                self.add_item(&get_item_path(&type_path, &name), kind, signature, false);
+            }
+        }
+    }
+
+    // pub use re-exports, resolved once all modules are read
+    fn add_use_tree(&mut self, prefix: &str, use_path: &str, tree: &syn::UseTree) {
+        let join = |name: &str| {
+            if use_path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}::{}", use_path, name)
+            }
+        };
+        let mut add_reexport = |name: Option<String>, source: String| {
+            self.reexports.push(Reexport {
+                module: prefix.to_string(),
+                name,
+                source,
+            })
+        };
+        match tree {
+            syn::UseTree::Path(p) => {
+                self.add_use_tree(prefix, &join(&p.ident.to_string()), &p.tree)
+            }
+            // e.g., `pub use imp::{self, a}` re-exports the module imp
+            syn::UseTree::Name(n) if n.ident == "self" => add_reexport(
+                use_path.rsplit("::").next().map(|name| name.to_string()),
+                use_path.to_string(),
+            ),
+            syn::UseTree::Name(n) => {
+                add_reexport(Some(n.ident.to_string()), join(&n.ident.to_string()))
+            }
+            syn::UseTree::Rename(r) => {
+                add_reexport(Some(r.rename.to_string()), join(&r.ident.to_string()))
+            }
+            syn::UseTree::Glob(_) => add_reexport(None, use_path.to_string()),
+            syn::UseTree::Group(g) => {
+                for tree in &g.items {
+                    self.add_use_tree(prefix, use_path, tree);
+                }
+            }
+        }
+    }
+
+    // Re-exports of local items take the items they point to, e.g.,
+    // a function moved to a private module and re-exported is still a function,
+    // while re-exports of other crates are kept as such
+    fn resolve_reexports(&mut self) {
+        let reexports: Vec<Reexport> = std::mem::take(&mut self.reexports);
+        let mut resolved: Vec<bool> = vec![false; reexports.len()];
+        // Resolve all again until no new item is found,
+        // as a re-export can point to the items of another
+        for _ in 0..MAX_REEXPORT_DEPTH {
+            let count = self.api.len();
+            for (reexport, resolved) in reexports.iter().zip(resolved.iter_mut()) {
+                *resolved |= self.resolve_reexport(reexport);
+            }
+            if self.api.len() == count {
+                break;
+            }
+        }
+
+        for (reexport, _) in reexports
+            .into_iter()
+            .zip(resolved)
+            .filter(|(_, resolved)| !resolved)
+        {
+            let (path, signature) = match &reexport.name {
+                Some(name) => (get_item_path(&reexport.module, name), reexport.source),
+                None => {
+                    let source = format!("{}::*", reexport.source);
+                    (get_item_path(&reexport.module, &source), source)
+                }
+            };
+            self.add_item(&path, ApiItemKind::Reexport, signature, false);
+        }
+    }
+
+    // Copies the items a re-export points to, along with their items,
+    // returns false if the source is not a local item
+    fn resolve_reexport(&mut self, reexport: &Reexport) -> bool {
+        let source = match self.get_local_path(&reexport.module, &reexport.source) {
+            Some(source) => source,
+            None => return false,
+        };
+        let (target, from) = match &reexport.name {
+            Some(name) => (get_item_path(&reexport.module, name), source.clone()),
+            // a glob takes the items within the source
+            None => (reexport.module.clone(), format!("{}::", source)),
+        };
+
+        let mut items: Vec<(String, ApiItem)> = Vec::new();
+        if reexport.name.is_some() {
+            if let Some(item) = self.api.get(&source) {
+                items.push((target.clone(), item.clone()));
+            }
+        }
+        let descendants_from = format!("{}::", source);
+        for (path, item) in self
+            .api
+            .range::<str, _>((Bound::Included(descendants_from.as_str()), Bound::Unbounded))
+            .take_while(|(path, _)| path.starts_with(&descendants_from))
+        {
+            // e.g., the items of a private module within the source are not re-exported
+            if !is_visible(path, &source, &self.private_modules) {
+                continue;
+            }
+            let rest = &path[from.len()..];
+            let rest = rest.trim_start_matches("::");
+            items.push((get_item_path(&target, &rest), item.clone()));
+        }
+
+        for (path, item) in items {
+            if item.kind == ApiItemKind::Module {
+                self.modules.insert(path.clone());
+            }
+            self.api.insert(path, item);
+        }
+        true
+    }
+
+    // The crate path of a use path in a module if it names a local item or module
+    fn get_local_path(&self, module: &str, use_path: &str) -> Option<String> {
+        get_candidate_paths(module, use_path)
+            .into_iter()
+            .find(|candidate| {
+                let descendants_from = format!("{}::", candidate);
+                self.api.contains_key(candidate)
+                    || self.modules.contains(candidate)
+                    || self
+                        .api
+                        .range::<str, _>((
+                            Bound::Included(descendants_from.as_str()),
+                            Bound::Unbounded,
+                        ))
+                        .next()
+                        .is_some_and(|(path, _)| path.starts_with(&descendants_from))
+            })
+    }
+
+    fn add_item(
+        &mut self,
+        path: &str,
+        kind: ApiItemKind,
+        signature: String,
+        breaking_if_added: bool,
+    ) {
+        self.api.insert(
+            path.to_string(),
+            ApiItem {
+                kind,
+                signature,
+                breaking_if_added,
+            },
+        );
+    }
+}
+
+// The crate paths a path in a module may refer to
+fn get_candidate_paths(module: &str, path: &str) -> Vec<String> {
+    let mut segments: Vec<&str> = path.split("::").collect();
+    let mut base: Vec<&str> = if module.is_empty() {
+        Vec::new()
+    } else {
+        module.split("::").collect()
+    };
+    match segments.first() {
+        Some(&"crate") => {
+            segments.remove(0);
+            vec![segments.join("::")]
+        }
+        Some(&"self") | Some(&"super") => {
+            while let Some(&segment) = segments.first() {
+                match segment {
+                    "self" => (),
+                    "super" => {
+                        if base.pop().is_none() {
+                            return Vec::new();
+                        }
+                    }
+                    _ => break,
+                }
+                segments.remove(0);
+            }
+            base.extend(segments);
+            vec![base.join("::")]
+        }
+        // relative to the module, or to the crate root as in 2015 edition
+        _ => {
+            base.extend(segments.iter());
+            vec![base.join("::"), segments.join("::")]
+        }
+    }
+}
+
+// Whether an item is reachable from a module, i.e.,
+// it is not within a private module under the module
+fn is_visible(path: &str, module: &str, private_modules: &HashSet<String>) -> bool {
+    let mut prefix = String::new();
+    for segment in path.split("::") {
+        if !prefix.is_empty() {
+            prefix.push_str("::");
+        }
+        prefix.push_str(segment);
+        if prefix.len() > module.len() && private_modules.contains(&prefix) {
+            return false;
+        }
+    }
+    true
+}
+
+// Items hidden from the docs are not considered a part of the public API
+fn is_public(vis: &syn::Visibility, attrs: &[syn::Attribute]) -> bool {
+    matches!(vis, syn::Visibility::Public(_))
+        && !attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("doc") && attr.tokens.to_string().contains("hidden"))
+}
+
+fn is_non_exhaustive(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("non_exhaustive"))
+}
+
+fn get_item_path(prefix: &str, ident: &impl ToString) -> String {
+    if prefix.is_empty() {
+        ident.to_string()
+    } else {
+        format!("{}::{}", prefix, ident.to_string())
+    }
+}
+
+fn get_tokens(tokens: &impl ToTokens) -> String {
+    tokens.to_token_stream().to_string()
+}
+
+fn get_generics_signature(generics: &syn::Generics) -> String {
+    format!(
+        "{}{}",
+        get_tokens(generics),
+        get_tokens(&generics.where_clause)
+    )
+}
+
+// Parameter names are not a part of the signature
+fn get_fn_signature(sig: &syn::Signature) -> String {
+    let inputs: Vec<String> = sig
+        .inputs
+        .iter()
+        .map(|input| match input {
+            syn::FnArg::Receiver(receiver) if receiver.reference.is_some() => get_tokens(receiver),
+            syn::FnArg::Receiver(_) => "self".to_string(),
+            syn::FnArg::Typed(pat) => get_tokens(&pat.ty),
+        })
+        .collect();
+    let mut parts: Vec<String> = vec![
+        get_tokens(&sig.constness),
+        get_tokens(&sig.asyncness),
+        get_tokens(&sig.unsafety),
+        get_tokens(&sig.abi),
+        format!(
+            "fn{}({})",
+            get_generics_signature(&sig.generics),
+            inputs.join(", ")
+        ),
+        get_tokens(&sig.output),
+    ];
+    parts.retain(|part| !part.is_empty());
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crate_layout::DEFAULT_LIB_PATH;
+    use std::collections::HashMap;
+
+    fn get_api(files: &[(&str, &str)]) -> PublicApi {
+        let files: HashMap<&str, &str> = files.iter().cloned().collect();
+        get_public_api(DEFAULT_LIB_PATH, |path: &str| {
+            files.get(path).map(|content| content.to_string())
+        })
+    }
+
+    const OLD_LIB: &str = r#"
+        pub mod de;
+        mod private;
+
+        pub struct Config {
+            pub name: String,
+        }
+
+        pub enum Mode {
+            Fast,
+            Slow,
+        }
+
+        #[non_exhaustive]
+        pub enum Format {
+            Json,
+        }
+
+        pub trait Visitor {
+            fn visit(&self, input: &str);
+            fn finish(&self) {}
+        }
+
+        impl Config {
+            pub fn new(name: String) -> Self {
+                Config { name }
+            }
+            fn check(&self) {}
+        }
+
+        impl Clone for Config {
+            fn clone(&self) -> Self {
+                Config { name: self.name.clone() }
+            }
+        }
+
+        pub use de::Error as DeError;
+    "#;
+
+    #[test]
+    fn test_semver_check_public_api() {
+        let api = get_api(&[
+            ("src/lib.rs", OLD_LIB),
+            ("src/de.rs", "pub struct Error; pub(crate) fn helper() {}"),
+            ("src/private.rs", "pub fn hidden() {}"),
+        ]);
+        let paths: Vec<&str> = api.keys().map(|path| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "Config",
+                "Config::name",
+                "Config::new",
+                "DeError",
+                "Format",
+                "Format::Json",
+                "Mode",
+                "Mode::Fast",
+                "Mode::Slow",
+                "Visitor",
+                "Visitor::finish",
+                "Visitor::visit",
+                "de",
+                "de::Error",
+                "impl Clone for Config",
+            ]
+        );
+        assert_eq!(api["Config::new"].signature, "fn(String) -> Self");
+        assert_eq!(api["DeError"].kind, ApiItemKind::Struct);
+        assert!(api["Mode::Fast"].breaking_if_added);
+        assert!(!api["Format::Json"].breaking_if_added);
+        assert!(api["Visitor::visit"].breaking_if_added);
+        assert!(!api["Visitor::finish"].breaking_if_added);
+    }
+
+    #[test]
+    fn test_semver_check_inherent_impls() {
+        let api = get_api(&[
+            (
+                "src/lib.rs",
+                r#"
+            mod imp;
+
+            struct Inner;
+            impl Inner {
+                pub fn new() -> Self { Inner }
+            }
+
+            impl Parser {
+                pub const DEPTH: usize = 8;
+            }
+            pub struct Parser;
+            "#,
+            ),
+            (
+                "src/imp.rs",
+                "impl super::Parser { pub fn run(&self) {} } impl Inner { pub fn reset(&self) {} }",
+            ),
+        ]);
+        let paths: Vec<&str> = api.keys().map(|path| path.as_str()).collect();
+        // the pub fns of the private Inner are not part of the API,
+        // while those of Parser are wherever the impl is
+        assert_eq!(paths, vec!["Parser", "Parser::DEPTH", "Parser::run"]);
+    }
+
+    #[test]
+    fn test_semver_check_report() {
+        let old_api = get_api(&[("src/lib.rs", OLD_LIB), ("src/de.rs", "pub struct Error;")]);
+        let new_lib = OLD_LIB
+            .replace("pub fn new(name: String)", "pub fn new(title: String)")
+            .replace("Fast,", "Fast, Medium,")
+            .replace("Json,", "Json, Yaml,")
+            .replace(
+                "fn finish(&self) {}",
+                "fn finish(&self) {} fn reset(&self);",
+            )
+            .replace("impl Clone for Config", "impl Default for Config")
+            .replace(
+                "Config { name: self.name.clone() }",
+                "Config { name: String::new() }",
+            );
+        let new_api = get_api(&[
+            ("src/lib.rs", &new_lib),
+            ("src/de.rs", "pub struct Error; pub fn from_str() {}"),
+        ]);
+
+        let report = get_semver_check_report(
+            (&Version::parse("1.2.0").unwrap(), &old_api),
+            (&Version::parse("1.3.0").unwrap(), &new_api),
+        );
+        assert!(!report.breaking_change_allowed);
+        let violations: Vec<(&str, ApiChangeType)> = report
+            .get_violations()
+            .iter()
+            .map(|change| (change.path.as_str(), change.change_type))
+            .collect();
+        // renaming a parameter is not a breaking change
+        assert_eq!(
+            violations,
+            vec![
+                ("Mode::Medium", ApiChangeType::Added),
+                ("Visitor::reset", ApiChangeType::Added),
+                ("impl Clone for Config", ApiChangeType::Removed),
+            ]
+        );
+        assert_eq!(report.changes.len(), 6);
+
+        // the same changes are allowed in a major version bump
+        let report = get_semver_check_report(
+            (&Version::parse("1.2.0").unwrap(), &old_api),
+            (&Version::parse("2.0.0").unwrap(), &new_api),
+        );
+        assert!(report.breaking_change_allowed);
+        assert!(report.get_violations().is_empty());
+    }
+
+    #[test]
+    fn test_semver_check_reexports() {
+        let old_api = get_api(&[(
+            "src/lib.rs",
+            r#"
+            pub fn parse(input: &str) -> u8 { 0 }
+            pub struct Parser;
+            impl Parser {
+                pub fn run(&self) {}
+            }
+            pub enum Mode { Fast }
+            pub const LIMIT: u8 = 1;
+            "#,
+        )]);
+        // The same API moved into private modules and re-exported
+        let new_api = get_api(&[
+            (
+                "src/lib.rs",
+                r#"
+                mod imp;
+                mod modes;
+                mod limits {
+                    pub use self::inner::*;
+                    mod inner {
+                        pub const LIMIT: u8 = 1;
+                    }
+                }
+                pub use imp::{parse, Parser};
+                pub use crate::modes::*;
+                pub use limits::*;
+                pub use serde::Serialize;
+                "#,
+            ),
+            (
+                "src/imp.rs",
+                r#"
+                pub fn parse(input: &str) -> u8 { 0 }
+                pub struct Parser;
+                impl Parser {
+                    pub fn run(&self) {}
+                    pub(crate) fn reset(&self) {}
+                }
+                impl Clone for Parser {
+                    fn clone(&self) -> Self { Parser }
+                }
+                "#,
+            ),
+            ("src/modes.rs", "pub enum Mode { Fast }"),
+        ]);
+        assert_eq!(new_api["parse"].kind, ApiItemKind::Function);
+        assert_eq!(new_api["Parser::run"].kind, ApiItemKind::Function);
+        assert_eq!(new_api["Mode::Fast"].kind, ApiItemKind::Variant);
+        assert_eq!(new_api["LIMIT"].kind, ApiItemKind::Const);
+        assert_eq!(new_api["Serialize"].kind, ApiItemKind::Reexport);
+        assert!(!new_api.contains_key("imp::parse"));
+        assert!(!new_api.contains_key("limits"));
+
+        let report = get_semver_check_report(
+            (&Version::parse("1.2.0").unwrap(), &old_api),
+            (&Version::parse("1.3.0").unwrap(), &new_api),
+        );
+        assert!(report.get_violations().is_empty());
+        let changes: Vec<(&str, ApiItemKind, ApiChangeType)> = report
+            .changes
+            .iter()
+            .map(|change| (change.path.as_str(), change.kind, change.change_type))
+            .collect();
+        // the trait impl in the private module is a part of the API
+        assert_eq!(
+            changes,
+            vec![
+                ("Serialize", ApiItemKind::Reexport, ApiChangeType::Added),
+                (
+                    "impl Clone for Parser",
+                    ApiItemKind::TraitImpl,
+                    ApiChangeType::Added
+                ),
+            ]
+        );
+    }
+}
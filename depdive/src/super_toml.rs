@@ -3,6 +3,7 @@
 //! 2. Check a Cargo.toml is a package or a virtual manifest toml
 
 use crate::guppy_wrapper::get_direct_dependencies;
+use crate::registry::CargoConfig;
 use anyhow::{anyhow, Result};
 use camino::Utf8Path;
 use guppy::{
@@ -52,6 +53,9 @@ impl SuperPackageGenerator {
 
         let deps = get_direct_dependencies(graph);
         let feature_map = FeatureMapGenerator::get_direct_dependencies_features(graph)?;
+        // to name the alternative registries of the deps
+        let cargo_config = CargoConfig::discover(graph.workspace().root().as_ref())?;
+        let mut registries: HashMap<String, String> = HashMap::new();
 
         for dep in &deps {
             let feaure_info = feature_map
@@ -82,7 +86,18 @@ impl SuperPackageGenerator {
                 PackageSource::External(..) => {
                     if let Some(source) = dep.source().parse_external() {
                         match source {
-                            ExternalSource::Registry(..) => (), // TODO: handle non crates.io registries
+                            ExternalSource::Registry(index) => {
+                                let registry =
+                                    cargo_config.get_registry_name(index).ok_or_else(|| {
+                                        anyhow!(
+                                            "no registry in Cargo config for {} index {}",
+                                            dep.name(),
+                                            index
+                                        )
+                                    })?;
+                                line.push_str(&format!(", registry = \"{}\"", registry));
+                                registries.insert(registry.to_string(), index.to_string());
+                            }
                             ExternalSource::Git {
                                 repository,
                                 resolved,
@@ -113,6 +128,18 @@ impl SuperPackageGenerator {
         let mut file = OpenOptions::new().append(true).open(&path).unwrap();
         write!(file, "{}", toml).unwrap();
 
+        // The super package is outside the workspace,
+        // therefore, declare the registries in its own Cargo config
+        if !registries.is_empty() {
+            let mut config = String::new();
+            for (name, index) in &registries {
+                config.push_str(&format!("[registries.{}]\nindex = \"{}\"\n", name, index));
+            }
+            create_dir_all(self.dir.path().join(".cargo"))?;
+            let mut file = File::create(self.dir.path().join(".cargo").join("config.toml"))?;
+            write!(file, "{}", config)?;
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,492 @@
+//! This module locates unsafe items, i.e., unsafe blocks, fns, methods, impls, and traits,
+//! in a rust file with their line ranges,
+//! and maps the lines changed in a diff onto them
+//! to determine which unsafe items have been added, removed, or modified
+
+use anyhow::{anyhow, Result};
+use git2::Patch;
+use proc_macro2::Span;
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use syn::visit::{self, Visit};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UnsafeItemKind {
+    Block,
+    Function,
+    Method,
+    Impl,
+    Trait,
+}
+
+/// An unsafe item spanning from the `unsafe` keyword to the end of its body
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UnsafeItem {
+    pub kind: UnsafeItemKind,
+    // Name of the fn, method, trait, or impl type,
+    // or the enclosing fn or method for a block
+    pub name: Option<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsafeItemChangeType {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UnsafeItemChange {
+    pub kind: UnsafeItemKind,
+    pub name: Option<String>,
+    pub change_type: UnsafeItemChangeType,
+    pub old_lines: Option<(usize, usize)>, // None when the item is added
+    pub new_lines: Option<(usize, usize)>, // None when the item is removed
+}
+
+/// Returns the unsafe items in a rust source file in the order they appear
+pub fn get_unsafe_items(source: &str) -> Result<Vec<UnsafeItem>> {
+    let file = syn::parse_file(source)?;
+    let mut visitor = UnsafeItemVisitor::default();
+    visitor.visit_file(&file);
+    Ok(visitor.items)
+}
+
+#[derive(Default)]
+struct UnsafeItemVisitor {
+    items: Vec<UnsafeItem>,
+    fn_names: Vec<String>, // enclosing fns and methods
+}
+
+impl UnsafeItemVisitor {
+    fn add_item(&mut self, kind: UnsafeItemKind, name: Option<String>, start: Span, end: Span) {
+        self.items.push(UnsafeItem {
+            kind,
+            name,
+            start_line: start.start().line,
+            end_line: end.end().line,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for UnsafeItemVisitor {
+    fn visit_item_fn(&mut self, i: &'ast syn::ItemFn) {
+        let name = i.sig.ident.to_string();
+        if let Some(unsafety) = &i.sig.unsafety {
+            self.add_item(
+                UnsafeItemKind::Function,
+                Some(name.clone()),
+                unsafety.span,
+                i.block.brace_token.span,
+            );
+        }
+        self.fn_names.push(name);
+        visit::visit_item_fn(self, i);
+        self.fn_names.pop();
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast syn::ImplItemMethod) {
+        let name = i.sig.ident.to_string();
+        if let Some(unsafety) = &i.sig.unsafety {
+            self.add_item(
+                UnsafeItemKind::Method,
+                Some(name.clone()),
+                unsafety.span,
+                i.block.brace_token.span,
+            );
+        }
+        self.fn_names.push(name);
+        visit::visit_impl_item_method(self, i);
+        self.fn_names.pop();
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast syn::TraitItemMethod) {
+        let name = i.sig.ident.to_string();
+        if let Some(unsafety) = &i.sig.unsafety {
+            let end = match (&i.default, &i.semi_token) {
+                (Some(block), _) => block.brace_token.span,
+                (None, Some(semi_token)) => semi_token.spans[0],
+                (None, None) => unsafety.span,
+            };
+            self.add_item(
+                UnsafeItemKind::Method,
+                Some(name.clone()),
+                unsafety.span,
+                end,
+            );
+        }
+        self.fn_names.push(name);
+        visit::visit_trait_item_method(self, i);
+        self.fn_names.pop();
+    }
+
+    fn visit_item_impl(&mut self, i: &'ast syn::ItemImpl) {
+        if let Some(unsafety) = &i.unsafety {
+            let self_ty = i.self_ty.to_token_stream().to_string();
+            let name = match &i.trait_ {
+                Some((_, path, _)) => format!("{} for {}", path.to_token_stream(), self_ty),
+                None => self_ty,
+            };
+            self.add_item(
+                UnsafeItemKind::Impl,
+                Some(name),
+                unsafety.span,
+                i.brace_token.span,
+            );
+        }
+        visit::visit_item_impl(self, i);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast syn::ItemTrait) {
+        if let Some(unsafety) = &i.unsafety {
+            self.add_item(
+                UnsafeItemKind::Trait,
+                Some(i.ident.to_string()),
+                unsafety.span,
+                i.brace_token.span,
+            );
+        }
+        visit::visit_item_trait(self, i);
+    }
+
+    fn visit_expr_unsafe(&mut self, i: &'ast syn::ExprUnsafe) {
+        self.add_item(
+            UnsafeItemKind::Block,
+            self.fn_names.last().cloned(),
+            i.unsafe_token.span,
+            i.block.brace_token.span,
+        );
+        visit::visit_expr_unsafe(self, i);
+    }
+}
+
+/// Lines changed in a file as per its diff hunks
+#[derive(Debug, Default)]
+pub struct LineChanges {
+    deleted: BTreeSet<usize>, // line numbers in the old file
+    added: BTreeSet<usize>,   // line numbers in the new file
+    // (old_start, old_lines, new_start, new_lines) of each hunk
+    hunks: Vec<(usize, usize, usize, usize)>,
+    // unchanged lines within hunks, old to new line number
+    context: HashMap<usize, usize>,
+}
+
+impl LineChanges {
+    pub fn from_patch(patch: &Patch) -> Result<Self> {
+        let mut line_changes = Self::default();
+        for hunk_idx in 0..patch.num_hunks() {
+            let (hunk, lines) = patch.hunk(hunk_idx)?;
+            line_changes.hunks.push((
+                hunk.old_start() as usize,
+                hunk.old_lines() as usize,
+                hunk.new_start() as usize,
+                hunk.new_lines() as usize,
+            ));
+            for line_idx in 0..lines {
+                let line = patch.line_in_hunk(hunk_idx, line_idx)?;
+                match (line.old_lineno(), line.new_lineno()) {
+                    (Some(old), Some(new)) => {
+                        line_changes.context.insert(old as usize, new as usize);
+                    }
+                    (Some(old), None) => {
+                        line_changes.deleted.insert(old as usize);
+                    }
+                    (None, Some(new)) => {
+                        line_changes.added.insert(new as usize);
+                    }
+                    (None, None) => (),
+                }
+            }
+        }
+        Ok(line_changes)
+    }
+
+    /// Line number in the new file for an unchanged line in the old file
+    fn map_old_line(&self, line: usize) -> Option<usize> {
+        if self.deleted.contains(&line) {
+            return None;
+        }
+        if let Some(new_line) = self.context.get(&line) {
+            return Some(*new_line);
+        }
+
+        // Shift by the lines added and deleted in preceding hunks
+        let mut offset: i64 = 0;
+        for (old_start, old_lines, _, new_lines) in &self.hunks {
+            // a hunk with no old lines inserts after old_start
+            let old_end = if *old_lines == 0 {
+                *old_start
+            } else {
+                old_start + old_lines - 1
+            };
+            if old_end < line {
+                offset += *new_lines as i64 - *old_lines as i64;
+            }
+        }
+        Some((line as i64 + offset) as usize)
+    }
+
+    fn is_old_range_changed(&self, start: usize, end: usize) -> bool {
+        self.deleted.range(start..=end).next().is_some()
+    }
+
+    fn is_new_range_changed(&self, start: usize, end: usize) -> bool {
+        self.added.range(start..=end).next().is_some()
+    }
+}
+
+/// Matches the unsafe items before and after a change
+/// and returns the ones added, removed, or with any line changed
+pub fn get_unsafe_item_changes(
+    old_items: &[UnsafeItem],
+    new_items: &[UnsafeItem],
+    line_changes: &LineChanges,
+) -> Vec<UnsafeItemChange> {
+    let mut matches: Vec<(Option<&UnsafeItem>, Option<&UnsafeItem>)> = Vec::new();
+    let mut unmatched_old: Vec<&UnsafeItem> = Vec::new();
+    let mut matched_new: BTreeSet<usize> = BTreeSet::new();
+
+    // Match items whose first line is unchanged by where the line has moved
+    for old_item in old_items {
+        let new_idx = line_changes
+            .map_old_line(old_item.start_line)
+            .and_then(|line| {
+                new_items.iter().enumerate().position(|(idx, new_item)| {
+                    !matched_new.contains(&idx)
+                        && new_item.kind == old_item.kind
+                        && new_item.start_line == line
+                })
+            });
+        match new_idx {
+            Some(idx) => {
+                matched_new.insert(idx);
+                matches.push((Some(old_item), Some(&new_items[idx])));
+            }
+            None => unmatched_old.push(old_item),
+        }
+    }
+
+    // Then match the rest by kind and name in order of appearance,
+    // e.g., when the signature of an unsafe fn changes
+    for old_item in unmatched_old {
+        let new_idx = new_items.iter().enumerate().position(|(idx, new_item)| {
+            !matched_new.contains(&idx)
+                && new_item.kind == old_item.kind
+                && new_item.name == old_item.name
+        });
+        match new_idx {
+            Some(idx) => {
+                matched_new.insert(idx);
+                matches.push((Some(old_item), Some(&new_items[idx])));
+            }
+            None => matches.push((Some(old_item), None)),
+        }
+    }
+    for (idx, new_item) in new_items.iter().enumerate() {
+        if !matched_new.contains(&idx) {
+            matches.push((None, Some(new_item)));
+        }
+    }
+
+    let mut changes: Vec<UnsafeItemChange> = matches
+        .into_iter()
+        .filter_map(|(old_item, new_item)| {
+            let change_type = match (old_item, new_item) {
+                (Some(old_item), Some(new_item)) => {
+                    if line_changes.is_old_range_changed(old_item.start_line, old_item.end_line)
+                        || line_changes.is_new_range_changed(new_item.start_line, new_item.end_line)
+                    {
+                        UnsafeItemChangeType::Modified
+                    } else {
+                        return None;
+                    }
+                }
+                (None, Some(_)) => UnsafeItemChangeType::Added,
+                (Some(_), None) => UnsafeItemChangeType::Removed,
+                (None, None) => return None,
+            };
+            let item = new_item.or(old_item)?;
+            Some(UnsafeItemChange {
+                kind: item.kind,
+                name: item.name.clone(),
+                change_type,
+                old_lines: old_item.map(|item| (item.start_line, item.end_line)),
+                new_lines: new_item.map(|item| (item.start_line, item.end_line)),
+            })
+        })
+        .collect();
+    changes.sort_by_key(|change| (change.new_lines, change.old_lines));
+    changes
+}
+
+/// Unsafe item changes in a file given its contents before and after, and the diff
+pub fn get_file_unsafe_item_changes(
+    old_source: Option<&str>,
+    new_source: Option<&str>,
+    patch: &Patch,
+) -> Result<Vec<UnsafeItemChange>> {
+    let old_items = match old_source {
+        Some(source) => get_unsafe_items(source)
+            .map_err(|error| anyhow!("failed to parse old file: {}", error))?,
+        None => Vec::new(),
+    };
+    let new_items = match new_source {
+        Some(source) => get_unsafe_items(source)
+            .map_err(|error| anyhow!("failed to parse new file: {}", error))?,
+        None => Vec::new(),
+    };
+    let line_changes = LineChanges::from_patch(patch)?;
+    Ok(get_unsafe_item_changes(
+        &old_items,
+        &new_items,
+        &line_changes,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    const OLD_SOURCE: &str = indoc! {r#"
+        pub fn safe() -> u8 {
+            1
+        }
+
+        pub unsafe fn deref(p: *const u8) -> u8 {
+            *p
+        }
+
+        pub fn read(p: *const u8) -> u8 {
+            unsafe { *p }
+        }
+
+        unsafe impl Send for Wrapper {}
+
+        pub fn removed(p: *const u8) -> u8 {
+            unsafe { p.read() }
+        }
+    "#};
+
+    const NEW_SOURCE: &str = indoc! {r#"
+        pub fn safe() -> u8 {
+            2
+        }
+
+        pub unsafe fn deref(p: *const u8) -> u8 {
+            *p.add(1)
+        }
+
+        pub fn read(p: *const u8) -> u8 {
+            unsafe { *p }
+        }
+
+        unsafe impl Send for Wrapper {}
+
+        pub fn removed(p: *const u8) -> u8 {
+            0
+        }
+
+        pub unsafe trait Marker {}
+    "#};
+
+    fn get_patch<'a>(old: &'a str, new: &'a str) -> Patch<'a> {
+        Patch::from_buffers(
+            old.as_bytes(),
+            None,
+            new.as_bytes(),
+            None,
+            Some(git2::DiffOptions::new().context_lines(1)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_unsafe_items() {
+        let items = get_unsafe_items(OLD_SOURCE).unwrap();
+        assert_eq!(
+            items,
+            vec![
+                UnsafeItem {
+                    kind: UnsafeItemKind::Function,
+                    name: Some("deref".to_string()),
+                    start_line: 5,
+                    end_line: 7,
+                },
+                UnsafeItem {
+                    kind: UnsafeItemKind::Block,
+                    name: Some("read".to_string()),
+                    start_line: 10,
+                    end_line: 10,
+                },
+                UnsafeItem {
+                    kind: UnsafeItemKind::Impl,
+                    name: Some("Send for Wrapper".to_string()),
+                    start_line: 13,
+                    end_line: 13,
+                },
+                UnsafeItem {
+                    kind: UnsafeItemKind::Block,
+                    name: Some("removed".to_string()),
+                    start_line: 16,
+                    end_line: 16,
+                },
+            ]
+        );
+        assert!(get_unsafe_items("fn a( {").is_err());
+    }
+
+    #[test]
+    fn test_unsafe_item_changes() {
+        let patch = get_patch(OLD_SOURCE, NEW_SOURCE);
+        let changes =
+            get_file_unsafe_item_changes(Some(OLD_SOURCE), Some(NEW_SOURCE), &patch).unwrap();
+
+        // The unchanged unsafe block and impl are not reported
+        assert_eq!(
+            changes,
+            vec![
+                UnsafeItemChange {
+                    kind: UnsafeItemKind::Block,
+                    name: Some("removed".to_string()),
+                    change_type: UnsafeItemChangeType::Removed,
+                    old_lines: Some((16, 16)),
+                    new_lines: None,
+                },
+                UnsafeItemChange {
+                    kind: UnsafeItemKind::Function,
+                    name: Some("deref".to_string()),
+                    change_type: UnsafeItemChangeType::Modified,
+                    old_lines: Some((5, 7)),
+                    new_lines: Some((5, 7)),
+                },
+                UnsafeItemChange {
+                    kind: UnsafeItemKind::Trait,
+                    name: Some("Marker".to_string()),
+                    change_type: UnsafeItemChangeType::Added,
+                    old_lines: None,
+                    new_lines: Some((19, 19)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsafe_item_moved() {
+        // Lines added above an unsafe block shift it without modifying it
+        let old = "fn a() {\n    unsafe { b() }\n}\n";
+        let new = "fn c() {}\n\nfn a() {\n    unsafe { b() }\n}\n";
+        let patch = get_patch(old, new);
+        let changes = get_file_unsafe_item_changes(Some(old), Some(new), &patch).unwrap();
+        assert!(changes.is_empty());
+
+        // Deleted file
+        let patch = get_patch(old, "");
+        let changes = get_file_unsafe_item_changes(Some(old), None, &patch).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, UnsafeItemChangeType::Removed);
+    }
+}
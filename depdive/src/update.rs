@@ -1,39 +1,64 @@
 //! This module abstracts analyses for dependency update review.
 
-use crate::cratesio::CratesioAnalyzer;
+use crate::cratesio::{CrateVersions, CratesioAnalyzer, ReleaseInfo, VersionPublisher};
 use anyhow::{anyhow, Result};
+use cargo_geiger_serde::CounterBlock;
 use geiger::RsFileMetrics;
-use git2::{build::CheckoutBuilder, Delta, Diff};
+use git2::{build::CheckoutBuilder, Delta, Diff, DiffFile, Oid, Patch, Repository};
 use guppy::graph::{
     cargo::{CargoOptions, CargoResolverVersion},
-    feature::{FeatureFilter, StandardFeatures},
+    feature::{feature_filter, FeatureFilter, StandardFeatures},
     summaries::{
         diff::{SummaryDiff, SummaryDiffStatus},
-        Summary, SummaryId,
+        Summary, SummaryId, SummarySource,
     },
-    BuildTargetId, PackageGraph,
+    BuildTargetId, ExternalSource, PackageGraph, PackageMetadata, PackageSet,
 };
+use guppy::{PackageId, Platform, TargetFeatures};
+use rayon::{prelude::*, ThreadPoolBuilder};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet},
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env, fs,
     ops::Sub,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 use url::Url;
+use walkdir::WalkDir;
 
 use crate::advisory::AdvisoryLookup;
-use crate::diff::{CrateSourceDiffReport, DiffAnalyzer, HeadCommitNotFoundError, VersionDiffInfo};
-use crate::guppy_wrapper::get_direct_dependencies;
+use crate::build_scan::{
+    get_build_time_targets_from_manifest, scan_build_time_targets, BuildScanReport,
+    BuildTimeTarget, BuildTimeTargetKind,
+};
+use crate::code::UnsafeDetails;
+use crate::crate_layout::get_lib_path;
+use crate::diff::{
+    trim_remote_url, CrateSourceDiffReport, DiffAnalyzer, FetchedGitRepos, HeadCommitNotFoundError,
+    VersionDiffInfo,
+};
+use crate::file_kind::NativeArtifacts;
+use crate::guppy_wrapper::{
+    get_build_time_targets, get_dep_kind_map, get_dependency_paths, get_direct_dependencies,
+    get_package_dependencies, get_workspace_dependents, DependencyKind, DependencyPaths,
+};
+use crate::lockfile::{LockedPackage, LockedPackageChange, Lockfile};
+use crate::registry::{CargoConfig, Registry, RegistryResolver, CRATES_IO_INDEX};
+use crate::semver_check::{
+    get_public_api, get_semver_check_report, get_semver_compatibility, PublicApi, SemverCheckReport,
+};
+use crate::unsafe_items::{get_file_unsafe_item_changes, UnsafeItemChange};
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum DependencyType {
     Host,
     Target,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DependencyChangeInfo {
     pub name: String,
     pub dep_type: DependencyType,
@@ -41,40 +66,192 @@ pub struct DependencyChangeInfo {
     pub new_version_info: Option<VersionSourceInfo>, // None when a dep is removed
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VersionSourceInfo {
-    // TODO: accomodate to specify source and commits here for cases like
-    // crate_a updating from commit_a to commit_b from repo_a
     pub version: Version,
     pub repository: Option<String>,
     pub build_script_paths: HashSet<String>,
+    pub build_time_targets: BTreeSet<BuildTimeTarget>, // build script, proc-macro, and build dep lib
+    pub git_source: Option<GitSourceInfo>, // Some when the crate is sourced from a git repository
+    pub registry_index: Option<String>, // Some when the crate is from a registry, e.g., crates.io
+}
+
+/// The dependency resolution an update review is based on, like the cargo build flags.
+/// By default, all features and dev dependencies of all workspace members
+/// are resolved for any platform
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ReviewOptions {
+    pub targets: Vec<String>, // target triples, each reviewed as a configuration
+    // features enabled in any package on top of the default features,
+    // all features are enabled when neither this nor no_default_features is set
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub exclude_dev: bool,
+    pub members: Vec<String>, // workspace members to resolve, all when empty
+}
+
+impl ReviewOptions {
+    /// Whether any option narrows the review from the default resolution
+    pub fn narrows_review(&self) -> bool {
+        !self.targets.is_empty()
+            || !self.features.is_empty()
+            || self.no_default_features
+            || self.exclude_dev
+            || !self.members.is_empty()
+    }
+
+    fn get_feature_filter<'g: 'a, 'a>(&'a self) -> impl FeatureFilter<'g> + 'a {
+        let base = match (self.features.is_empty(), self.no_default_features) {
+            (true, false) => StandardFeatures::All,
+            (_, true) => StandardFeatures::None,
+            (false, false) => StandardFeatures::Default,
+        };
+        feature_filter(base, self.features.iter().map(|feature| feature.as_str()))
+    }
+
+    // (name, platform), a single configuration for any platform without targets
+    fn get_platforms(&self) -> Result<Vec<(String, Option<Platform<'static>>)>> {
+        if self.targets.is_empty() {
+            return Ok(vec![(ANY_PLATFORM.to_string(), None)]);
+        }
+        self.targets
+            .iter()
+            .map(|triple| {
+                let platform = Platform::new(triple.clone(), TargetFeatures::Unknown)
+                    .map_err(|e| anyhow!("unknown target {}: {}", triple, e))?;
+                Ok((triple.clone(), Some(platform)))
+            })
+            .collect()
+    }
+}
+
+/// A git source resolved to a commit in Cargo.lock,
+/// e.g., `git+https://github.com/diem/diem?rev=1d8ea1b#1d8ea1b...`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GitSourceInfo {
+    pub repository: String,
+    pub commit: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UpdateReviewReport {
     pub dep_update_review_reports: Vec<DepUpdateReviewReport>,
+    // For a downgrade, prior_version is the higher version
+    // and the diff stats are computed from the higher to the lower version
+    pub dep_downgrade_review_reports: Vec<DepUpdateReviewReport>,
+    // A crate moving to another source at the same version,
+    // e.g., from crates.io to a git repository,
+    // the diff stats compare the code from the two sources
+    pub dep_source_change_review_reports: Vec<DepUpdateReviewReport>,
+    pub dep_addition_review_reports: Vec<DepAdditionReviewReport>,
+    pub dep_removal_reports: Vec<DepRemovalReport>,
     pub version_conflicts: Vec<VersionConflict>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DepUpdateReviewReport {
     pub name: String,
     pub prior_version: VersionInfo,
     pub updated_version: VersionInfo,
     pub diff_stats: Option<VersionDiffStats>,
+    // None when the new version has no code running at build time,
+    // or the code could not be scanned
+    pub build_scan_report: Option<BuildScanReport>,
+    // None when the crate could not be found in the package graphs
+    pub transitive_churn: Option<TransitiveChurn>,
+    // None when either version has no library, or the diff is not available
+    pub semver_check_report: Option<SemverCheckReport>,
+    // None when the updated version is not from a registry,
+    // or the registry has no web API to list the owners
+    pub ownership_report: Option<OwnershipReport>,
+    // target configurations resolving the updated version under the review options,
+    // None when no option narrows the review
+    pub configurations: Option<Vec<String>>,
+    // how the workspace members pull in the updated version,
+    // None when the crate could not be found in the package graphs
+    pub dependency_paths: Option<DependencyPaths>,
+}
+
+/// Who published the two versions, and who owns the crate now
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct OwnershipReport {
+    pub prior_publisher: Option<String>, // None when the registry has no record of it
+    pub updated_publisher: Option<String>,
+    pub owners: Vec<String>, // current owners
+    // crates.io keeps no ownership history, so an owner counts as added
+    // when their first published version is after the prior version
+    pub new_owners: Vec<String>,
+    pub prior_repository: Option<String>,
+    pub updated_repository: Option<String>,
+}
+
+impl OwnershipReport {
+    pub fn is_publisher_changed(&self) -> bool {
+        matches!(
+            (&self.prior_publisher, &self.updated_publisher),
+            (Some(prior), Some(updated)) if prior != updated
+        )
+    }
+
+    /// Compares the repository urls ignoring trivial differences, e.g., a .git suffix
+    pub fn is_repository_changed(&self) -> bool {
+        let normalize = |url: &Option<String>| {
+            url.as_ref()
+                .map(|url| trim_remote_url(url).unwrap_or_else(|_| url.clone()))
+        };
+        normalize(&self.prior_repository) != normalize(&self.updated_repository)
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.is_publisher_changed() || !self.new_owners.is_empty() || self.is_repository_changed()
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Crates introduced or dropped in the dependency tree of a crate by its update
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TransitiveChurn {
+    pub added: Vec<ChurnedDependency>,
+    pub removed: Vec<ChurnedDependency>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChurnedDependency {
+    pub name: String,
+    pub version: Version,
+    // crates in the dependency tree that depend on it, as `name version`
+    pub dependents: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DepAdditionReviewReport {
+    pub name: String,
+    pub added_version: VersionInfo,
+    pub build_script_paths: HashSet<String>, // Empty indicates no build script
+    pub unsafe_stats: Option<CrateUnsafeStats>, // None if the crates.io code could not be scanned
+    // target configurations resolving the added version under the review options,
+    // None when no option narrows the review
+    pub configurations: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DepRemovalReport {
+    pub name: String,
+    pub version: Version,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VersionInfo {
     pub name: String,
     pub version: Version,
-    pub downloads: u64,
+    pub downloads: Option<u64>, // None when the registry has no download stats
     pub crate_source_diff_report: Option<CrateSourceDiffReport>, // We can optionally present this report
     // based on the use case
     pub known_advisories: Vec<CrateVersionRustSecAdvisory>,
+    pub git_source: Option<GitSourceInfo>, // None when the version is from crates.io
+    pub release_info: Option<ReleaseInfo>, // None when the registry has no web API, or not from one
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CrateVersionRustSecAdvisory {
     pub id: String,
     pub title: String,
@@ -84,9 +261,11 @@ pub struct CrateVersionRustSecAdvisory {
 pub struct VersionChangeInfo {
     pub old_version: Option<Version>, // None when a dep is added
     pub new_version: Option<Version>, // None when a dep is removed
+    pub old_source: Option<SummarySource>,
+    pub new_source: Option<SummarySource>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VersionDiffStats {
     pub files_changed: HashSet<String>,
     pub rust_files_changed: u64,
@@ -94,9 +273,18 @@ pub struct VersionDiffStats {
     pub deletions: u64,
     pub modified_build_scripts: HashSet<String>, // Empty indicates no change in build scripts
     pub unsafe_file_changed: Vec<FileUnsafeChangeStats>,
+    pub native_artifacts: NativeArtifacts, // binaries and native code added in the new version
 }
 
-#[derive(Debug, Clone)]
+/// Unsafe code present in a given version of a crate
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CrateUnsafeStats {
+    pub rust_files: u64,
+    pub files_with_unsafe: HashSet<String>,
+    pub unsafe_details: UnsafeDetails,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum VersionConflict {
     // Case 1: A dep has two copies of different version
     //         as a direct and a transitive dep in the graph
@@ -105,32 +293,117 @@ pub enum VersionConflict {
         direct_dep_version: Version,
         transitive_dep_version: Version,
     },
+    // Case 2: A dep has semver incompatible copies in the graph
+    SemverIncompatibleVersions {
+        name: String,
+        copies: Vec<ConflictingCopy>,
+    },
+    // Case 3: A dep has more copies in the graph after the change than before
+    DuplicateIntroduced {
+        name: String,
+        prior_copies: usize,
+        copies: Vec<ConflictingCopy>,
+    },
+    // Case 4: Multiple crates declare the same native library in the links key
+    LinksCollision {
+        links: String,
+        copies: Vec<ConflictingCopy>,
+    },
+    // Case 5: A dep is pulled from both a registry and a git repository
+    RegistryAndGitSources {
+        name: String,
+        copies: Vec<ConflictingCopy>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A copy of a crate in the graph involved in a version conflict
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConflictingCopy {
+    pub name: String,
+    pub version: Version,
+    pub source: String,
+    // workspace members depending on this copy, other than through another member
+    pub workspace_members: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum FileUnsafeCodeChangeStatus {
     UnsafeCounterModified, // when we have a delta in unsafe counter
     NoUnsafeCode,          // changed file(s) contained no unsafe code before and after change
     AllUnsafeCodeRemoved,  // there was unsafe code before the change that all got removed
-    Uncertain,             // changed files contain unsafe code,
-                           // TODO: our tool isn't that smart yet to verify if unsafe code lines have been changed
+    UnsafeCodeModified,    // lines within unsafe items are changed while the counter is the same
+    UnsafeCodeUnchanged,   // changed file contains unsafe code, but no unsafe item is changed
+    UnsafeCodeNotAnalyzed, // the counter is the same, but the file could not be parsed to map the changes
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileUnsafeChangeStats {
     pub file: String,
+    #[serde(with = "DeltaDef")]
     pub change_type: Delta,
     pub unsafe_change_status: FileUnsafeCodeChangeStatus,
     pub unsafe_delta: UnsafeDelta, // Delta in Unsafe counter:
     // Unsafe Delta cannot detect the case where a line is modified
-    // in which case the unsafe counter before and after will be the same
-    // TODO: detect if unsafe code has been modified in a diff
+    // in which case the unsafe counter before and after will be the same,
+    // therefore, the changed lines are mapped onto the unsafe items
+    pub unsafe_item_changes: Vec<UnsafeItemChange>,
 
     // Below field indicate the post state of an added/modified file
     // and will be None in case of a deleted file
+    #[serde(with = "option_rs_file_metrics")]
     pub unsafe_status: Option<RsFileMetrics>,
 }
 
+// Serde definitions for the foreign types in FileUnsafeChangeStats
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Delta")]
+enum DeltaDef {
+    Unmodified,
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+    Ignored,
+    Untracked,
+    Typechange,
+    Unreadable,
+    Conflicted,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RsFileMetrics")]
+struct RsFileMetricsDef {
+    counters: CounterBlock,
+    forbids_unsafe: bool,
+}
+
+mod option_rs_file_metrics {
+    use super::RsFileMetricsDef;
+    use geiger::RsFileMetrics;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct RsFileMetricsRef<'a>(#[serde(with = "RsFileMetricsDef")] &'a RsFileMetrics);
+
+    #[derive(Deserialize)]
+    struct RsFileMetricsOwned(#[serde(with = "RsFileMetricsDef")] RsFileMetrics);
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<RsFileMetrics>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.as_ref().map(RsFileMetricsRef).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<RsFileMetrics>, D::Error> {
+        let value: Option<RsFileMetricsOwned> = Option::deserialize(deserializer)?;
+        Ok(value.map(|RsFileMetricsOwned(metrics)| metrics))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct UnsafeDelta {
     pub functions: i64,
@@ -164,18 +437,110 @@ impl Sub for UnsafeDelta {
     }
 }
 
+pub const WORKERS_ENV: &str = "DEPDIVE_WORKERS";
+const ANY_PLATFORM: &str = "any platform";
+const DEFAULT_WORKERS: usize = 4;
+
+// crate name, old version, updated version,
+// and the old and updated git sources, if any
+type UpdateKey = (
+    String,
+    Version,
+    Version,
+    Option<GitSourceInfo>,
+    Option<GitSourceInfo>,
+);
+
+// crate name and version to its dependency kind in a graph
+type DepKindMap = HashMap<(String, Version), DependencyKind>;
+// name, whether a host dep, and the old and new version and git source
+type DepChangeKey = (
+    String,
+    bool,
+    Option<(Version, Option<GitSourceInfo>)>,
+    Option<(Version, Option<GitSourceInfo>)>,
+);
+
+// Analyses on the diff between the two versions of an updated crate
+#[derive(Default)]
+struct VersionDiffReview {
+    diff_stats: Option<VersionDiffStats>,
+    build_scan_report: Option<BuildScanReport>,
+    semver_check_report: Option<SemverCheckReport>,
+}
+
 pub struct UpdateAnalyzer {
-    // the key will be crate name, old version, and updated version
-    cache: RefCell<HashMap<(String, Version, Version), DepUpdateReviewReport>>,
+    cache: Mutex<HashMap<UpdateKey, DepUpdateReviewReport>>,
+    // the key will be crate name and added version
+    addition_cache: Mutex<HashMap<(String, Version), DepAdditionReviewReport>>,
+    // loaded once on first use and shared across all reviews
+    advisory_lookup: Mutex<Option<Arc<AdvisoryLookup>>>,
+    // max number of dependencies reviewed concurrently
+    workers: usize,
+    // resolves the registries to download crates from,
+    // read from the Cargo config of the post workspace unless given
+    registry_resolver: Mutex<Option<Arc<RegistryResolver>>>,
+    // cached git repos are fetched once per run
+    fetched_git_repos: FetchedGitRepos,
+    // without package graphs, the manifests are read from the crates downloaded for review
+    read_manifests: bool,
 }
 
 impl UpdateAnalyzer {
+    /// Reviews up to DEPDIVE_WORKERS dependencies concurrently, 4 by default
     pub fn new() -> Self {
+        let workers = env::var(WORKERS_ENV)
+            .ok()
+            .and_then(|workers| workers.parse().ok())
+            .filter(|&workers| workers > 0)
+            .unwrap_or(DEFAULT_WORKERS);
+        Self {
+            cache: Mutex::new(HashMap::new()),
+            addition_cache: Mutex::new(HashMap::new()),
+            advisory_lookup: Mutex::new(None),
+            workers,
+            registry_resolver: Mutex::new(None),
+            fetched_git_repos: FetchedGitRepos::default(),
+            read_manifests: false,
+        }
+    }
+
+    pub fn with_advisory_lookup(advisory_lookup: AdvisoryLookup) -> Self {
+        Self {
+            advisory_lookup: Mutex::new(Some(Arc::new(advisory_lookup))),
+            ..Self::new()
+        }
+    }
+
+    pub fn with_workers(self, workers: usize) -> Self {
         Self {
-            cache: RefCell::new(HashMap::new()),
+            workers: workers.max(1),
+            ..self
         }
     }
 
+    pub fn with_registry_resolver(self, registry_resolver: RegistryResolver) -> Self {
+        Self {
+            registry_resolver: Mutex::new(Some(Arc::new(registry_resolver))),
+            ..self
+        }
+    }
+
+    fn get_advisory_lookup(&self) -> Result<Arc<AdvisoryLookup>> {
+        // Hold the lock while loading so that the database is loaded only once
+        let mut advisory_lookup = self.advisory_lookup.lock().unwrap();
+        if advisory_lookup.is_none() {
+            *advisory_lookup = Some(Arc::new(AdvisoryLookup::new()?));
+        }
+        advisory_lookup
+            .clone()
+            .ok_or_else(|| anyhow!("fatal error in loading advisory lookup"))
+    }
+
+    fn get_diff_analyzer(&self) -> Result<DiffAnalyzer> {
+        Ok(DiffAnalyzer::new()?.with_fetched_git_repos(self.fetched_git_repos.clone()))
+    }
+
     /// Given two guppy graph
     /// determines the updated dependencies
     /// and provides a update review report
@@ -203,34 +568,349 @@ impl UpdateAnalyzer {
         // Get the changed dependency stats
         let dep_change_infos =
             Self::compare_pacakge_graphs(prior_graph, post_graph, cargo_opts, feature_filter)?;
+        self.load_registry_resolver(post_graph.workspace().root().as_ref())?;
+
+        self.review_dep_changes(&dep_change_infos, Some((prior_graph, post_graph)))
+    }
 
-        // Filter version updates
-        let updated_deps: Vec<DependencyChangeInfo> = dep_change_infos
+    /// Given two guppy graph,
+    /// determines the updated dependencies for the targets, features,
+    /// and workspace members in the review options
+    /// and provides a update review report
+    /// with the target configurations including each updated or added crate
+    pub fn analyze_updates_with_review_options(
+        self,
+        prior_graph: &PackageGraph,
+        post_graph: &PackageGraph,
+        review_options: &ReviewOptions,
+    ) -> Result<UpdateReviewReport> {
+        let (dep_change_infos, configurations) = Self::compare_package_graphs_for_review_options(
+            prior_graph,
+            post_graph,
+            review_options,
+        )?;
+        self.load_registry_resolver(post_graph.workspace().root().as_ref())?;
+
+        let mut report =
+            self.review_dep_changes(&dep_change_infos, Some((prior_graph, post_graph)))?;
+        if !review_options.narrows_review() {
+            return Ok(report);
+        }
+
+        let get_configurations = |name: &str, version: &Version| {
+            let key = (name.to_string(), version.clone());
+            Some(
+                configurations
+                    .iter()
+                    .filter(|(_, packages)| packages.contains(&key))
+                    .map(|(configuration, _)| configuration.clone())
+                    .collect(),
+            )
+        };
+        for dep in report
+            .dep_update_review_reports
+            .iter_mut()
+            .chain(report.dep_downgrade_review_reports.iter_mut())
+            .chain(report.dep_source_change_review_reports.iter_mut())
+        {
+            dep.configurations = get_configurations(&dep.name, &dep.updated_version.version);
+        }
+        for dep in report.dep_addition_review_reports.iter_mut() {
+            dep.configurations = get_configurations(&dep.name, &dep.added_version.version);
+        }
+        Ok(report)
+    }
+
+    // Returns the changed dependencies over all configurations,
+    // and the crates each configuration resolves in the post graph
+    #[allow(clippy::type_complexity)]
+    fn compare_package_graphs_for_review_options(
+        prior_graph: &PackageGraph,
+        post_graph: &PackageGraph,
+        review_options: &ReviewOptions,
+    ) -> Result<(
+        Vec<DependencyChangeInfo>,
+        Vec<(String, HashSet<(String, Version)>)>,
+    )> {
+        for member in &review_options.members {
+            if prior_graph.workspace().member_by_name(member).is_err()
+                && post_graph.workspace().member_by_name(member).is_err()
+            {
+                return Err(anyhow!("workspace member {} not found", member));
+            }
+        }
+
+        let mut dep_change_infos: Vec<DependencyChangeInfo> = Vec::new();
+        // a change found in more than one configuration is reviewed once
+        let mut dep_change_keys: HashSet<DepChangeKey> = HashSet::new();
+        // configuration name, and the crates it resolves in the post graph
+        let mut configurations: Vec<(String, HashSet<(String, Version)>)> = Vec::new();
+        for (name, platform) in review_options.get_platforms()? {
+            let mut cargo_opts = Self::get_default_cargo_options();
+            cargo_opts
+                .set_include_dev(!review_options.exclude_dev)
+                .set_platform(platform);
+
+            let prior_summary = Self::get_summary(
+                Self::get_member_packages(prior_graph, review_options)?,
+                review_options.get_feature_filter(),
+                &cargo_opts,
+            )?;
+            let post_summary = Self::get_summary(
+                Self::get_member_packages(post_graph, review_options)?,
+                review_options.get_feature_filter(),
+                &cargo_opts,
+            )?;
+
+            for dep in
+                Self::compare_summaries((prior_graph, &prior_summary), (post_graph, &post_summary))?
+            {
+                if dep_change_keys.insert(Self::get_dep_change_key(&dep)) {
+                    dep_change_infos.push(dep);
+                }
+            }
+            configurations.push((
+                name,
+                post_summary
+                    .host_packages
+                    .keys()
+                    .chain(post_summary.target_packages.keys())
+                    .map(|summary_id| (summary_id.name.clone(), summary_id.version.clone()))
+                    .collect(),
+            ));
+        }
+        Ok((dep_change_infos, configurations))
+    }
+
+    // Resolves the given workspace members, or all of them when none is given.
+    // Without any narrowing option, all packages are resolved
+    // the same as analyze_updates does
+    fn get_member_packages<'g>(
+        graph: &'g PackageGraph,
+        review_options: &ReviewOptions,
+    ) -> Result<PackageSet<'g>> {
+        if !review_options.narrows_review() {
+            return Ok(graph.resolve_all());
+        }
+        if review_options.members.is_empty() {
+            return Ok(graph.resolve_workspace());
+        }
+        // a member can be added or removed by the change
+        let members = review_options
+            .members
+            .iter()
+            .filter(|member| graph.workspace().member_by_name(member).is_ok());
+        Ok(graph.resolve_workspace_names(members)?)
+    }
+
+    fn get_dep_change_key(dep: &DependencyChangeInfo) -> DepChangeKey {
+        let get_version_key = |info: &Option<VersionSourceInfo>| {
+            info.as_ref()
+                .map(|info| (info.version.clone(), info.git_source.clone()))
+        };
+        (
+            dep.name.clone(),
+            matches!(dep.dep_type, DependencyType::Host),
+            get_version_key(&dep.old_version_info),
+            get_version_key(&dep.new_version_info),
+        )
+    }
+
+    /// Given the packages locked in two Cargo.lock files, prior and post,
+    /// determines the changed dependencies without building package graphs
+    /// and provides a update review report.
+    /// Crates are read from the registry recorded in the lockfile
+    /// as per the Cargo config in the workspace root.
+    /// Checks over the package graphs, i.e., transitive churn and version conflicts, are skipped
+    pub fn analyze_lockfile_updates(
+        self,
+        prior_lockfile: &Lockfile,
+        post_lockfile: &Lockfile,
+        workspace_root: &Path,
+    ) -> Result<UpdateReviewReport> {
+        self.load_registry_resolver(workspace_root)?;
+
+        let dep_change_infos: Vec<DependencyChangeInfo> = prior_lockfile
+            .get_changes(post_lockfile)
+            .iter()
+            .map(Self::get_dependency_change_info_from_lockfile)
+            .collect();
+
+        Self {
+            read_manifests: true,
+            ..self
+        }
+        .review_dep_changes(&dep_change_infos, None)
+    }
+
+    fn load_registry_resolver(&self, workspace_root: &Path) -> Result<()> {
+        let mut registry_resolver = self.registry_resolver.lock().unwrap();
+        if registry_resolver.is_none() {
+            let config = CargoConfig::discover(workspace_root)?;
+            *registry_resolver = Some(Arc::new(RegistryResolver::new(config)?));
+        }
+        Ok(())
+    }
+
+    // graphs are None when the changes are read from lockfiles
+    fn review_dep_changes(
+        &self,
+        dep_change_infos: &[DependencyChangeInfo],
+        graphs: Option<(&PackageGraph, &PackageGraph)>,
+    ) -> Result<UpdateReviewReport> {
+        // Filter version updates and downgrades,
+        // a git dependency moving to another commit counts as an update
+        // unless the version goes down,
+        // source changes at the same version are split from the reports below
+        let (updated_deps, downgraded_deps): (
+            Vec<DependencyChangeInfo>,
+            Vec<DependencyChangeInfo>,
+        ) = dep_change_infos
             .iter()
             .filter(
                 |dep| match (dep.old_version_info.as_ref(), dep.new_version_info.as_ref()) {
-                    (Some(old), Some(new)) => new.version > old.version,
+                    (Some(old), Some(new)) => {
+                        new.version != old.version || new.git_source != old.git_source
+                    }
                     _ => false,
                 },
             )
             .cloned()
-            .collect();
-        // TODO: add reporting for version downgrades, add, and remove
+            .partition(|dep| {
+                match (dep.old_version_info.as_ref(), dep.new_version_info.as_ref()) {
+                    (Some(old), Some(new)) => new.version >= old.version,
+                    _ => false,
+                }
+            });
+
+        // Review dependencies concurrently with a bounded worker pool
+        let pool = ThreadPoolBuilder::new().num_threads(self.workers).build()?;
 
         // clean cache if there's anything in a weird scenario
         // And store all the distinct update review in the cache
-        self.cache.borrow_mut().clear();
-        for dep in &updated_deps {
-            self.get_update_review(dep)?;
+        self.cache.lock().unwrap().clear();
+        // a crate can be changed both as a host and a target dep
+        let distinct_changed_deps: HashMap<UpdateKey, &DependencyChangeInfo> = updated_deps
+            .iter()
+            .chain(downgraded_deps.iter())
+            .filter_map(|dep| Some((Self::get_update_key(dep)?, dep)))
+            .collect();
+        pool.install(|| {
+            distinct_changed_deps
+                .par_iter()
+                .map(|(_key, dep)| self.get_update_review(dep).map(|_| ()))
+                .collect::<Result<()>>()
+        })?;
+        let (mut dep_source_change_review_reports, changed_version_reports): (
+            Vec<DepUpdateReviewReport>,
+            Vec<DepUpdateReviewReport>,
+        ) = self
+            .cache
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_k, v)| v)
+            .partition(|report| {
+                Self::is_source_change(
+                    (
+                        &report.prior_version.version,
+                        &report.prior_version.git_source,
+                    ),
+                    (
+                        &report.updated_version.version,
+                        &report.updated_version.git_source,
+                    ),
+                )
+            });
+        let (mut dep_update_review_reports, mut dep_downgrade_review_reports): (
+            Vec<DepUpdateReviewReport>,
+            Vec<DepUpdateReviewReport>,
+        ) = changed_version_reports
+            .into_iter()
+            .partition(|report| report.updated_version.version >= report.prior_version.version);
+        // Link the crates pulled in or dropped in the dependency tree back to the update
+        if let Some((prior_graph, post_graph)) = graphs {
+            for report in dep_update_review_reports
+                .iter_mut()
+                .chain(dep_downgrade_review_reports.iter_mut())
+                .chain(dep_source_change_review_reports.iter_mut())
+            {
+                report.transitive_churn = Self::get_transitive_churn(
+                    prior_graph,
+                    post_graph,
+                    &report.name,
+                    &report.prior_version.version,
+                    &report.updated_version.version,
+                )?;
+                report.dependency_paths =
+                    Self::get_package(post_graph, &report.name, &report.updated_version.version)
+                        .map(|package| get_dependency_paths(&package));
+            }
         }
-        let dep_update_review_reports: Vec<DepUpdateReviewReport> =
-            self.cache.borrow_mut().drain().map(|(_k, v)| v).collect();
+        // Keep the ordering deterministic regardless of completion order
+        dep_update_review_reports.sort_by(Self::cmp_dep_update_review_reports);
+        dep_downgrade_review_reports.sort_by(Self::cmp_dep_update_review_reports);
+        dep_source_change_review_reports.sort_by(Self::cmp_dep_update_review_reports);
 
-        let version_conflicts: Vec<VersionConflict> =
-            Self::determine_version_conflict(&updated_deps, post_graph);
+        // Filter dependency additions
+        let added_deps: Vec<DependencyChangeInfo> = dep_change_infos
+            .iter()
+            .filter(|dep| dep.old_version_info.is_none() && dep.new_version_info.is_some())
+            .cloned()
+            .collect();
+
+        self.addition_cache.lock().unwrap().clear();
+        pool.install(|| {
+            added_deps
+                .par_iter()
+                .map(|dep| self.get_addition_review(dep).map(|_| ()))
+                .collect::<Result<()>>()
+        })?;
+        let mut dep_addition_review_reports: Vec<DepAdditionReviewReport> = self
+            .addition_cache
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_k, v)| v)
+            .collect();
+        dep_addition_review_reports.sort_by(|a, b| {
+            (&a.name, &a.added_version.version).cmp(&(&b.name, &b.added_version.version))
+        });
+
+        // Filter dependency removals,
+        // a crate can be removed both as a host and a target dep
+        let dep_removal_reports: BTreeSet<DepRemovalReport> = dep_change_infos
+            .iter()
+            .filter_map(|dep| {
+                match (dep.old_version_info.as_ref(), dep.new_version_info.as_ref()) {
+                    (Some(old), None) => Some(DepRemovalReport {
+                        name: dep.name.clone(),
+                        version: old.version.clone(),
+                    }),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let mut version_conflicts: Vec<VersionConflict> = Vec::new();
+        if let Some((prior_graph, post_graph)) = graphs {
+            let changed_deps: Vec<DependencyChangeInfo> =
+                [updated_deps.as_slice(), downgraded_deps.as_slice()].concat();
+            version_conflicts = Self::determine_version_conflict(&changed_deps, post_graph);
+            if !dep_change_infos.is_empty() {
+                version_conflicts.extend(Self::determine_graph_version_conflicts(
+                    prior_graph,
+                    post_graph,
+                )?);
+            }
+        }
 
         Ok(UpdateReviewReport {
             dep_update_review_reports,
+            dep_downgrade_review_reports,
+            dep_source_change_review_reports,
+            dep_addition_review_reports,
+            dep_removal_reports: dep_removal_reports.into_iter().collect(),
             version_conflicts,
         })
     }
@@ -263,6 +943,106 @@ impl UpdateAnalyzer {
         conflicts
     }
 
+    /// Checks the whole post graph for duplicate copies of a crate
+    /// and the workspace members pulling them in
+    fn determine_graph_version_conflicts(
+        prior_graph: &PackageGraph,
+        post_graph: &PackageGraph,
+    ) -> Result<Vec<VersionConflict>> {
+        let mut conflicts: Vec<VersionConflict> = Vec::new();
+
+        let prior_copies = Self::get_copies_by_name(prior_graph);
+        for (name, packages) in Self::get_copies_by_name(post_graph) {
+            if packages.len() < 2 {
+                continue;
+            }
+            let copies = packages
+                .iter()
+                .map(|package| Self::get_conflicting_copy(post_graph, package))
+                .collect::<Result<Vec<ConflictingCopy>>>()?;
+
+            let semver_groups: HashSet<(u64, u64, u64)> = packages
+                .iter()
+                .map(|package| get_semver_compatibility(package.version()))
+                .collect();
+            if semver_groups.len() > 1 {
+                conflicts.push(VersionConflict::SemverIncompatibleVersions {
+                    name: name.to_string(),
+                    copies: copies.clone(),
+                });
+            }
+
+            let prior_count = prior_copies.get(name).map_or(0, |copies| copies.len());
+            if packages.len() > prior_count {
+                conflicts.push(VersionConflict::DuplicateIntroduced {
+                    name: name.to_string(),
+                    prior_copies: prior_count,
+                    copies: copies.clone(),
+                });
+            }
+
+            let (registry, git) =
+                packages
+                    .iter()
+                    .fold((false, false), |(registry, git), package| {
+                        match package.source().parse_external() {
+                            Some(ExternalSource::Registry(_)) => (true, git),
+                            Some(ExternalSource::Git { .. }) => (registry, true),
+                            _ => (registry, git),
+                        }
+                    });
+            if registry && git {
+                conflicts.push(VersionConflict::RegistryAndGitSources {
+                    name: name.to_string(),
+                    copies,
+                });
+            }
+        }
+
+        // Cargo allows only one package to link a given native library
+        let mut links: BTreeMap<&str, Vec<PackageMetadata>> = BTreeMap::new();
+        for package in post_graph.packages() {
+            if let Some(library) = package.links() {
+                links.entry(library).or_default().push(package);
+            }
+        }
+        for (library, packages) in links.into_iter().filter(|(_, packages)| packages.len() > 1) {
+            conflicts.push(VersionConflict::LinksCollision {
+                links: library.to_string(),
+                copies: packages
+                    .iter()
+                    .map(|package| Self::get_conflicting_copy(post_graph, package))
+                    .collect::<Result<Vec<ConflictingCopy>>>()?,
+            });
+        }
+
+        Ok(conflicts)
+    }
+
+    // Non workspace packages grouped by name, ordered by version
+    fn get_copies_by_name(graph: &PackageGraph) -> BTreeMap<&str, Vec<PackageMetadata<'_>>> {
+        let mut copies: BTreeMap<&str, Vec<PackageMetadata>> = BTreeMap::new();
+        for package in graph.packages().filter(|package| !package.in_workspace()) {
+            copies.entry(package.name()).or_default().push(package);
+        }
+        for packages in copies.values_mut() {
+            packages.sort_by(|a, b| a.version().cmp(b.version()));
+        }
+        copies
+    }
+
+    fn get_conflicting_copy(
+        graph: &PackageGraph,
+        package: &PackageMetadata,
+    ) -> Result<ConflictingCopy> {
+        Ok(ConflictingCopy {
+            name: package.name().to_string(),
+            version: package.version().clone(),
+            source: package.source().to_string(),
+            workspace_members: get_workspace_dependents(graph, package)?,
+        })
+    }
+
     fn get_default_cargo_options() -> CargoOptions<'static> {
         let mut cargo_opts = CargoOptions::new();
         cargo_opts.set_version(CargoResolverVersion::V2);
@@ -276,26 +1056,48 @@ impl UpdateAnalyzer {
         cargo_opts: &CargoOptions,
         mut feature_filter: impl FeatureFilter<'a>,
     ) -> Result<Vec<DependencyChangeInfo>> {
-        let prior_summary = Self::get_summary(prior_graph, &mut feature_filter, cargo_opts)?;
-        let post_summary = Self::get_summary(post_graph, &mut feature_filter, cargo_opts)?;
-        let diff = SummaryDiff::new(&prior_summary, &post_summary);
+        let prior_summary =
+            Self::get_summary(prior_graph.resolve_all(), &mut feature_filter, cargo_opts)?;
+        let post_summary =
+            Self::get_summary(post_graph.resolve_all(), &mut feature_filter, cargo_opts)?;
+        Self::compare_summaries((prior_graph, &prior_summary), (post_graph, &post_summary))
+    }
+
+    fn compare_summaries(
+        (prior_graph, prior_summary): (&PackageGraph, &Summary),
+        (post_graph, post_summary): (&PackageGraph, &Summary),
+    ) -> Result<Vec<DependencyChangeInfo>> {
+        let diff = SummaryDiff::new(prior_summary, post_summary);
+        // to find the libraries of build deps that run at build time
+        let prior_dep_kind_map = get_dep_kind_map(prior_graph)?;
+        let post_dep_kind_map = get_dep_kind_map(post_graph)?;
 
         let mut dep_change_infos: Vec<DependencyChangeInfo> = Vec::new();
 
-        for (summary_id, summary_diff_status) in diff.host_packages.changed.iter() {
+        for (summary_id, summary_diff_status) in diff
+            .host_packages
+            .changed
+            .iter()
+            .filter(|(summary_id, _)| !Self::is_workspace_member(summary_id))
+        {
             dep_change_infos.push(Self::get_dependency_change_info(
-                prior_graph,
-                post_graph,
+                (prior_graph, &prior_dep_kind_map),
+                (post_graph, &post_dep_kind_map),
                 summary_id,
                 summary_diff_status,
                 DependencyType::Host,
             )?);
         }
 
-        for (summary_id, summary_diff_status) in diff.target_packages.changed.iter() {
+        for (summary_id, summary_diff_status) in diff
+            .target_packages
+            .changed
+            .iter()
+            .filter(|(summary_id, _)| !Self::is_workspace_member(summary_id))
+        {
             dep_change_infos.push(Self::get_dependency_change_info(
-                prior_graph,
-                post_graph,
+                (prior_graph, &prior_dep_kind_map),
+                (post_graph, &post_dep_kind_map),
                 summary_id,
                 summary_diff_status,
                 DependencyType::Target,
@@ -305,13 +1107,18 @@ impl UpdateAnalyzer {
         Ok(dep_change_infos)
     }
 
+    // A workspace member added or removed by the change is the code under review,
+    // not a dependency, and looking it up on crates.io would review a namesake crate
+    fn is_workspace_member(summary_id: &SummaryId) -> bool {
+        matches!(summary_id.source, SummarySource::Workspace { .. })
+    }
+
     fn get_summary<'a>(
-        graph: &'a PackageGraph,
+        package_set: PackageSet<'a>,
         feature_filter: impl FeatureFilter<'a>,
         cargo_opts: &CargoOptions,
     ) -> Result<Summary> {
-        let summary = graph
-            .resolve_all()
+        let summary = package_set
             .to_feature_set(feature_filter)
             .into_cargo_set(cargo_opts)?
             .to_summary(cargo_opts)?;
@@ -319,8 +1126,8 @@ impl UpdateAnalyzer {
     }
 
     fn get_dependency_change_info(
-        prior_graph: &PackageGraph,
-        post_graph: &PackageGraph,
+        (prior_graph, prior_dep_kind_map): (&PackageGraph, &DepKindMap),
+        (post_graph, post_dep_kind_map): (&PackageGraph, &DepKindMap),
         summary_id: &SummaryId,
         summary_diff_status: &SummaryDiffStatus,
         dep_type: DependencyType,
@@ -339,10 +1146,22 @@ impl UpdateAnalyzer {
                     build_script_paths.insert(x);
                 });
 
+            let build_time_targets =
+                Self::get_build_time_targets(prior_graph, prior_dep_kind_map, &name, &old_version)?;
+
             old_version_info = Some(VersionSourceInfo {
                 version: old_version,
                 repository,
                 build_script_paths,
+                build_time_targets,
+                git_source: version_change_info
+                    .old_source
+                    .as_ref()
+                    .and_then(Self::get_git_source_info),
+                registry_index: version_change_info
+                    .old_source
+                    .as_ref()
+                    .and_then(Self::get_registry_index),
             });
         }
 
@@ -357,10 +1176,22 @@ impl UpdateAnalyzer {
                     build_script_paths.insert(x);
                 });
 
+            let build_time_targets =
+                Self::get_build_time_targets(post_graph, post_dep_kind_map, &name, &new_version)?;
+
             new_version_info = Some(VersionSourceInfo {
                 version: new_version,
                 repository,
                 build_script_paths,
+                build_time_targets,
+                git_source: version_change_info
+                    .new_source
+                    .as_ref()
+                    .and_then(Self::get_git_source_info),
+                registry_index: version_change_info
+                    .new_source
+                    .as_ref()
+                    .and_then(Self::get_registry_index),
             })
         }
 
@@ -372,6 +1203,192 @@ impl UpdateAnalyzer {
         })
     }
 
+    fn get_dependency_change_info_from_lockfile(
+        change: &LockedPackageChange,
+    ) -> DependencyChangeInfo {
+        DependencyChangeInfo {
+            name: change.name.clone(),
+            // Cargo.lock does not tell the platform a dependency is for
+            dep_type: DependencyType::Host,
+            old_version_info: change
+                .old
+                .as_ref()
+                .map(Self::get_version_source_info_from_lockfile),
+            new_version_info: change
+                .new
+                .as_ref()
+                .map(Self::get_version_source_info_from_lockfile),
+        }
+    }
+
+    // The repository and the build time targets are read from the manifest at review
+    fn get_version_source_info_from_lockfile(package: &LockedPackage) -> VersionSourceInfo {
+        let source = package
+            .source
+            .as_ref()
+            .map(|source| SummarySource::External {
+                source: source.clone(),
+            });
+        VersionSourceInfo {
+            version: package.version.clone(),
+            repository: None,
+            build_script_paths: HashSet::new(),
+            build_time_targets: BTreeSet::new(),
+            git_source: source.as_ref().and_then(Self::get_git_source_info),
+            registry_index: source.as_ref().and_then(Self::get_registry_index),
+        }
+    }
+
+    // Without a package graph, read the repository and the build time targets
+    // from the manifest of the downloaded crate.
+    // The crate is downloaded with the diff analyzer of the review
+    // so that the review reuses it
+    fn read_manifest(
+        &self,
+        diff_analyzer: &DiffAnalyzer,
+        registry: &Option<Registry>,
+        name: &str,
+        version_source_info: &VersionSourceInfo,
+    ) -> Result<VersionSourceInfo> {
+        let mut version_source_info = version_source_info.clone();
+        let registry = match registry {
+            Some(registry) if self.read_manifests => registry,
+            _ => return Ok(version_source_info),
+        };
+
+        let crate_path = diff_analyzer.get_registry_version(
+            registry,
+            name,
+            &version_source_info.version.to_string(),
+        )?;
+        let manifest = fs::read_to_string(crate_path.join("Cargo.toml"))?;
+
+        version_source_info.repository = toml::from_str::<toml::Value>(&manifest)?
+            .get("package")
+            .and_then(|package| package.get("repository"))
+            .and_then(|repository| repository.as_str())
+            .map(|repository| repository.to_string());
+        version_source_info.build_time_targets =
+            get_build_time_targets_from_manifest(&manifest, |path| {
+                crate_path.join(path).is_file()
+            })?;
+        version_source_info.build_script_paths = version_source_info
+            .build_time_targets
+            .iter()
+            .filter(|target| target.kind == BuildTimeTargetKind::BuildScript)
+            .map(|target| target.path.clone())
+            .collect();
+
+        Ok(version_source_info)
+    }
+
+    fn get_build_time_targets(
+        graph: &PackageGraph,
+        dep_kind_map: &DepKindMap,
+        crate_name: &str,
+        version: &Version,
+    ) -> Result<BTreeSet<BuildTimeTarget>> {
+        match Self::get_package(graph, crate_name, version) {
+            Some(package) => get_build_time_targets(
+                &package,
+                dep_kind_map.get(&(crate_name.to_string(), version.clone())),
+            ),
+            None => Ok(BTreeSet::new()),
+        }
+    }
+
+    /// Returns the crates newly introduced or dropped
+    /// in the dependency tree of a crate by its update
+    fn get_transitive_churn(
+        prior_graph: &PackageGraph,
+        post_graph: &PackageGraph,
+        crate_name: &str,
+        prior_version: &Version,
+        updated_version: &Version,
+    ) -> Result<Option<TransitiveChurn>> {
+        let (prior_package, post_package) = match (
+            Self::get_package(prior_graph, crate_name, prior_version),
+            Self::get_package(post_graph, crate_name, updated_version),
+        ) {
+            (Some(prior_package), Some(post_package)) => (prior_package, post_package),
+            _ => return Ok(None),
+        };
+        let prior_deps = get_package_dependencies(prior_graph, &prior_package)?;
+        let post_deps = get_package_dependencies(post_graph, &post_package)?;
+
+        Ok(Some(TransitiveChurn {
+            added: Self::get_churned_dependencies(
+                (&post_package, &post_deps, post_graph),
+                (&prior_deps, prior_graph),
+            ),
+            removed: Self::get_churned_dependencies(
+                (&prior_package, &prior_deps, prior_graph),
+                (&post_deps, post_graph),
+            ),
+        }))
+    }
+
+    // Dependencies of a package that are absent in the other graph,
+    // excluding the ones replacing another version of the crate in the other tree
+    // as those are reviewed as updates of their own
+    fn get_churned_dependencies(
+        (package, dependencies, graph): (&PackageMetadata, &[PackageMetadata], &PackageGraph),
+        (other_dependencies, other_graph): (&[PackageMetadata], &PackageGraph),
+    ) -> Vec<ChurnedDependency> {
+        let packages: HashSet<(&str, &Version)> = graph
+            .packages()
+            .map(|pkg| (pkg.name(), pkg.version()))
+            .collect();
+        let other_packages: HashSet<(&str, &Version)> = other_graph
+            .packages()
+            .map(|pkg| (pkg.name(), pkg.version()))
+            .collect();
+        let replaced_names: HashSet<&str> = other_dependencies
+            .iter()
+            .filter(|pkg| !packages.contains(&(pkg.name(), pkg.version())))
+            .map(|pkg| pkg.name())
+            .collect();
+        let tree: HashSet<&PackageId> = dependencies
+            .iter()
+            .map(|pkg| pkg.id())
+            .chain(std::iter::once(package.id()))
+            .collect();
+
+        let mut churned: Vec<ChurnedDependency> = dependencies
+            .iter()
+            .filter(|dep| {
+                !other_packages.contains(&(dep.name(), dep.version()))
+                    && !replaced_names.contains(dep.name())
+            })
+            .map(|dep| {
+                // the reverse edges that pull the crate in
+                let dependents: BTreeSet<String> = dep
+                    .reverse_direct_links()
+                    .map(|link| link.from())
+                    .filter(|from| tree.contains(from.id()))
+                    .map(|from| format!("{} {}", from.name(), from.version()))
+                    .collect();
+                ChurnedDependency {
+                    name: dep.name().to_string(),
+                    version: dep.version().clone(),
+                    dependents: dependents.into_iter().collect(),
+                }
+            })
+            .collect();
+        churned.sort();
+        churned
+    }
+
+    fn get_package<'a>(
+        graph: &'a PackageGraph,
+        crate_name: &str,
+        version: &Version,
+    ) -> Option<PackageMetadata<'a>> {
+        graph
+            .packages()
+            .find(|p| p.name() == crate_name && p.version() == version)
+    }
+
     fn get_build_script_paths(graph: &PackageGraph, crate_name: &str) -> Result<HashSet<String>> {
         let package = graph
             .packages()
@@ -398,31 +1415,215 @@ impl UpdateAnalyzer {
     ) -> VersionChangeInfo {
         let mut old_version: Option<Version> = None;
         let mut new_version: Option<Version> = None;
+        let mut old_source: Option<SummarySource> = None;
+        let mut new_source: Option<SummarySource> = None;
 
         match summary_diff_status {
             SummaryDiffStatus::Added { .. } => {
                 new_version = Some(summary_id.version.clone());
+                new_source = Some(summary_id.source.clone());
             }
             SummaryDiffStatus::Modified {
                 old_version: version,
+                old_source: source,
                 ..
             } => {
                 new_version = Some(summary_id.version.clone());
-                if version.is_some() {
-                    old_version = Some(version.unwrap().clone());
-                }
+                new_source = Some(summary_id.source.clone());
+                // old_version and old_source are None when they have not changed,
+                // e.g., only the enabled features are modified
+                old_version = Some(version.unwrap_or(&summary_id.version).clone());
+                old_source = Some(source.unwrap_or(&summary_id.source).clone());
             }
             SummaryDiffStatus::Removed { .. } => {
                 old_version = Some(summary_id.version.clone());
+                old_source = Some(summary_id.source.clone());
             }
         }
 
         VersionChangeInfo {
             old_version,
             new_version,
+            old_source,
+            new_source,
+        }
+    }
+
+    /// Returns the repository and the resolved commit for a git source
+    fn get_git_source_info(source: &SummarySource) -> Option<GitSourceInfo> {
+        match source {
+            SummarySource::External { source } => match ExternalSource::new(source)? {
+                ExternalSource::Git {
+                    repository,
+                    resolved,
+                    ..
+                } => Some(GitSourceInfo {
+                    repository: repository.to_string(),
+                    commit: resolved.to_string(),
+                }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the index url for a registry source
+    fn get_registry_index(source: &SummarySource) -> Option<String> {
+        match source {
+            SummarySource::CratesIo => Some(CRATES_IO_INDEX.to_string()),
+            // guppy does not parse sparse registry sources
+            SummarySource::External { source } if source.starts_with("sparse+") => {
+                Some(source.clone())
+            }
+            SummarySource::External { source } => match ExternalSource::new(source)? {
+                ExternalSource::Registry(index) => Some(index.to_string()),
+                _ => None,
+            },
+            _ => None,
         }
     }
 
+    fn get_registry(&self, version_source_info: &VersionSourceInfo) -> Result<Option<Registry>> {
+        let index = match &version_source_info.registry_index {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let registry_resolver = self
+            .registry_resolver
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("fatal error in loading registry resolver"))?;
+        Ok(Some(registry_resolver.resolve(index)?))
+    }
+
+    // Index-only registries have no web API, and therefore, no download stats
+    fn get_version_downloads(
+        registry: &Option<Registry>,
+        name: &str,
+        version: &Version,
+    ) -> Option<u64> {
+        let registry = registry.as_ref().filter(|registry| registry.has_api())?;
+        CratesioAnalyzer::new()
+            .ok()?
+            .with_registry(registry.clone())
+            .get_version_downloads(name, version)
+            .ok()
+    }
+
+    // The versions come from the registry web API, like the download stats
+    fn get_crate_versions(registry: &Option<Registry>, name: &str) -> Option<CrateVersions> {
+        let registry = registry.as_ref().filter(|registry| registry.has_api())?;
+        CratesioAnalyzer::new()
+            .ok()?
+            .with_registry(registry.clone())
+            .get_versions(name)
+            .ok()
+    }
+
+    fn get_release_info(
+        crate_versions: &Option<CrateVersions>,
+        version: &Version,
+    ) -> Option<ReleaseInfo> {
+        crate_versions.as_ref()?.get_release_info(version).ok()
+    }
+
+    // The owners come from the registry web API,
+    // the ownership is unavailable without it rather than failing the review
+    fn get_ownership_report(
+        registry: &Option<Registry>,
+        crate_versions: &Option<CrateVersions>,
+        name: &str,
+        old_version_info: &VersionSourceInfo,
+        new_version_info: &VersionSourceInfo,
+    ) -> Option<OwnershipReport> {
+        let registry = registry.as_ref().filter(|registry| registry.has_api())?;
+        let crate_versions = crate_versions.as_ref()?;
+        let cratesio_analyzer = CratesioAnalyzer::new()
+            .ok()?
+            .with_registry(registry.clone());
+        Some(Self::get_ownership_report_from_publishers(
+            old_version_info,
+            new_version_info,
+            &crate_versions.get_version_publishers().ok()?,
+            cratesio_analyzer.get_owners(name).ok()?,
+        ))
+    }
+
+    fn get_ownership_report_from_publishers(
+        old_version_info: &VersionSourceInfo,
+        new_version_info: &VersionSourceInfo,
+        publishers: &[VersionPublisher],
+        owners: Vec<String>,
+    ) -> OwnershipReport {
+        let old_version = &old_version_info.version;
+        let new_version = &new_version_info.version;
+        let get_publisher = |version: &Version| {
+            publishers
+                .iter()
+                .find(|publisher| &publisher.version == version)
+                .and_then(|publisher| publisher.publisher.clone())
+        };
+
+        let published_before: HashSet<&str> = publishers
+            .iter()
+            .filter(|publisher| &publisher.version <= old_version)
+            .filter_map(|publisher| publisher.publisher.as_deref())
+            .collect();
+        let published_since: HashSet<&str> = publishers
+            .iter()
+            .filter(|publisher| {
+                &publisher.version > old_version && &publisher.version <= new_version
+            })
+            .filter_map(|publisher| publisher.publisher.as_deref())
+            .collect();
+        let new_owners: Vec<String> = owners
+            .iter()
+            .filter(|owner| {
+                published_since.contains(owner.as_str())
+                    && !published_before.contains(owner.as_str())
+            })
+            .cloned()
+            .collect();
+
+        OwnershipReport {
+            prior_publisher: get_publisher(old_version),
+            updated_publisher: get_publisher(new_version),
+            owners,
+            new_owners,
+            prior_repository: old_version_info.repository.clone(),
+            updated_repository: new_version_info.repository.clone(),
+        }
+    }
+
+    // The same version from another source, i.e., crates.io and git,
+    // or two git repositories, while a git dependency
+    // moving to another commit in its repository is an update
+    fn is_source_change(
+        (old_version, old_git_source): (&Version, &Option<GitSourceInfo>),
+        (new_version, new_git_source): (&Version, &Option<GitSourceInfo>),
+    ) -> bool {
+        let get_repository = |git_source: &Option<GitSourceInfo>| {
+            git_source
+                .as_ref()
+                .map(|git_source| git_source.repository.clone())
+        };
+        old_version == new_version
+            && get_repository(old_git_source) != get_repository(new_git_source)
+    }
+
+    fn get_update_key(dep_change_info: &DependencyChangeInfo) -> Option<UpdateKey> {
+        let old = dep_change_info.old_version_info.as_ref()?;
+        let new = dep_change_info.new_version_info.as_ref()?;
+        Some((
+            dep_change_info.name.clone(),
+            old.version.clone(),
+            new.version.clone(),
+            old.git_source.clone(),
+            new.git_source.clone(),
+        ))
+    }
+
     fn get_repository_from_graph(graph: &PackageGraph, crate_name: &str) -> Option<String> {
         let package = graph.packages().find(|p| p.name() == crate_name)?;
         let repository = package.repository()?.to_string();
@@ -437,63 +1638,99 @@ impl UpdateAnalyzer {
             dep_change_info.old_version_info.as_ref(),
             dep_change_info.new_version_info.as_ref(),
         ) {
+            // Note: new_version can be lower than old_version
+            // in which case the report reviews a downgrade
             let new_version = &new_version_info.version;
             let old_version = &old_version_info.version;
 
-            if new_version < old_version {
-                return Err(anyhow!("dependency change is a downgrade - not update "));
-            }
-
             let name = &dep_change_info.name;
-            let key = (name.clone(), old_version.clone(), new_version.clone());
+            let key = Self::get_update_key(dep_change_info)
+                .ok_or_else(|| anyhow!("fatal error in computing update key"))?;
 
             if let Some(report) = self.get_update_review_report_from_cache(&key) {
                 return Ok(report);
             }
 
-            let cratesio_analyzer = CratesioAnalyzer::new()?;
-            let advisory_lookup = AdvisoryLookup::new()?;
+            let advisory_lookup = self.get_advisory_lookup()?;
+            let old_registry = self.get_registry(old_version_info)?;
+            let new_registry = self.get_registry(new_version_info)?;
+            let diff_analyzer = self.get_diff_analyzer()?;
+            let old_version_info =
+                &self.read_manifest(&diff_analyzer, &old_registry, name, old_version_info)?;
+            let new_version_info =
+                &self.read_manifest(&diff_analyzer, &new_registry, name, new_version_info)?;
+            let dep_change_info = &DependencyChangeInfo {
+                old_version_info: Some(old_version_info.clone()),
+                new_version_info: Some(new_version_info.clone()),
+                ..dep_change_info.clone()
+            };
+            // A single request lists the versions for the release info and the publishers
+            let new_crate_versions = Self::get_crate_versions(&new_registry, name);
+            let old_crate_versions = if old_registry == new_registry {
+                new_crate_versions.clone()
+            } else {
+                Self::get_crate_versions(&old_registry, name)
+            };
 
+            // Versions not from a registry, e.g., sourced from git, have no registry stats
             let prior_version = VersionInfo {
                 name: name.clone(),
                 version: old_version.clone(),
-                downloads: cratesio_analyzer.get_version_downloads(name, old_version)?,
+                downloads: Self::get_version_downloads(&old_registry, name, old_version),
                 crate_source_diff_report: None, // We do not need to do this heavy calculation
                 // for the old_version in the update report
-                known_advisories: advisory_lookup
-                    .get_crate_version_advisories(name, &old_version.to_string())?
-                    .iter()
-                    .filter(|advisory| advisory.metadata.withdrawn.is_none())
-                    .map(|advisory| Self::get_crate_version_rustsec_advisory(advisory))
-                    .collect(),
+                known_advisories: Self::get_known_advisories(&advisory_lookup, name, old_version)?,
+                git_source: old_version_info.git_source.clone(),
+                release_info: Self::get_release_info(&old_crate_versions, old_version),
             };
 
             let updated_version = VersionInfo {
                 name: name.clone(),
                 version: new_version.clone(),
-                downloads: cratesio_analyzer.get_version_downloads(name, new_version)?,
-                crate_source_diff_report: Some(DiffAnalyzer::new()?.analyze_crate_source_diff(
-                    name,
-                    &new_version.to_string(),
-                    new_version_info.repository.as_deref(),
-                )?),
-                known_advisories: advisory_lookup
-                    .get_crate_version_advisories(name, &new_version.to_string())?
-                    .iter()
-                    .filter(|advisory| advisory.metadata.withdrawn.is_none())
-                    .map(|advisory| Self::get_crate_version_rustsec_advisory(advisory))
-                    .collect(),
+                downloads: Self::get_version_downloads(&new_registry, name, new_version),
+                crate_source_diff_report: match &new_registry {
+                    Some(registry) => Some(
+                        self.get_diff_analyzer()?
+                            .with_registry(registry.clone())
+                            .analyze_crate_source_diff(
+                                name,
+                                &new_version.to_string(),
+                                new_version_info.repository.as_deref(),
+                            )?,
+                    ),
+                    None => None,
+                },
+                known_advisories: Self::get_known_advisories(&advisory_lookup, name, new_version)?,
+                git_source: new_version_info.git_source.clone(),
+                release_info: Self::get_release_info(&new_crate_versions, new_version),
             };
 
-            let diff_stats = Self::analyze_version_diff(dep_change_info)?;
+            let version_diff_review = Self::analyze_version_diff(
+                &diff_analyzer,
+                dep_change_info,
+                &old_registry,
+                &new_registry,
+            )?;
 
             let report = DepUpdateReviewReport {
                 name: dep_change_info.name.clone(),
                 prior_version,
                 updated_version,
-                diff_stats,
+                diff_stats: version_diff_review.diff_stats,
+                build_scan_report: version_diff_review.build_scan_report,
+                transitive_churn: None, // computed from the package graphs
+                semver_check_report: version_diff_review.semver_check_report,
+                ownership_report: Self::get_ownership_report(
+                    &new_registry,
+                    &new_crate_versions,
+                    name,
+                    old_version_info,
+                    new_version_info,
+                ),
+                configurations: None,   // computed from the review options
+                dependency_paths: None, // computed from the package graphs
             };
-            self.cache.borrow_mut().insert(key.clone(), report);
+            self.cache.lock().unwrap().insert(key.clone(), report);
             self.get_update_review_report_from_cache(&key)
                 .ok_or_else(|| anyhow!("fatal cache error for update analyzer"))
         } else {
@@ -503,6 +1740,107 @@ impl UpdateAnalyzer {
         }
     }
 
+    fn get_addition_review(
+        &self,
+        dep_change_info: &DependencyChangeInfo,
+    ) -> Result<DepAdditionReviewReport> {
+        let new_version_info = match (
+            dep_change_info.old_version_info.as_ref(),
+            dep_change_info.new_version_info.as_ref(),
+        ) {
+            (None, Some(new_version_info)) => new_version_info,
+            _ => {
+                return Err(anyhow!(
+                    "dependency change is either an update or removal - not addition"
+                ))
+            }
+        };
+
+        let name = &dep_change_info.name;
+        let version = &new_version_info.version;
+        let key = (name.clone(), version.clone());
+
+        if let Some(report) = self.get_addition_review_report_from_cache(&key) {
+            return Ok(report);
+        }
+
+        let advisory_lookup = self.get_advisory_lookup()?;
+        let known_advisories = Self::get_known_advisories(&advisory_lookup, name, version)?;
+
+        let report = if let Some(registry) = self.get_registry(new_version_info)? {
+            let downloads = Self::get_version_downloads(&Some(registry.clone()), name, version);
+            let crate_versions = Self::get_crate_versions(&Some(registry.clone()), name);
+            let diff_analyzer = self.get_diff_analyzer()?.with_registry(registry.clone());
+            let new_version_info =
+                &self.read_manifest(&diff_analyzer, &Some(registry), name, new_version_info)?;
+
+            let added_version = VersionInfo {
+                name: name.clone(),
+                version: version.clone(),
+                downloads,
+                crate_source_diff_report: Some(diff_analyzer.analyze_crate_source_diff(
+                    name,
+                    &version.to_string(),
+                    new_version_info.repository.as_deref(),
+                )?),
+                known_advisories,
+                git_source: None,
+                release_info: Self::get_release_info(&crate_versions, version),
+            };
+
+            // The registry code is what gets built,
+            // therefore, scan that for unsafe code
+            let unsafe_stats = diff_analyzer
+                .get_cratesio_version(name, &version.to_string())
+                .and_then(|path| Self::get_crate_unsafe_stats(&path))
+                .ok();
+
+            DepAdditionReviewReport {
+                name: name.clone(),
+                added_version,
+                build_script_paths: new_version_info.build_script_paths.clone(),
+                unsafe_stats,
+                configurations: None,
+            }
+        } else {
+            DepAdditionReviewReport {
+                name: name.clone(),
+                added_version: VersionInfo {
+                    name: name.clone(),
+                    version: version.clone(),
+                    downloads: None,
+                    crate_source_diff_report: None,
+                    known_advisories,
+                    git_source: new_version_info.git_source.clone(),
+                    release_info: None,
+                },
+                build_script_paths: new_version_info.build_script_paths.clone(),
+                unsafe_stats: None,
+                configurations: None,
+            }
+        };
+
+        self.addition_cache
+            .lock()
+            .unwrap()
+            .insert(key.clone(), report);
+        self.get_addition_review_report_from_cache(&key)
+            .ok_or_else(|| anyhow!("fatal cache error for update analyzer"))
+    }
+
+    fn get_known_advisories(
+        advisory_lookup: &AdvisoryLookup,
+        name: &str,
+        version: &Version,
+    ) -> Result<Vec<CrateVersionRustSecAdvisory>> {
+        Ok(advisory_lookup
+            .get_crate_version_advisories(name, &version.to_string())?
+            .iter()
+            .filter(|advisory| advisory.metadata.withdrawn.is_none())
+            .map(|advisory| Self::get_crate_version_rustsec_advisory(advisory))
+            .collect())
+    }
+
     fn get_crate_version_rustsec_advisory(
         advisory: &rustsec::advisory::Advisory,
     ) -> CrateVersionRustSecAdvisory {
@@ -514,8 +1852,11 @@ impl UpdateAnalyzer {
     }
 
     fn analyze_version_diff(
+        diff_analyzer: &DiffAnalyzer,
         dep_change_info: &DependencyChangeInfo,
-    ) -> Result<Option<VersionDiffStats>> {
+        old_registry: &Option<Registry>,
+        new_registry: &Option<Registry>,
+    ) -> Result<VersionDiffReview> {
         if let (name, Some(old_version_info), Some(new_version_info)) = (
             &dep_change_info.name,
             &dep_change_info.old_version_info,
@@ -523,19 +1864,49 @@ impl UpdateAnalyzer {
         ) {
             let new_version = &new_version_info.version;
             let old_version = &old_version_info.version;
-            let diff_analyzer = DiffAnalyzer::new()?;
 
-            if let (Ok(repo_old_version), Ok(repo_new_version)) = (
-                diff_analyzer.get_git_repo_for_cratesio_version(name, &old_version.to_string()),
-                diff_analyzer.get_git_repo_for_cratesio_version(name, &new_version.to_string()),
+            if let (Some(old_git_source), Some(new_git_source)) =
+                (&old_version_info.git_source, &new_version_info.git_source)
+            {
+                // Diff the two resolved commits for a dependency sourced from git
+                let repo = diff_analyzer.get_git_repo(name, &new_git_source.repository)?;
+                diff_analyzer.fetch_git_commit(
+                    &repo,
+                    &old_git_source.repository,
+                    &old_git_source.commit,
+                )?;
+                diff_analyzer.fetch_git_commit(
+                    &repo,
+                    &new_git_source.repository,
+                    &new_git_source.commit,
+                )?;
+                let version_diff_info = diff_analyzer.get_git_commit_diff_info(
+                    name,
+                    &repo,
+                    &old_git_source.commit,
+                    &new_git_source.commit,
+                )?;
+                Self::get_version_diff_review(dep_change_info, &version_diff_info)
+            } else if let (Some(Ok(repo_old_version)), Some(Ok(repo_new_version))) = (
+                old_registry.as_ref().map(|registry| {
+                    diff_analyzer.get_git_repo_for_registry_version(
+                        registry,
+                        name,
+                        &old_version.to_string(),
+                    )
+                }),
+                new_registry.as_ref().map(|registry| {
+                    diff_analyzer.get_git_repo_for_registry_version(
+                        registry,
+                        name,
+                        &new_version.to_string(),
+                    )
+                }),
             ) {
-                // Get version diff info from crates.io if avalaiable on crates.io
+                // Get version diff info from the registry, e.g., crates.io, if avalaiable
                 let version_diff_info = diff_analyzer
                     .get_version_diff_info_between_repos(&repo_old_version, &repo_new_version)?;
-                Ok(Some(Self::get_version_diff_stats(
-                    dep_change_info,
-                    &version_diff_info,
-                )?))
+                Self::get_version_diff_review(dep_change_info, &version_diff_info)
             } else if let Some(repository) = &new_version_info.repository {
                 // Get version diff info from git source if avaialbe
                 // We take here the repo for the new version as the latest source
@@ -549,22 +1920,103 @@ impl UpdateAnalyzer {
                     Ok(info) => info,
                     Err(error) => {
                         match error.root_cause().downcast_ref::<HeadCommitNotFoundError>() {
-                            Some(_err) => return Ok(None),
+                            Some(_err) => return Ok(VersionDiffReview::default()),
                             None => return Err(anyhow!("fatal error in fetching head commit")),
                         }
                     }
                 };
-                Ok(Some(Self::get_version_diff_stats(
-                    dep_change_info,
-                    &version_diff_info,
-                )?))
+                Self::get_version_diff_review(dep_change_info, &version_diff_info)
             } else {
-                Ok(None)
+                Ok(VersionDiffReview::default())
             }
         } else {
             // If old version, or new version is none, there is no update diff
-            Ok(None)
+            Ok(VersionDiffReview::default())
+        }
+    }
+
+    fn get_version_diff_review(
+        dep_change_info: &DependencyChangeInfo,
+        version_diff_info: &VersionDiffInfo,
+    ) -> Result<VersionDiffReview> {
+        Ok(VersionDiffReview {
+            diff_stats: Some(Self::get_version_diff_stats(
+                dep_change_info,
+                version_diff_info,
+            )?),
+            build_scan_report: Self::get_build_scan_report(dep_change_info, version_diff_info)?,
+            semver_check_report: Self::get_semver_check_report(dep_change_info, version_diff_info)?,
+        })
+    }
+
+    // Scan the build time code of the new version at the end of the diff
+    fn get_build_scan_report(
+        dep_change_info: &DependencyChangeInfo,
+        version_diff_info: &VersionDiffInfo,
+    ) -> Result<Option<BuildScanReport>> {
+        let targets = match &dep_change_info.new_version_info {
+            Some(info) if !info.build_time_targets.is_empty() => &info.build_time_targets,
+            _ => return Ok(None),
+        };
+
+        let read_file = Self::get_crate_file_reader(
+            version_diff_info.repo,
+            version_diff_info.commit_b,
+            &version_diff_info.crate_path_b,
+        )?;
+
+        let report = scan_build_time_targets(targets, read_file);
+        if report.scanned_files.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(report))
+    }
+
+    // Compare the public API of the library at both ends of the diff
+    fn get_semver_check_report(
+        dep_change_info: &DependencyChangeInfo,
+        version_diff_info: &VersionDiffInfo,
+    ) -> Result<Option<SemverCheckReport>> {
+        let (old_version, new_version) = match (
+            &dep_change_info.old_version_info,
+            &dep_change_info.new_version_info,
+        ) {
+            (Some(old), Some(new)) => (&old.version, &new.version),
+            _ => return Ok(None),
+        };
+
+        let mut apis: Vec<PublicApi> = Vec::new();
+        for (commit, crate_path) in [
+            (version_diff_info.commit_a, &version_diff_info.crate_path_a),
+            (version_diff_info.commit_b, &version_diff_info.crate_path_b),
+        ] {
+            let read_file =
+                Self::get_crate_file_reader(version_diff_info.repo, commit, crate_path)?;
+            let lib_path = get_lib_path(read_file("Cargo.toml").as_deref());
+            if read_file(&lib_path).is_none() {
+                return Ok(None);
+            }
+            apis.push(get_public_api(&lib_path, read_file));
         }
+
+        Ok(Some(get_semver_check_report(
+            (old_version, &apis[0]),
+            (new_version, &apis[1]),
+        )))
+    }
+
+    // Reads a file relative to the crate directory at a commit
+    fn get_crate_file_reader<'a>(
+        repo: &'a Repository,
+        commit: Oid,
+        crate_path: &'a Path,
+    ) -> Result<impl Fn(&str) -> Option<String> + 'a> {
+        let tree = repo.find_commit(commit)?.tree()?;
+        Ok(move |path: &str| -> Option<String> {
+            let entry = tree.get_path(&crate_path.join(path)).ok()?;
+            let blob = entry.to_object(repo).ok()?.peel_to_blob().ok()?;
+            String::from_utf8(blob.content().to_vec()).ok()
+        })
     }
 
     fn get_version_diff_stats(
@@ -572,16 +2024,22 @@ impl UpdateAnalyzer {
         version_diff_info: &VersionDiffInfo,
     ) -> Result<VersionDiffStats> {
         let mut files_changed: HashSet<String> = HashSet::new();
+        let mut native_artifacts = NativeArtifacts::default();
         for diff_delta in version_diff_info.diff.deltas() {
-            files_changed.insert(
-                diff_delta
-                    .new_file()
-                    .path()
-                    .or_else(|| diff_delta.old_file().path())
-                    .and_then(|path| path.to_str())
-                    .ok_or_else(|| anyhow!("fatal error: diff contains no files"))?
-                    .to_string(),
-            );
+            let path = diff_delta
+                .new_file()
+                .path()
+                .or_else(|| diff_delta.old_file().path())
+                .and_then(|path| path.to_str())
+                .ok_or_else(|| anyhow!("fatal error: diff contains no files"))?
+                .to_string();
+            if diff_delta.status() == Delta::Added {
+                // a submodule is added as a commit, not a blob
+                if let Ok(blob) = version_diff_info.repo.find_blob(diff_delta.new_file().id()) {
+                    native_artifacts.add_file(&path, blob.content());
+                }
+            }
+            files_changed.insert(path);
         }
 
         let mut build_script_paths: HashSet<String> = HashSet::new();
@@ -613,9 +2071,14 @@ impl UpdateAnalyzer {
             unsafe_file_changed: files_unsafe_change_stats
                 .into_iter()
                 .filter(|report| {
-                    report.unsafe_change_status != FileUnsafeCodeChangeStatus::NoUnsafeCode
+                    !matches!(
+                        report.unsafe_change_status,
+                        FileUnsafeCodeChangeStatus::NoUnsafeCode
+                            | FileUnsafeCodeChangeStatus::UnsafeCodeUnchanged
+                    )
                 })
                 .collect(),
+            native_artifacts,
         })
     }
 
@@ -640,13 +2103,18 @@ impl UpdateAnalyzer {
     fn get_file_unsafe_change_status(
         rs_file_metrics: &Option<RsFileMetrics>,
         unsafe_delta: &UnsafeDelta,
+        unsafe_item_changes: Option<&[UnsafeItemChange]>, // None if the file could not be parsed
     ) -> FileUnsafeCodeChangeStatus {
         if let Some(rs_file_metrics) = rs_file_metrics {
             match (
                 unsafe_delta.has_no_change(),
                 rs_file_metrics.counters.has_unsafe(),
             ) {
-                (true, true) => FileUnsafeCodeChangeStatus::Uncertain,
+                (true, true) => match unsafe_item_changes {
+                    Some([]) => FileUnsafeCodeChangeStatus::UnsafeCodeUnchanged,
+                    Some(_) => FileUnsafeCodeChangeStatus::UnsafeCodeModified,
+                    None => FileUnsafeCodeChangeStatus::UnsafeCodeNotAnalyzed,
+                },
                 (true, false) => FileUnsafeCodeChangeStatus::NoUnsafeCode,
                 (false, true) => FileUnsafeCodeChangeStatus::UnsafeCounterModified,
                 (false, false) => FileUnsafeCodeChangeStatus::AllUnsafeCodeRemoved,
@@ -684,7 +2152,7 @@ impl UpdateAnalyzer {
         for diff_delta in version_diff_info.diff.deltas() {
             if let Some(path) = diff_delta.old_file().path() {
                 let old_file_unsafe_stats = geiger::find::find_unsafe_in_file(
-                    &repo_path.join(path),
+                    &repo_path.join(&version_diff_info.crate_path_a).join(path),
                     geiger::IncludeTests::No,
                 )
                 .ok();
@@ -703,7 +2171,7 @@ impl UpdateAnalyzer {
         for diff_delta in version_diff_info.diff.deltas() {
             if let Some(path) = diff_delta.new_file().path() {
                 let new_file_unsafe_stats = geiger::find::find_unsafe_in_file(
-                    &repo_path.join(path),
+                    &repo_path.join(&version_diff_info.crate_path_b).join(path),
                     geiger::IncludeTests::No,
                 )
                 .ok();
@@ -713,7 +2181,7 @@ impl UpdateAnalyzer {
 
         // Calculate changes in unsafe counter for each file
         let mut files_unsafe_change_stats: Vec<FileUnsafeChangeStats> = Vec::new();
-        for diff_delta in version_diff_info.diff.deltas() {
+        for (idx, diff_delta) in version_diff_info.diff.deltas().enumerate() {
             let old_file_unsafe_stats = diff_delta
                 .old_file()
                 .path()
@@ -733,6 +2201,23 @@ impl UpdateAnalyzer {
             let unsafe_delta = Self::get_unsafe_delta_from_rs_file_metrics(&new_file_unsafe_stats)
                 - Self::get_unsafe_delta_from_rs_file_metrics(&old_file_unsafe_stats);
             let unsafe_status = new_file_unsafe_stats;
+
+            // Map the changed lines onto the unsafe items of the file
+            let unsafe_item_changes =
+                Self::get_blob_content(version_diff_info, &diff_delta.old_file())
+                    .and_then(|old_source| {
+                        let new_source =
+                            Self::get_blob_content(version_diff_info, &diff_delta.new_file())?;
+                        let patch = Patch::from_diff(&version_diff_info.diff, idx)?
+                            .ok_or_else(|| anyhow!("no patch for {:?}", diff_delta))?;
+                        get_file_unsafe_item_changes(
+                            old_source.as_deref(),
+                            new_source.as_deref(),
+                            &patch,
+                        )
+                    })
+                    .ok();
+
             files_unsafe_change_stats.push(FileUnsafeChangeStats {
                 file: diff_delta
                     .new_file()
@@ -747,8 +2232,10 @@ impl UpdateAnalyzer {
                 unsafe_change_status: Self::get_file_unsafe_change_status(
                     &unsafe_status,
                     &unsafe_delta,
+                    unsafe_item_changes.as_deref(),
                 ),
                 unsafe_delta,
+                unsafe_item_changes: unsafe_item_changes.unwrap_or_default(),
                 unsafe_status,
             })
         }
@@ -759,6 +2246,18 @@ impl UpdateAnalyzer {
         Ok(files_unsafe_change_stats)
     }
 
+    // Returns None for an absent file, e.g., the old file of an added file
+    fn get_blob_content(
+        version_diff_info: &VersionDiffInfo,
+        diff_file: &DiffFile,
+    ) -> Result<Option<String>> {
+        if diff_file.id().is_zero() {
+            return Ok(None);
+        }
+        let blob = version_diff_info.repo.find_blob(diff_file.id())?;
+        Ok(Some(String::from_utf8(blob.content().to_vec())?))
+    }
+
     fn get_unsafe_delta_from_rs_file_metrics(
         rs_file_metrics: &Option<RsFileMetrics>,
     ) -> UnsafeDelta {
@@ -774,11 +2273,82 @@ impl UpdateAnalyzer {
         }
     }
 
+    /// Scans all the rust files within a crate directory
+    /// and sums up the unsafe code counters
+    fn get_crate_unsafe_stats(crate_path: &Path) -> Result<CrateUnsafeStats> {
+        let mut stats = CrateUnsafeStats::default();
+
+        for entry in WalkDir::new(crate_path)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git")
+        {
+            let entry = entry?;
+            let path = entry.path();
+            if !entry.file_type().is_file()
+                || path.extension().and_then(|ext| ext.to_str()) != Some("rs")
+            {
+                continue;
+            }
+
+            // Skip files that geiger fails to parse
+            let rs_file_metrics =
+                match geiger::find::find_unsafe_in_file(path, geiger::IncludeTests::No) {
+                    Ok(rs_file_metrics) => rs_file_metrics,
+                    Err(_) => continue,
+                };
+
+            stats.rust_files += 1;
+            let counters = &rs_file_metrics.counters;
+            if counters.has_unsafe() {
+                stats.files_with_unsafe.insert(
+                    path.strip_prefix(crate_path)?
+                        .to_str()
+                        .ok_or_else(|| anyhow!("invalid unicode in path: {:?}", path))?
+                        .to_string(),
+                );
+            }
+            stats.unsafe_details.functions += counters.functions.unsafe_ as u64;
+            stats.unsafe_details.expressions += counters.exprs.unsafe_ as u64;
+            stats.unsafe_details.impls += counters.item_impls.unsafe_ as u64;
+            stats.unsafe_details.traits += counters.item_traits.unsafe_ as u64;
+            stats.unsafe_details.methods += counters.methods.unsafe_ as u64;
+        }
+
+        Ok(stats)
+    }
+
     fn get_update_review_report_from_cache(
         &self,
-        key: &(String, Version, Version),
+        key: &UpdateKey,
     ) -> Option<DepUpdateReviewReport> {
-        self.cache.borrow().get(key).cloned()
+        self.cache.lock().unwrap().get(key).cloned()
+    }
+
+    fn cmp_dep_update_review_reports(
+        a: &DepUpdateReviewReport,
+        b: &DepUpdateReviewReport,
+    ) -> Ordering {
+        (
+            &a.name,
+            &a.prior_version.version,
+            &a.updated_version.version,
+            &a.prior_version.git_source,
+            &a.updated_version.git_source,
+        )
+            .cmp(&(
+                &b.name,
+                &b.prior_version.version,
+                &b.updated_version.version,
+                &b.prior_version.git_source,
+                &b.updated_version.git_source,
+            ))
+    }
+
+    fn get_addition_review_report_from_cache(
+        &self,
+        key: &(String, Version),
+    ) -> Option<DepAdditionReviewReport> {
+        self.addition_cache.lock().unwrap().get(key).cloned()
     }
 }
 
@@ -791,15 +2361,29 @@ impl Default for UpdateAnalyzer {
 #[cfg(test)]
 mod test {
     use super::{
-        DependencyType, DiffAnalyzer, FileUnsafeCodeChangeStatus, PackageGraph, StandardFeatures,
-        UpdateAnalyzer, VersionConflict::DirectTransitiveVersionConflict,
+        AdvisoryLookup, Arc, BTreeSet, BuildTimeTarget, CargoConfig, ChurnedDependency, Delta,
+        DependencyChangeInfo, DependencyType, DiffAnalyzer, FileUnsafeChangeStats,
+        FileUnsafeCodeChangeStatus, GitSourceInfo, HashSet, PackageGraph, RegistryResolver,
+        ReviewOptions, StandardFeatures, SummarySource, UnsafeDelta, UnsafeItemChange,
+        UpdateAnalyzer, VersionConflict,
+        VersionConflict::{
+            DirectTransitiveVersionConflict, DuplicateIntroduced, LinksCollision,
+            RegistryAndGitSources, SemverIncompatibleVersions,
+        },
+        VersionDiffReview, VersionPublisher, VersionSourceInfo, CRATES_IO_INDEX,
     };
+    use crate::build_scan::{BuildBehaviour, BuildTimeTargetKind};
     use crate::diff::trim_remote_url;
+    use crate::file_kind::BinaryFormat;
+    use crate::registry::test::serve_http;
+    use crate::semver_check::ApiChangeType;
+    use crate::unsafe_items::{UnsafeItemChangeType, UnsafeItemKind};
     use guppy::{CargoMetadata, MetadataCommand};
     use once_cell::sync::Lazy;
     use semver::Version;
     use serial_test::serial;
-    use std::path::PathBuf;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
     use std::sync::Once;
 
     struct PackageGraphPair {
@@ -922,33 +2506,289 @@ mod test {
                 .filter(|dep| matches!(dep.dep_type, DependencyType::Target))
                 .count()
         );
-
-        // Deps added
+
+        // Deps added, cc and libc only have their features changed
+        assert_eq!(
+            6,
+            dep_change_infos
+                .iter()
+                .filter(|dep| dep.old_version_info.is_none() && dep.new_version_info.is_some())
+                .count()
+        );
+
+        // Deps removed
+        assert_eq!(
+            10,
+            dep_change_infos
+                .iter()
+                .filter(|dep| dep.old_version_info.is_some() && dep.new_version_info.is_none())
+                .count()
+        );
+
+        // Deps version changed
+        assert_eq!(
+            2,
+            dep_change_infos
+                .iter()
+                .filter(|dep| match (&dep.old_version_info, &dep.new_version_info) {
+                    (Some(old), Some(new)) => old.version != new.version,
+                    _ => false,
+                })
+                .count()
+        );
+
+        // Deps with only feature changes
+        assert_eq!(
+            2,
+            dep_change_infos
+                .iter()
+                .filter(|dep| match (&dep.old_version_info, &dep.new_version_info) {
+                    (Some(old), Some(new)) => old.version == new.version,
+                    _ => false,
+                })
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_update_compare_package_graph_workspace_members() {
+        // A path dependency inside the workspace directory becomes a member
+        let get_graph = |dir: &Path, with_member: bool| {
+            std::fs::create_dir_all(dir.join("src")).unwrap();
+            std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+            let mut manifest =
+                "[workspace]\n[package]\nname = \"app\"\nversion = \"0.1.0\"\n".to_string();
+            if with_member {
+                std::fs::create_dir_all(dir.join("inner/src")).unwrap();
+                std::fs::write(dir.join("inner/src/lib.rs"), "").unwrap();
+                std::fs::write(
+                    dir.join("inner/Cargo.toml"),
+                    "[package]\nname = \"inner\"\nversion = \"0.1.0\"\n",
+                )
+                .unwrap();
+                manifest.push_str("[dependencies]\ninner = { path = \"inner\" }\n");
+            }
+            std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+            guppy::MetadataCommand::new()
+                .current_dir(dir)
+                .build_graph()
+                .unwrap()
+        };
+        let prior_dir = tempfile::tempdir().unwrap();
+        let prior = get_graph(prior_dir.path(), false);
+        let post_dir = tempfile::tempdir().unwrap();
+        let post = get_graph(post_dir.path(), true);
+        assert!(post.workspace().member_by_name("inner").is_ok());
+
+        let dep_change_infos = UpdateAnalyzer::compare_pacakge_graphs(
+            &prior,
+            &post,
+            &UpdateAnalyzer::get_default_cargo_options(),
+            StandardFeatures::All,
+        )
+        .unwrap();
+        assert!(dep_change_infos.is_empty());
+    }
+
+    // A crate with path dependencies only on a platform, as a dev dep, or behind a feature
+    fn get_test_graph_with_deps(dir: &Path, deps: &str) -> PackageGraph {
+        for name in &["app", "winonly", "devonly", "featdep"] {
+            let crate_dir = dir.join(name);
+            std::fs::create_dir_all(crate_dir.join("src")).unwrap();
+            std::fs::write(crate_dir.join("src/lib.rs"), "").unwrap();
+            std::fs::write(
+                crate_dir.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n{}",
+                    name,
+                    if *name == "app" { deps } else { "" }
+                ),
+            )
+            .unwrap();
+        }
+        guppy::MetadataCommand::new()
+            .current_dir(dir.join("app"))
+            .build_graph()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_update_review_options() {
+        let prior_dir = tempfile::tempdir().unwrap();
+        let prior = get_test_graph_with_deps(prior_dir.path(), "");
+        let post_dir = tempfile::tempdir().unwrap();
+        let post = get_test_graph_with_deps(
+            post_dir.path(),
+            r#"
+            [dependencies]
+            featdep = { path = "../featdep", optional = true }
+            [features]
+            extra = ["featdep"]
+            [target.'cfg(windows)'.dependencies]
+            winonly = { path = "../winonly" }
+            [dev-dependencies]
+            devonly = { path = "../devonly" }
+            "#,
+        );
+
+        let get_added = |review_options: &ReviewOptions| {
+            let (dep_change_infos, configurations) =
+                UpdateAnalyzer::compare_package_graphs_for_review_options(
+                    &prior,
+                    &post,
+                    review_options,
+                )
+                .unwrap();
+            let added: BTreeSet<String> = dep_change_infos
+                .iter()
+                .filter(|dep| dep.old_version_info.is_none())
+                .map(|dep| dep.name.clone())
+                .collect();
+            let configurations: Vec<(String, bool)> = configurations
+                .into_iter()
+                .map(|(name, packages)| {
+                    let has_winonly = packages
+                        .contains(&("winonly".to_string(), Version::parse("0.1.0").unwrap()));
+                    (name, has_winonly)
+                })
+                .collect();
+            (added.into_iter().collect::<Vec<String>>(), configurations)
+        };
+
+        // All features and dev deps for any platform by default
+        assert_eq!(
+            get_added(&ReviewOptions::default()),
+            (
+                vec![
+                    "devonly".to_string(),
+                    "featdep".to_string(),
+                    "winonly".to_string()
+                ],
+                vec![("any platform".to_string(), true)]
+            )
+        );
+        // the same packages as analyze_updates reviews
+        let added: BTreeSet<String> = UpdateAnalyzer::compare_pacakge_graphs(
+            &prior,
+            &post,
+            &UpdateAnalyzer::get_default_cargo_options(),
+            StandardFeatures::All,
+        )
+        .unwrap()
+        .into_iter()
+        .filter(|dep| dep.old_version_info.is_none())
+        .map(|dep| dep.name)
+        .collect();
         assert_eq!(
-            8,
-            dep_change_infos
-                .iter()
-                .filter(|dep| dep.old_version_info.is_none() && dep.new_version_info.is_some())
-                .count()
+            added.into_iter().collect::<Vec<String>>(),
+            get_added(&ReviewOptions::default()).0
         );
-
-        // Deps removed
+        assert!(!ReviewOptions::default().narrows_review());
+        assert!(ReviewOptions {
+            exclude_dev: true,
+            ..Default::default()
+        }
+        .narrows_review());
+
+        let (added, configurations) = get_added(&ReviewOptions {
+            targets: vec![
+                "x86_64-unknown-linux-gnu".to_string(),
+                "x86_64-pc-windows-msvc".to_string(),
+            ],
+            no_default_features: true,
+            exclude_dev: true,
+            ..Default::default()
+        });
+        assert_eq!(added, vec!["winonly"]);
         assert_eq!(
-            10,
-            dep_change_infos
-                .iter()
-                .filter(|dep| dep.old_version_info.is_some() && dep.new_version_info.is_none())
-                .count()
+            configurations,
+            vec![
+                ("x86_64-unknown-linux-gnu".to_string(), false),
+                ("x86_64-pc-windows-msvc".to_string(), true),
+            ]
         );
 
-        // Deps version changed
+        let (added, _) = get_added(&ReviewOptions {
+            targets: vec!["x86_64-unknown-linux-gnu".to_string()],
+            features: vec!["extra".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(added, vec!["devonly", "featdep"]);
+
+        let (added, _) = get_added(&ReviewOptions {
+            members: vec!["app".to_string()],
+            exclude_dev: true,
+            ..Default::default()
+        });
+        assert_eq!(added, vec!["featdep", "winonly"]);
+
+        assert!(UpdateAnalyzer::compare_package_graphs_for_review_options(
+            &prior,
+            &post,
+            &ReviewOptions {
+                members: vec!["unknown".to_string()],
+                ..Default::default()
+            }
+        )
+        .is_err());
+        assert!(UpdateAnalyzer::compare_package_graphs_for_review_options(
+            &prior,
+            &post,
+            &ReviewOptions {
+                targets: vec!["not-a-target".to_string()],
+                ..Default::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_update_transitive_churn() {
+        // cargo_metadata is updated on its own and camino is already present
+        let package_graph_pair = get_test_graph_pair_guppy();
+        let churn = UpdateAnalyzer::get_transitive_churn(
+            &package_graph_pair.prior,
+            &package_graph_pair.post,
+            "guppy",
+            &Version::parse("0.8.0").unwrap(),
+            &Version::parse("0.9.0").unwrap(),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(churn.added.is_empty());
+        assert!(churn.removed.is_empty());
+
+        // target-spec 0.6.1 stays as a direct dependency
+        // while guppy 0.9.0 brings in a second copy
+        let package_graph_pair = get_test_graph_pair_conflict();
+        let churn = UpdateAnalyzer::get_transitive_churn(
+            &package_graph_pair.prior,
+            &package_graph_pair.post,
+            "guppy",
+            &Version::parse("0.7.2").unwrap(),
+            &Version::parse("0.9.0").unwrap(),
+        )
+        .unwrap()
+        .unwrap();
         assert_eq!(
-            2,
-            dep_change_infos
-                .iter()
-                .filter(|dep| dep.old_version_info.is_some() && dep.new_version_info.is_some())
-                .count()
+            churn.added,
+            vec![ChurnedDependency {
+                name: "target-spec".to_string(),
+                version: Version::parse("0.7.0").unwrap(),
+                dependents: vec!["guppy 0.9.0".to_string()],
+            }]
         );
+        assert!(churn.removed.is_empty());
+
+        assert!(UpdateAnalyzer::get_transitive_churn(
+            &package_graph_pair.prior,
+            &package_graph_pair.post,
+            "guppy",
+            &Version::parse("0.7.2").unwrap(),
+            &Version::parse("0.8.0").unwrap(),
+        )
+        .unwrap()
+        .is_none());
     }
 
     #[test]
@@ -1016,6 +2856,204 @@ mod test {
         println!("{:?}", update_review_reports);
     }
 
+    #[test]
+    fn test_update_review_report_addition_and_removal() {
+        let package_graph_pair = get_test_graph_pair_guppy();
+        let update_analyzer = get_test_update_analyzer();
+        let update_review_reports = update_analyzer
+            .analyze_updates(&package_graph_pair.prior, &package_graph_pair.post)
+            .unwrap();
+
+        // jobserver is added both as a host and a target dep
+        assert_eq!(update_review_reports.dep_addition_review_reports.len(), 5);
+        let report = update_review_reports
+            .dep_addition_review_reports
+            .iter()
+            .find(|report| report.name == "libgit2-sys")
+            .unwrap();
+        assert_eq!(
+            report.added_version.version,
+            Version::parse("0.12.21+1.1.0").unwrap()
+        );
+        assert!(report.added_version.downloads.unwrap() > 0);
+        assert!(report.added_version.crate_source_diff_report.is_some());
+        assert!(report.build_script_paths.contains("build.rs"));
+        assert!(report.unsafe_stats.as_ref().unwrap().rust_files > 0);
+
+        // version_check is removed both as a host and a target dep
+        assert_eq!(update_review_reports.dep_removal_reports.len(), 9);
+        assert_eq!(
+            update_review_reports
+                .dep_removal_reports
+                .iter()
+                .filter(|report| report.name == "version_check")
+                .count(),
+            1
+        );
+        assert!(update_review_reports
+            .dep_removal_reports
+            .iter()
+            .any(|report| report.name == "octocrab"
+                && report.version == Version::parse("0.9.1").unwrap()));
+    }
+
+    #[test]
+    fn test_update_review_report_downgrade() {
+        // Reverse the libc update to get a downgrade
+        let package_graph_pair = get_test_graph_pair_libc();
+        let update_analyzer = get_test_update_analyzer();
+        let update_review_reports = update_analyzer
+            .analyze_updates(&package_graph_pair.post, &package_graph_pair.prior)
+            .unwrap();
+        assert!(update_review_reports.dep_update_review_reports.is_empty());
+        assert_eq!(update_review_reports.dep_downgrade_review_reports.len(), 1);
+
+        let report = &update_review_reports.dep_downgrade_review_reports[0];
+        assert_eq!(
+            report.prior_version.version,
+            Version::parse("0.2.93").unwrap()
+        );
+        assert_eq!(
+            report.updated_version.version,
+            Version::parse("0.2.92").unwrap()
+        );
+        // The diff stats are the reverse of the update
+        assert_eq!(report.diff_stats.as_ref().unwrap().insertions, 4942);
+        assert_eq!(report.diff_stats.as_ref().unwrap().deletions, 1333);
+    }
+
+    #[test]
+    fn test_update_rustsec_downgrade() {
+        // Reverse the tokio update to re-introduce RUSTSEC-2021-0072
+        let package_graph_pair = get_test_graph_pair_rustsec();
+        let update_analyzer = get_test_update_analyzer();
+        let reports = update_analyzer
+            .analyze_updates(&package_graph_pair.post, &package_graph_pair.prior)
+            .unwrap();
+        let report = reports
+            .dep_downgrade_review_reports
+            .iter()
+            .find(|report| report.name == "tokio")
+            .unwrap();
+
+        assert!(!report
+            .prior_version
+            .known_advisories
+            .iter()
+            .any(|adv| adv.id == "RUSTSEC-2021-0072"));
+        assert!(report
+            .updated_version
+            .known_advisories
+            .iter()
+            .any(|adv| adv.id == "RUSTSEC-2021-0072"));
+    }
+
+    #[test]
+    fn test_update_file_unsafe_change_stats_serde() {
+        let path = PathBuf::from("resources/test/unsafe_code/src/lib.rs");
+        let metrics = geiger::find_unsafe_in_file(&path, geiger::IncludeTests::No).unwrap();
+        let stats = FileUnsafeChangeStats {
+            file: "src/lib.rs".to_string(),
+            change_type: Delta::Renamed,
+            unsafe_change_status: FileUnsafeCodeChangeStatus::UnsafeCodeModified,
+            unsafe_delta: UnsafeDelta::default(),
+            unsafe_item_changes: vec![UnsafeItemChange {
+                kind: UnsafeItemKind::Block,
+                name: Some("f".to_string()),
+                change_type: UnsafeItemChangeType::Modified,
+                old_lines: Some((3, 5)),
+                new_lines: Some((3, 6)),
+            }],
+            unsafe_status: Some(metrics.clone()),
+        };
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let stats: FileUnsafeChangeStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(stats.change_type, Delta::Renamed);
+        assert_eq!(stats.unsafe_status, Some(metrics));
+        assert_eq!(stats.unsafe_item_changes[0].new_lines, Some((3, 6)));
+
+        let stats = FileUnsafeChangeStats {
+            unsafe_status: None,
+            ..stats
+        };
+        let json = serde_json::to_string(&stats).unwrap();
+        let stats: FileUnsafeChangeStats = serde_json::from_str(&json).unwrap();
+        assert!(stats.unsafe_status.is_none());
+    }
+
+    #[test]
+    fn test_update_review_report_ordering() {
+        // Reviews run concurrently, but the report order stays the same
+        let package_graph_pair = get_test_graph_pair_guppy();
+        let get_names = |workers: usize| -> (Vec<String>, Vec<String>) {
+            let report = get_test_update_analyzer()
+                .with_workers(workers)
+                .analyze_updates(&package_graph_pair.prior, &package_graph_pair.post)
+                .unwrap();
+            (
+                report
+                    .dep_update_review_reports
+                    .iter()
+                    .map(|r| r.name.clone())
+                    .collect(),
+                report
+                    .dep_addition_review_reports
+                    .iter()
+                    .map(|r| r.name.clone())
+                    .collect(),
+            )
+        };
+
+        let (updated, added) = get_names(8);
+        assert!(updated.contains(&"guppy".to_string()));
+        for names in &[&updated, &added] {
+            let mut sorted_names = names.to_vec();
+            sorted_names.sort();
+            assert_eq!(names.to_vec(), sorted_names);
+        }
+        assert_eq!(get_names(1), (updated, added));
+    }
+
+    #[test]
+    fn test_update_analyzer_is_sync() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<UpdateAnalyzer>();
+        assert_eq!(UpdateAnalyzer::new().with_workers(0).workers, 1);
+    }
+
+    #[test]
+    fn test_update_shared_advisory_lookup() {
+        let advisory_lookup =
+            AdvisoryLookup::from_path(Path::new("resources/test/advisory_db")).unwrap();
+        let update_analyzer = UpdateAnalyzer::with_advisory_lookup(advisory_lookup);
+
+        let lookup_a = update_analyzer.get_advisory_lookup().unwrap();
+        let lookup_b = update_analyzer.get_advisory_lookup().unwrap();
+        assert!(Arc::ptr_eq(&lookup_a, &lookup_b));
+
+        let advisories = UpdateAnalyzer::get_known_advisories(
+            &lookup_a,
+            "tokio",
+            &Version::parse("1.7.1").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].id, "RUSTSEC-2021-0072");
+    }
+
+    #[test]
+    fn test_update_crate_unsafe_stats() {
+        let stats = UpdateAnalyzer::get_crate_unsafe_stats(Path::new("resources/test/unsafe_code"))
+            .unwrap();
+        assert_eq!(stats.rust_files, 2);
+        assert_eq!(stats.files_with_unsafe.len(), 1);
+        assert!(stats.files_with_unsafe.contains("src/lib.rs"));
+        assert_eq!(stats.unsafe_details.functions, 1);
+        assert_eq!(stats.unsafe_details.impls, 1);
+        assert!(stats.unsafe_details.expressions > 0);
+    }
+
     #[test]
     fn test_update_review_report_libc() {
         let package_graph_pair = get_test_graph_pair_libc();
@@ -1106,11 +3144,67 @@ mod test {
         assert_eq!(version_conflicts.len(), 1);
 
         let conflict = version_conflicts.get(0).unwrap();
-        match conflict {
-            DirectTransitiveVersionConflict { name, .. } => {
+        assert!(
+            matches!(conflict, DirectTransitiveVersionConflict { name, .. } if name == "target-spec")
+        );
+    }
+
+    #[test]
+    fn test_update_graph_version_conflicts() {
+        let package_graph_pair = get_test_graph_pair_conflict();
+        let version_conflicts = UpdateAnalyzer::determine_graph_version_conflicts(
+            &package_graph_pair.prior,
+            &package_graph_pair.post,
+        )
+        .unwrap();
+
+        let semver_incompatible: Vec<&str> = version_conflicts
+            .iter()
+            .filter_map(|conflict| match conflict {
+                SemverIncompatibleVersions { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            semver_incompatible,
+            vec![
+                "itertools",
+                "proc-macro2",
+                "quote",
+                "syn",
+                "target-spec",
+                "unicode-xid"
+            ]
+        );
+
+        // guppy 0.9.0 brings in a second copy of target-spec
+        let duplicates: Vec<&VersionConflict> = version_conflicts
+            .iter()
+            .filter(|conflict| matches!(conflict, DuplicateIntroduced { .. }))
+            .collect();
+        assert_eq!(duplicates.len(), 1);
+        match duplicates[0] {
+            DuplicateIntroduced {
+                name,
+                prior_copies,
+                copies,
+            } => {
                 assert_eq!(name, "target-spec");
+                assert_eq!(*prior_copies, 1);
+                let versions: Vec<String> =
+                    copies.iter().map(|copy| copy.version.to_string()).collect();
+                assert_eq!(versions, vec!["0.6.1", "0.7.0"]);
+                assert!(copies
+                    .iter()
+                    .all(|copy| copy.workspace_members == vec!["valid_dep"]));
             }
+            _ => unreachable!(),
         }
+
+        assert!(!version_conflicts.iter().any(|conflict| matches!(
+            conflict,
+            LinksCollision { .. } | RegistryAndGitSources { .. }
+        )));
     }
 
     #[test]
@@ -1252,12 +3346,6 @@ mod test {
         println!("{:?}", files_unsafe_change_stats);
 
         for report in &files_unsafe_change_stats {
-            if report.file == "src/main.rs" {
-                assert_eq!(
-                    report.unsafe_change_status,
-                    FileUnsafeCodeChangeStatus::Uncertain
-                );
-            }
             if report.file == "src/newanother.rs" {
                 assert_eq!(
                     report.unsafe_change_status,
@@ -1283,5 +3371,414 @@ mod test {
                 );
             }
         }
+
+        let version_diff_info = DIFF_ANALYZER
+            .get_git_source_version_diff_info(
+                name,
+                &repo,
+                &Version::parse("2.4.0").unwrap(),
+                &Version::parse("2.5.0").unwrap(),
+            )
+            .unwrap();
+        let files_unsafe_change_stats =
+            UpdateAnalyzer::analyze_unsafe_changes_in_diff(&version_diff_info).unwrap();
+
+        // A line within an unsafe block changes while the counter remains same
+        let report = files_unsafe_change_stats
+            .iter()
+            .find(|stat| stat.file == "src/main.rs")
+            .unwrap();
+        assert_eq!(
+            report.unsafe_change_status,
+            FileUnsafeCodeChangeStatus::UnsafeCodeModified
+        );
+        assert!(report
+            .unsafe_item_changes
+            .iter()
+            .any(|change| change.change_type == UnsafeItemChangeType::Modified));
+    }
+
+    #[test]
+    fn test_update_unsafe_change_status_not_analyzed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(
+            &path,
+            "pub fn deref(p: *const u8) -> u8 {\n    unsafe { *p }\n}\n",
+        )
+        .unwrap();
+        let rs_file_metrics =
+            geiger::find::find_unsafe_in_file(&path, geiger::IncludeTests::No).ok();
+        assert!(rs_file_metrics.as_ref().unwrap().counters.has_unsafe());
+        let unsafe_delta = UpdateAnalyzer::get_unsafe_delta_from_rs_file_metrics(&None);
+
+        assert_eq!(
+            UpdateAnalyzer::get_file_unsafe_change_status(&rs_file_metrics, &unsafe_delta, None),
+            FileUnsafeCodeChangeStatus::UnsafeCodeNotAnalyzed
+        );
+        assert_eq!(
+            UpdateAnalyzer::get_file_unsafe_change_status(
+                &rs_file_metrics,
+                &unsafe_delta,
+                Some(&[])
+            ),
+            FileUnsafeCodeChangeStatus::UnsafeCodeUnchanged
+        );
+    }
+
+    #[test]
+    fn test_update_ownership_report() {
+        let get_version_source_info = |version: &str, repository: &str| VersionSourceInfo {
+            version: Version::parse(version).unwrap(),
+            repository: Some(repository.to_string()),
+            build_script_paths: HashSet::new(),
+            build_time_targets: BTreeSet::new(),
+            git_source: None,
+            registry_index: Some(CRATES_IO_INDEX.to_string()),
+        };
+        let publishers: Vec<VersionPublisher> = vec![
+            ("1.0.0", Some("alice")),
+            ("1.1.0", Some("alice")),
+            ("1.1.1", Some("mallory")),
+            ("1.2.0", Some("mallory")),
+            ("1.3.0", Some("bob")),
+        ]
+        .into_iter()
+        .map(|(version, publisher)| VersionPublisher {
+            version: Version::parse(version).unwrap(),
+            publisher: publisher.map(|publisher| publisher.to_string()),
+        })
+        .collect();
+        let owners: Vec<String> = vec![
+            "alice".to_string(),
+            "bob".to_string(),
+            "mallory".to_string(),
+        ];
+
+        let report = UpdateAnalyzer::get_ownership_report_from_publishers(
+            &get_version_source_info("1.1.0", "https://github.com/alice/foo"),
+            &get_version_source_info("1.2.0", "https://github.com/mallory/foo"),
+            &publishers,
+            owners.clone(),
+        );
+        assert_eq!(report.prior_publisher.as_deref(), Some("alice"));
+        assert_eq!(report.updated_publisher.as_deref(), Some("mallory"));
+        assert!(report.is_publisher_changed());
+        // bob only published after the updated version
+        assert_eq!(report.new_owners, vec!["mallory"]);
+        assert!(report.is_repository_changed());
+        assert!(report.has_changes());
+
+        let report = UpdateAnalyzer::get_ownership_report_from_publishers(
+            &get_version_source_info("1.1.1", "https://github.com/alice/foo"),
+            &get_version_source_info("1.2.0", "https://github.com/alice/foo.git"),
+            &publishers,
+            owners,
+        );
+        assert!(!report.is_publisher_changed());
+        assert!(report.new_owners.is_empty());
+        assert!(!report.is_repository_changed());
+        assert!(!report.has_changes());
+    }
+
+    #[test]
+    fn test_update_get_git_source_info() {
+        let source = SummarySource::External {
+            source: "git+https://github.com/diem/diem?rev=1d8ea1b#1d8ea1b6bdc8b5b8b4d9f5f1c8e1b2a3c4d5e6f7"
+                .to_string(),
+        };
+        assert_eq!(
+            UpdateAnalyzer::get_git_source_info(&source).unwrap(),
+            GitSourceInfo {
+                repository: "https://github.com/diem/diem".to_string(),
+                commit: "1d8ea1b6bdc8b5b8b4d9f5f1c8e1b2a3c4d5e6f7".to_string(),
+            }
+        );
+
+        let source = SummarySource::External {
+            source: "registry+https://github.com/rust-lang/crates.io-index".to_string(),
+        };
+        assert!(UpdateAnalyzer::get_git_source_info(&source).is_none());
+        assert!(UpdateAnalyzer::get_git_source_info(&SummarySource::CratesIo).is_none());
+    }
+
+    #[test]
+    fn test_update_is_source_change() {
+        let version = Version::parse("1.0.0").unwrap();
+        let git_source = |repository: &str, commit: &str| {
+            Some(GitSourceInfo {
+                repository: repository.to_string(),
+                commit: commit.to_string(),
+            })
+        };
+        let diem = git_source("https://github.com/diem/diem", "1d8ea1b");
+
+        // crates.io to git at the same version
+        assert!(UpdateAnalyzer::is_source_change(
+            (&version, &None),
+            (&version, &diem)
+        ));
+        // a fork of the git repository
+        assert!(UpdateAnalyzer::is_source_change(
+            (&version, &diem),
+            (
+                &version,
+                &git_source("https://github.com/fork/diem", "1d8ea1b")
+            )
+        ));
+        // another commit in the same repository is an update
+        assert!(!UpdateAnalyzer::is_source_change(
+            (&version, &diem),
+            (
+                &version,
+                &git_source("https://github.com/diem/diem", "51efd61")
+            )
+        ));
+        // a version bump along with the source is an update
+        assert!(!UpdateAnalyzer::is_source_change(
+            (&version, &None),
+            (&Version::parse("1.0.1").unwrap(), &diem)
+        ));
+    }
+
+    #[test]
+    fn test_update_get_registry_index() {
+        assert_eq!(
+            UpdateAnalyzer::get_registry_index(&SummarySource::CratesIo).unwrap(),
+            "https://github.com/rust-lang/crates.io-index"
+        );
+        let source = SummarySource::External {
+            source: "registry+https://git.example.com/index".to_string(),
+        };
+        assert_eq!(
+            UpdateAnalyzer::get_registry_index(&source).unwrap(),
+            "https://git.example.com/index"
+        );
+        let source = SummarySource::External {
+            source: "sparse+https://registry.example.com/index/".to_string(),
+        };
+        assert_eq!(
+            UpdateAnalyzer::get_registry_index(&source).unwrap(),
+            "sparse+https://registry.example.com/index/"
+        );
+        let source = SummarySource::External {
+            source: "git+https://github.com/diem/diem?rev=1d8ea1b#1d8ea1b6bdc8b5b8b4d9f5f1c8e1b2a3c4d5e6f7"
+                .to_string(),
+        };
+        assert!(UpdateAnalyzer::get_registry_index(&source).is_none());
+    }
+
+    #[test]
+    fn test_update_registry_without_api() {
+        let mut routes: HashMap<String, Vec<u8>> = HashMap::new();
+        routes.insert(
+            "/index/config.json".to_string(),
+            br#"{"dl": "https://registry.example.com/dl"}"#.to_vec(),
+        );
+        let url = serve_http(routes);
+        let registry = RegistryResolver::new(CargoConfig::default())
+            .unwrap()
+            .resolve(&format!("sparse+{}/index/", url))
+            .unwrap();
+        assert!(!registry.has_api());
+
+        // An index-only registry has no stats, but does not fail the review
+        let version = Version::parse("1.0.0").unwrap();
+        let registry = Some(registry);
+        assert!(UpdateAnalyzer::get_version_downloads(&registry, "internal", &version).is_none());
+        assert!(UpdateAnalyzer::get_crate_versions(&registry, "internal").is_none());
+
+        let version_source_info = VersionSourceInfo {
+            version,
+            repository: None,
+            build_script_paths: HashSet::new(),
+            build_time_targets: BTreeSet::new(),
+            git_source: None,
+            registry_index: Some(format!("sparse+{}/index/", url)),
+        };
+        assert!(UpdateAnalyzer::get_ownership_report(
+            &registry,
+            &None,
+            "internal",
+            &version_source_info,
+            &version_source_info
+        )
+        .is_none());
+    }
+
+    fn commit_all(repo: &git2::Repository, message: &str) -> String {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("user", "email@domain.com").unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+        .to_string()
+    }
+
+    // Reviews a crate hosted in a subdirectory of a git repository
+    // between a commit with the old files and one with the new files written over
+    fn get_git_commit_version_diff_review(
+        old_files: &[(&str, &[u8])],
+        new_files: &[(&str, &[u8])],
+    ) -> VersionDiffReview {
+        let dir = tempfile::tempdir().unwrap();
+        let crate_dir = dir.path().join("gitdep");
+        let write_files = |files: &[(&str, &[u8])]| {
+            for (path, content) in files {
+                let path = crate_dir.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, content).unwrap();
+            }
+        };
+        write_files(&[(
+            "Cargo.toml",
+            b"[package]\nname = \"gitdep\"\nversion = \"0.1.0\"\n",
+        )]);
+        write_files(old_files);
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let commit_a = commit_all(&repo, "a");
+        write_files(new_files);
+        let commit_b = commit_all(&repo, "b");
+
+        let repository = dir.path().to_str().unwrap().to_string();
+        let get_version_source_info = |commit: &str| VersionSourceInfo {
+            version: Version::parse("0.1.0").unwrap(),
+            repository: None,
+            build_script_paths: vec!["build.rs".to_string()].into_iter().collect(),
+            build_time_targets: vec![BuildTimeTarget {
+                kind: BuildTimeTargetKind::BuildScript,
+                path: "build.rs".to_string(),
+            }]
+            .into_iter()
+            .collect(),
+            git_source: Some(GitSourceInfo {
+                repository: repository.clone(),
+                commit: commit.to_string(),
+            }),
+            registry_index: None,
+        };
+        let dep_change_info = DependencyChangeInfo {
+            name: "gitdep".to_string(),
+            dep_type: DependencyType::Host,
+            old_version_info: Some(get_version_source_info(&commit_a)),
+            new_version_info: Some(get_version_source_info(&commit_b)),
+        };
+
+        UpdateAnalyzer::analyze_version_diff(
+            &DiffAnalyzer::new().unwrap(),
+            &dep_change_info,
+            &None,
+            &None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_update_git_commit_version_diff() {
+        let version_diff_review = get_git_commit_version_diff_review(
+            &[
+                ("build.rs", b"fn main() {}"),
+                ("src/lib.rs", b"pub fn a() {}"),
+            ],
+            &[
+                (
+                    "build.rs",
+                    b"fn main() { println!(\"cargo:rerun-if-changed=build.rs\"); }",
+                ),
+                (
+                    "src/lib.rs",
+                    b"pub fn a() { unsafe { std::ptr::null::<u8>().read(); } }",
+                ),
+            ],
+        );
+        let diff_stats = version_diff_review.diff_stats.unwrap();
+        let files: HashSet<String> = vec!["build.rs".to_string(), "src/lib.rs".to_string()]
+            .into_iter()
+            .collect();
+        assert_eq!(diff_stats.files_changed, files);
+        assert_eq!(diff_stats.rust_files_changed, 2);
+        assert_eq!(
+            diff_stats.modified_build_scripts,
+            vec!["build.rs".to_string()].into_iter().collect()
+        );
+        assert_eq!(diff_stats.unsafe_file_changed.len(), 1);
+        assert_eq!(diff_stats.unsafe_file_changed[0].file, "src/lib.rs");
+        assert_eq!(
+            diff_stats.unsafe_file_changed[0].unsafe_change_status,
+            FileUnsafeCodeChangeStatus::UnsafeCounterModified
+        );
+    }
+
+    #[test]
+    fn test_update_git_commit_semver_check() {
+        let version_diff_review = get_git_commit_version_diff_review(
+            &[("src/lib.rs", b"pub fn a() {}\npub fn b() {}")],
+            &[("src/lib.rs", b"pub fn a() {}")],
+        );
+
+        // pub fn b is removed without a version bump
+        let semver_check_report = version_diff_review.semver_check_report.unwrap();
+        let violations = semver_check_report.get_violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "b");
+        assert_eq!(violations[0].change_type, ApiChangeType::Removed);
+    }
+
+    #[test]
+    fn test_update_git_commit_build_scan() {
+        let version_diff_review = get_git_commit_version_diff_review(
+            &[
+                ("build.rs", b"fn main() {}"),
+                ("src/lib.rs", b"pub fn a() {}"),
+            ],
+            &[(
+                "build.rs",
+                b"fn main() {\n    std::process::Command::new(\"curl\").status().unwrap();\n}",
+            )],
+        );
+
+        // The new build script runs a process
+        let build_scan_report = version_diff_review.build_scan_report.unwrap();
+        assert_eq!(build_scan_report.scanned_files, vec!["build.rs"]);
+        assert_eq!(build_scan_report.findings.len(), 1);
+        assert_eq!(
+            build_scan_report.findings[0].behaviour,
+            BuildBehaviour::ProcessCommand
+        );
+        assert_eq!(build_scan_report.findings[0].line, 2);
+    }
+
+    #[test]
+    fn test_update_git_commit_native_artifacts() {
+        let version_diff_review = get_git_commit_version_diff_review(
+            &[("src/lib.rs", b"pub fn a() {}")],
+            &[
+                // a prebuilt library and vendored C code get added
+                ("libfoo.so", b"\x7fELF\x02\x01\x01\0"),
+                ("vendor/foo/foo.c", b"int foo() { return 0; }"),
+                ("vendor/foo/foo.h", b"int foo();"),
+            ],
+        );
+        let diff_stats = version_diff_review.diff_stats.unwrap();
+        let native_artifacts = &diff_stats.native_artifacts;
+        assert_eq!(native_artifacts.binary_files.len(), 1);
+        assert_eq!(native_artifacts.binary_files[0].path, "libfoo.so");
+        assert_eq!(native_artifacts.binary_files[0].format, BinaryFormat::Elf);
+        assert_eq!(native_artifacts.binary_files[0].size, 8);
+        assert_eq!(native_artifacts.vendored_native_dirs.len(), 1);
+        assert_eq!(native_artifacts.vendored_native_dirs[0].path, "vendor/foo");
+        assert_eq!(native_artifacts.vendored_native_dirs[0].files, 2);
     }
 }